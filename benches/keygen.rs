@@ -0,0 +1,25 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use blake3_lamport_signatures::merkle;
+
+// Run with and without `--features rayon` to compare the serial and
+// parallel key generation paths.
+const PATH: &str = if cfg!(feature = "rayon") {
+    "parallel"
+} else {
+    "serial"
+};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle keygen");
+    group.sample_size(10);
+    for n in [1000, 10000] {
+        group.bench_with_input(BenchmarkId::new(PATH, n), &n, |b, &n| {
+            b.iter(|| merkle::PrivateKey::generate(n).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
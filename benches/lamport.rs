@@ -0,0 +1,23 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use blake3_lamport_signatures::lamport;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let private_key = lamport::PrivateKey::generate().unwrap();
+    let public_key = private_key.public_key();
+    let message = b"Yeah, I said it";
+    let signature = private_key.sign(message);
+
+    c.bench_function("lamport keygen", |b| {
+        b.iter(|| lamport::PrivateKey::generate().unwrap().public_key())
+    });
+    c.bench_function("lamport public key", |b| {
+        b.iter(|| black_box(&private_key).public_key())
+    });
+    c.bench_function("lamport verify", |b| {
+        b.iter(|| public_key.verify(black_box(message), black_box(&signature)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
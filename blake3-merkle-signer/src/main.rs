@@ -1,10 +1,11 @@
+use blake3_lamport_signatures::state::{AnchorError, AnchoredStore, FileStore, SigningError};
 use blake3_lamport_signatures::{lamport, merkle};
 
 use clap::{Parser, Subcommand};
 use std::{
     fs::File,
-    io::{BufReader, BufWriter, Read, Write},
-    path::PathBuf,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 
 #[derive(Parser, Debug)]
@@ -24,12 +25,58 @@ enum Command {
         message: PathBuf,
         private_key: PathBuf,
         signature: PathBuf,
+        /// The file recording the last index signed with, kept away from
+        /// the private key so that restoring a backup of the key directory
+        /// cannot roll it back.
+        #[clap(long)]
+        anchor: Option<PathBuf>,
     },
     Verify {
         message: PathBuf,
         signature: PathBuf,
         public_key: PathBuf,
     },
+    /// Checks every signature in a directory against a public key, reporting
+    /// any leaf which signed more than one message. Fails if any leaf was
+    /// reused, or if any file is not a signature at all.
+    Audit {
+        directory: PathBuf,
+        public_key: PathBuf,
+    },
+}
+
+/// The file holding the index of the next unused key, beside the private
+/// key, which is reserved before every signature is written.
+pub fn state_path(private_key: &Path) -> PathBuf {
+    let mut path = private_key.as_os_str().to_owned();
+    path.push(".state");
+    path.into()
+}
+
+/// The file holding the Merkle tree of the private key, beside it, which
+/// is memory-mapped rather than rebuilt from every key.
+pub fn tree_path(private_key: &Path) -> PathBuf {
+    let mut path = private_key.as_os_str().to_owned();
+    path.push(".tree");
+    path.into()
+}
+
+/// The default anchor of the key with `fingerprint`, in the user's state
+/// directory rather than beside the key.
+pub fn anchor_path(fingerprint: &impl std::fmt::Display) -> std::io::Result<PathBuf> {
+    let directory = match (std::env::var_os("XDG_STATE_HOME"), std::env::var_os("HOME")) {
+        (Some(state), _) if !state.is_empty() => PathBuf::from(state),
+        (_, Some(home)) => Path::new(&home).join(".local/state"),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no state directory to keep the anchor in, pass --anchor",
+            ))
+        }
+    }
+    .join("blake3-lamport-signer");
+    std::fs::create_dir_all(&directory)?;
+    Ok(directory.join(format!("{fingerprint}.anchor")))
 }
 
 pub fn read_message(file: PathBuf) -> std::io::Result<Vec<u8>> {
@@ -41,65 +88,158 @@ pub fn read_message(file: PathBuf) -> std::io::Result<Vec<u8>> {
 }
 
 pub fn read_public_key(file: PathBuf) -> std::io::Result<merkle::PublicKey> {
-    let f = File::options().read(true).open(file)?;
-    let mut reader = BufReader::new(f);
-    let mut buf = [0u8; 40];
-    reader.read(&mut buf)?;
-
-    Ok(buf.into())
+    let public_key_bytes = std::fs::read(file)?;
+    merkle::PublicKey::from_bytes(&public_key_bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
 }
 
+/// Reads every Lamport key of a private key file and the index of the next
+/// unused one, failing on a file whose length is not that of a key file.
 pub fn read_private_key(file: PathBuf) -> std::io::Result<merkle::PrivateKey> {
-    let f = File::options().read(true).open(file)?;
+    let current_index = read_current_index(&file)?;
+    let f = File::open(&file)?;
+    let count = (f.metadata()?.len() - 8) / 16384;
     let mut reader = BufReader::new(f);
     let mut buf = [0u8; 16384];
     let mut private_keys: Vec<lamport::PrivateKey> = vec![];
-    while let Ok(private_key_length) = reader.read(&mut buf) {
-        if private_key_length != 16384 {
-            if private_key_length == 8 {
-                let mut inner_buf = [0u8; 8];
-                for i in 0..8 {
-                    inner_buf[i] = buf[i];
-                }
-                let current_index = u64::from_be_bytes(inner_buf) as usize;
-                return Ok((private_keys, current_index).into());
-            }
-        }
+    for _ in 0..count {
+        reader.read_exact(&mut buf)?;
         private_keys.push((&buf).into());
     }
-    panic!("fucko");
+    (private_keys, current_index as usize)
+        .try_into()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Reads only the Lamport key at `index` of a private key file.
+pub fn read_lamport_key(file: &Path, index: u64) -> std::io::Result<lamport::PrivateKey> {
+    let mut f = File::open(file)?;
+    f.seek(SeekFrom::Start(index * 16384))?;
+    let mut buf = [0u8; 16384];
+    f.read_exact(&mut buf)?;
+    Ok((&buf).into())
+}
+
+/// Reads the index of the next unused key from the 8-byte trailer of a
+/// private key file. Keys which signed before the state file existed only
+/// recorded their progress there.
+pub fn read_current_index(file: &Path) -> std::io::Result<u64> {
+    let mut f = File::open(file)?;
+    let length = f.metadata()?.len();
+    if length < 8 || (length - 8) % 16384 != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} is not a private key file", file.display()),
+        ));
+    }
+    f.seek(SeekFrom::End(-8))?;
+    let mut buf = [0u8; 8];
+    f.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+/// Overwrites the Lamport key at `index` of a private key file with zeros
+/// once it has signed, so that a later leak of the file cannot forge
+/// signatures with it.
+pub fn erase_lamport_key(file: &Path, index: u64) -> std::io::Result<()> {
+    let mut f = File::options().write(true).open(file)?;
+    f.seek(SeekFrom::Start(index * 16384))?;
+    f.write_all(&[0u8; 16384])?;
+    f.sync_data()
+}
+
+/// Replaces the file at `path` with `bytes` without ever changing it in
+/// place: they are written to a temporary file beside it and fsynced, the
+/// temporary file is renamed over it, and the directory is fsynced. Signers
+/// which have the old file mapped keep reading it unchanged, and a crash
+/// leaves either the old file or the new one.
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let mut f = File::create(&temporary_path)?;
+    f.write_all(bytes)?;
+    f.sync_all()?;
+    drop(f);
+    std::fs::rename(&temporary_path, path)?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    #[cfg(unix)]
+    File::open(parent)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = parent;
+    Ok(())
+}
+
+/// Maps the tree stored beside a private key, first writing it from the
+/// keys for private keys made before trees were stored, or whose tree was
+/// left unreadable.
+pub fn open_tree(private_key: &Path) -> std::io::Result<merkle::StoredTree> {
+    let path = tree_path(private_key);
+    match merkle::StoredTree::open(&path) {
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::InvalidData
+            ) => {}
+        opened => return opened,
+    }
+    let privk = read_private_key(private_key.to_path_buf())?;
+    // erased keys no longer hash to their leaves
+    if privk
+        .inner_keys()
+        .iter()
+        .any(|key| key.encode()[1..].iter().all(|&byte| byte == 0))
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "the tree of this key is missing or unreadable and cannot be rebuilt from erased keys",
+        ));
+    }
+    write_atomically(&path, &privk.tree_to_bytes())?;
+    merkle::StoredTree::open(path)
 }
 
 pub fn read_signature(file: PathBuf) -> std::io::Result<merkle::Signature> {
-    let mut signature_bytes = Vec::new();
-    let mut f: File = File::options().read(true).open(file)?;
-    f.read_to_end(&mut signature_bytes)?;
-    let signature_bytes_ref: &[u8] = &signature_bytes;
-    Ok(signature_bytes_ref.try_into().unwrap())
+    let signature_bytes = std::fs::read(file)?;
+    merkle::Signature::from_bytes(&signature_bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
 }
 
 pub fn write_signature(signature: &merkle::Signature, file: PathBuf) -> std::io::Result<()> {
-    let mut f: File = File::options().create(true).write(true).open(file)?;
-    f.write_all(&Vec::from(signature))?;
+    let mut f: File = File::options()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(file)?;
+    f.write_all(&signature.to_bytes())?;
     Ok(())
 }
 
 pub fn write_private_key(private_key: merkle::PrivateKey, file: PathBuf) -> std::io::Result<()> {
-    let f = File::options().create(true).write(true).open(file)?;
+    let f = File::options()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(file)?;
     let mut writer = BufWriter::new(f);
     for private_key in private_key.inner_keys() {
         let buf: [u8; 16384] = private_key.into();
-        writer.write(&buf)?;
+        writer.write_all(&buf)?;
     }
-    writer.write(&(private_key.current_index() as u64).to_be_bytes())?;
+    writer.write_all(&(private_key.current_index() as u64).to_be_bytes())?;
     Ok(())
 }
 
 pub fn write_public_key(public_key: merkle::PublicKey, file: PathBuf) -> std::io::Result<()> {
-    let f = File::options().create(true).write(true).open(file)?;
+    let f = File::options()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(file)?;
     let mut writer = BufWriter::new(f);
-    let buf: [u8; 40] = public_key.into();
-    writer.write(&buf)?;
+    writer.write_all(&public_key.to_bytes())?;
     Ok(())
 }
 
@@ -112,30 +252,62 @@ fn main() -> std::io::Result<()> {
             public_key,
             num_messages,
         } => {
-            let privk = merkle::PrivateKey::generate(num_messages).unwrap();
+            let privk =
+                merkle::PrivateKey::generate(num_messages).map_err(std::io::Error::other)?;
             let pubk = privk.public_key();
+            println!("public key fingerprint: {}", pubk.fingerprint());
+            // a state file left by an earlier key at this path would burn
+            // leaves of the new one
+            match std::fs::remove_file(state_path(&private_key)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            write_atomically(&tree_path(&private_key), &privk.tree_to_bytes())?;
             write_private_key(privk, private_key)?;
             write_public_key(pubk, public_key)?;
         }
         Sign {
             message,
             private_key,
-            signature,
+            signature: signature_path,
+            anchor,
         } => {
-            let mut privk = read_private_key(private_key.clone())?;
+            // the store starts out empty for keys which signed before it
+            // existed, and only their key file knows which leaves are used
+            let current_index = read_current_index(&private_key)?;
+            let tree = open_tree(&private_key)?;
+            let key_id = tree.public_key().fingerprint();
+            let anchor = match anchor {
+                Some(anchor) => anchor,
+                None => anchor_path(&key_id)?,
+            };
+            let mut store = AnchoredStore::new(
+                FileStore::new(state_path(&private_key)),
+                FileStore::new(anchor),
+            );
             let message = read_message(message)?;
-            let mut signature_file = File::options()
-                .create(true)
-                .write(true)
-                .open(signature)
-                .expect("open signature file");
-            if let Some(signature) = privk.sign(&message) {
-                let signature_vec: Vec<u8> = (&signature).into();
-                let signature_vec_bytes: &[u8] = &signature_vec;
-                signature_file.write_all(signature_vec_bytes)?;
-                write_private_key(privk, private_key)?;
-            } else {
-                eprintln!("ran out of signatures for this private key");
+            match tree.reserve_from(&mut store, current_index, 1) {
+                Ok(mut reservation) => {
+                    let index = reservation.next_index();
+                    let lamport_key = read_lamport_key(&private_key, index)?;
+                    let signature = tree
+                        .sign(&mut reservation, &lamport_key, &message)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                    std::fs::write(signature_path, signature.to_bytes_with_key_id(&key_id))?;
+                    erase_lamport_key(&private_key, index)?;
+                }
+                Err(SigningError::Exhausted) => {
+                    eprintln!("ran out of signatures for this private key");
+                }
+                Err(SigningError::Store(AnchorError::RolledBack { stored, anchored })) => {
+                    return Err(std::io::Error::other(format!(
+                        "refusing to sign: the signing state was restored to index {stored} \
+                         after index {anchored} was recorded, so leaves may be reused"
+                    )));
+                }
+                Err(SigningError::Store(AnchorError::Store(e) | AnchorError::Anchor(e))) => {
+                    return Err(e)
+                }
             }
         }
         Verify {
@@ -148,6 +320,61 @@ fn main() -> std::io::Result<()> {
             let message = read_message(message)?;
             println!("signature validity: {}", pubk.verify(message, &signature));
         }
+        Audit {
+            directory,
+            public_key,
+        } => {
+            let pubk = read_public_key(public_key)?;
+            let mut paths = std::fs::read_dir(directory)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            paths.sort();
+            let mut tracker = merkle::IndexTracker::new();
+            let mut first_seen = std::collections::HashMap::new();
+            let mut reused = 0;
+            let mut unreadable = 0;
+            for path in paths.into_iter().filter(|path| path.is_file()) {
+                // a signature which cannot be read may be hiding a reuse
+                let signature = match read_signature(path.clone()) {
+                    Ok(signature) => signature,
+                    Err(e) => {
+                        unreadable += 1;
+                        eprintln!("{}: not a readable signature: {e}", path.display());
+                        continue;
+                    }
+                };
+                match tracker.record(&pubk, &signature) {
+                    Ok(()) => {
+                        first_seen.entry(signature.index()).or_insert(path);
+                    }
+                    Err(merkle::TrackingError::Invalid) => {
+                        eprintln!("{}: not a signature by this key", path.display());
+                    }
+                    Err(merkle::TrackingError::Reused { index }) => {
+                        reused += 1;
+                        println!(
+                            "leaf {index} signed different messages in {} and {}",
+                            first_seen[&index].display(),
+                            path.display()
+                        );
+                    }
+                }
+            }
+            println!(
+                "leaves seen: {}, leaves reused: {reused}, unreadable files: {unreadable}",
+                tracker.count(&pubk)
+            );
+            if reused > 0 {
+                return Err(std::io::Error::other(
+                    "this key has signed with a leaf more than once and must be revoked",
+                ));
+            }
+            if unreadable > 0 {
+                return Err(std::io::Error::other(format!(
+                    "the audit is incomplete, since {unreadable} of the files are not readable signatures"
+                )));
+            }
+        }
     }
     Ok(())
 }
@@ -0,0 +1,286 @@
+use crate::wire::{self, Framed};
+
+/// The BLAKE3 key derivation context fingerprints are computed under, which
+/// separates them from every other use of BLAKE3 in this crate.
+const CONTEXT: &str = "blake3-lamport-signatures 2026-10-18 public key fingerprint";
+
+/// The human-readable part of an encoded fingerprint, before the `1`.
+pub const PREFIX: &str = "b3ls";
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_LEN: usize = 6;
+/// 256 bits in 5-bit groups, the last padded with four zero bits.
+const DATA_LEN: usize = 52;
+const BECH32M_CONSTANT: u32 = 0x2bc8_30a3;
+
+/// A short, stable identifier of a public key: a domain-separated BLAKE3
+/// digest of its framed encoding, so keys of different schemes or
+/// parameter sets never share one.
+///
+/// It displays in the bech32m encoding of BIP 350 with the prefix `b3ls`,
+/// as in `b3ls1qqsz...`, whose checksum catches any single typo and any
+/// transposition of neighbouring characters. [`str::parse`] reads it back
+/// in either case, rejecting anything that does not check out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint([u8; 32]);
+
+impl Fingerprint {
+    /// The fingerprint of `key`, whose framed encoding names its scheme and
+    /// parameter set.
+    pub(crate) fn of<T: Framed>(key: &T) -> Fingerprint {
+        Fingerprint(blake3::derive_key(CONTEXT, &wire::to_bytes(key)))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for Fingerprint {
+    fn from(bytes: [u8; 32]) -> Self {
+        Fingerprint(bytes)
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let data = to_groups(&self.0);
+        let checksum = checksum(&data);
+        write!(f, "{PREFIX}1")?;
+        for group in data.iter().chain(checksum.iter()) {
+            write!(f, "{}", CHARSET[*group as usize] as char)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Fingerprint {
+    type Err = DecodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let has_lower = s.chars().any(|c| c.is_ascii_lowercase());
+        let has_upper = s.chars().any(|c| c.is_ascii_uppercase());
+        if has_lower && has_upper {
+            return Err(DecodingError::MixedCase);
+        }
+        let s = s.to_ascii_lowercase();
+        let data = s
+            .strip_prefix(PREFIX)
+            .and_then(|rest| rest.strip_prefix('1'))
+            .ok_or(DecodingError::WrongPrefix)?;
+        if data.len() != DATA_LEN + CHECKSUM_LEN {
+            return Err(DecodingError::InvalidLength { length: s.len() });
+        }
+        let groups = data
+            .bytes()
+            .enumerate()
+            .map(|(i, c)| {
+                CHARSET
+                    .iter()
+                    .position(|&d| d == c)
+                    .map(|group| group as u8)
+                    .ok_or(DecodingError::InvalidCharacter {
+                        offset: PREFIX.len() + 1 + i,
+                    })
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        if polymod(&groups) != BECH32M_CONSTANT {
+            return Err(DecodingError::BadChecksum);
+        }
+        from_groups(&groups[..DATA_LEN])
+            .map(Fingerprint)
+            .ok_or(DecodingError::BadChecksum)
+    }
+}
+
+/// Splits `bytes` into 5-bit groups, most significant bits first.
+fn to_groups(bytes: &[u8; 32]) -> [u8; DATA_LEN] {
+    let mut groups = [0u8; DATA_LEN];
+    for (i, group) in groups.iter_mut().enumerate() {
+        let bit = i * 5;
+        let window = u16::from_be_bytes([bytes[bit / 8], *bytes.get(bit / 8 + 1).unwrap_or(&0)]);
+        *group = (window >> (11 - bit % 8)) as u8 & 31;
+    }
+    groups
+}
+
+/// Joins 5-bit groups back into bytes, or [`None`] if the padding bits
+/// are not zero.
+fn from_groups(groups: &[u8]) -> Option<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    let (mut accumulator, mut bits, mut out) = (0u32, 0, 0);
+    for &group in groups {
+        accumulator = accumulator << 5 | group as u32;
+        bits += 5;
+        if bits >= 8 && out < 32 {
+            bits -= 8;
+            bytes[out] = (accumulator >> bits) as u8;
+            out += 1;
+        }
+    }
+    (accumulator & ((1 << bits) - 1) == 0).then_some(bytes)
+}
+
+/// The bech32 checksum polynomial over the prefix and `groups`.
+fn polymod(groups: &[u8]) -> u32 {
+    const GENERATORS: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let prefix = PREFIX.bytes();
+    let expanded = prefix
+        .clone()
+        .map(|c| c >> 5)
+        .chain([0])
+        .chain(prefix.map(|c| c & 31))
+        .chain(groups.iter().copied());
+    expanded.fold(1u32, |checksum, value| {
+        let top = checksum >> 25;
+        let checksum = (checksum & 0x01ff_ffff) << 5 ^ value as u32;
+        GENERATORS
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 == 1)
+            .fold(checksum, |checksum, (_, generator)| checksum ^ generator)
+    })
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut padded = data.to_vec();
+    padded.extend([0; CHECKSUM_LEN]);
+    let remainder = polymod(&padded) ^ BECH32M_CONSTANT;
+    std::array::from_fn(|i| (remainder >> (5 * (CHECKSUM_LEN - 1 - i))) as u8 & 31)
+}
+
+/// An error encountered while parsing a [`Fingerprint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodingError {
+    /// The input does not start with `b3ls1`.
+    WrongPrefix,
+    /// The input mixes upper and lower case letters.
+    MixedCase,
+    /// The input is `length` characters long rather than 63.
+    InvalidLength { length: usize },
+    /// The character at `offset` is not in the bech32 alphabet.
+    InvalidCharacter { offset: usize },
+    /// The checksum does not match, so the input has a typo.
+    BadChecksum,
+}
+
+impl std::fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodingError::WrongPrefix => write!(f, "fingerprint does not start with {PREFIX}1"),
+            DecodingError::MixedCase => write!(f, "fingerprint mixes upper and lower case"),
+            DecodingError::InvalidLength { length } => {
+                write!(f, "fingerprint is {length} characters long, not 63")
+            }
+            DecodingError::InvalidCharacter { offset } => {
+                write!(f, "invalid fingerprint character at offset {offset}")
+            }
+            DecodingError::BadChecksum => write!(f, "fingerprint checksum mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lamport, merkle};
+
+    #[test]
+    fn round_trips() {
+        for bytes in [[0u8; 32], [0xffu8; 32], *blake3::hash(b"key").as_bytes()] {
+            let fingerprint = Fingerprint::from(bytes);
+            let encoded = fingerprint.to_string();
+            assert_eq!(encoded.len(), 63);
+            assert!(encoded.starts_with("b3ls1"));
+            assert_eq!(encoded.parse::<Fingerprint>(), Ok(fingerprint));
+            assert_eq!(
+                encoded.to_uppercase().parse::<Fingerprint>(),
+                Ok(fingerprint)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_bech32m() {
+        // computed with the reference implementation from BIP 350
+        assert_eq!(
+            Fingerprint::from([7u8; 32]).to_string(),
+            "b3ls1qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurst8307m"
+        );
+    }
+
+    #[test]
+    fn rejects_typos() {
+        let encoded = Fingerprint::from([7u8; 32]).to_string();
+        for offset in 5..encoded.len() {
+            for &c in CHARSET.iter() {
+                let mut typo = encoded.clone().into_bytes();
+                if typo[offset] == c {
+                    continue;
+                }
+                typo[offset] = c;
+                let typo = String::from_utf8(typo).unwrap();
+                assert_eq!(typo.parse::<Fingerprint>(), Err(DecodingError::BadChecksum));
+            }
+        }
+        for offset in 5..encoded.len() - 1 {
+            let mut swapped = encoded.clone().into_bytes();
+            if swapped[offset] == swapped[offset + 1] {
+                continue;
+            }
+            swapped.swap(offset, offset + 1);
+            let swapped = String::from_utf8(swapped).unwrap();
+            assert_eq!(
+                swapped.parse::<Fingerprint>(),
+                Err(DecodingError::BadChecksum)
+            );
+        }
+
+        let mut mixed = encoded.clone();
+        mixed.replace_range(..1, "B");
+        assert_eq!(mixed.parse::<Fingerprint>(), Err(DecodingError::MixedCase));
+        assert_eq!(
+            encoded.replacen("b3ls", "b3lx", 1).parse::<Fingerprint>(),
+            Err(DecodingError::WrongPrefix)
+        );
+        assert_eq!(
+            encoded[..62].parse::<Fingerprint>(),
+            Err(DecodingError::InvalidLength { length: 62 })
+        );
+        let mut unknown = encoded.clone();
+        unknown.replace_range(10..11, "o");
+        assert_eq!(
+            unknown.parse::<Fingerprint>(),
+            Err(DecodingError::InvalidCharacter { offset: 10 })
+        );
+    }
+
+    #[test]
+    fn separates_keys() {
+        let lamport_key = lamport::PrivateKey::derive(&[1u8; 32], 0).public_key();
+        let merkle_key = merkle::PrivateKey::generate(2).unwrap().public_key();
+        assert_eq!(lamport_key.fingerprint(), lamport_key.clone().fingerprint());
+        assert_ne!(
+            lamport_key.fingerprint(),
+            lamport::PrivateKey::derive(&[1u8; 32], 1)
+                .public_key()
+                .fingerprint()
+        );
+        assert_ne!(merkle_key.fingerprint(), lamport_key.fingerprint());
+        // the same root under another hash function is another key
+        #[cfg(feature = "sha3")]
+        assert_ne!(
+            merkle_key.fingerprint(),
+            merkle::PublicKey::<crate::hash::Shake256>::from(merkle_key.to_raw_bytes())
+                .fingerprint()
+        );
+    }
+}
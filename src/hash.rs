@@ -0,0 +1,190 @@
+use blake3::Hash;
+
+/// A hash function which Lamport keys and Merkle trees can be built with.
+///
+/// Outputs of any length can be requested. Merkle tree nodes are always 32
+/// bytes long, and are held in a [`blake3::Hash`] for its constant time
+/// equality whichever function produced them.
+pub trait HashFunction:
+    Copy + std::fmt::Debug + Default + Eq + Ord + std::hash::Hash + Send + Sync + 'static
+{
+    /// The byte identifying this function in framed encodings.
+    const ID: u8;
+
+    /// Hashes the concatenation of `inputs`, filling `output`.
+    fn hash_into(inputs: &[&[u8]], output: &mut [u8]);
+
+    /// Hashes the concatenation of `inputs` under the secret `key`, filling
+    /// `output`. Used to derive secrets from seeds.
+    fn keyed_hash_into(key: &[u8; 32], inputs: &[&[u8]], output: &mut [u8]);
+
+    /// The 32-byte hash of the concatenation of `inputs`.
+    fn digest(inputs: &[&[u8]]) -> Hash {
+        let mut output = [0u8; 32];
+        Self::hash_into(inputs, &mut output);
+        Hash::from(output)
+    }
+}
+
+/// BLAKE3, using its extendable output and keyed modes. The default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Blake3;
+
+impl HashFunction for Blake3 {
+    const ID: u8 = 1;
+
+    fn hash_into(inputs: &[&[u8]], output: &mut [u8]) {
+        let mut hasher = blake3::Hasher::new();
+        for input in inputs {
+            hasher.update(input);
+        }
+        hasher.finalize_xof().fill(output);
+    }
+
+    fn keyed_hash_into(key: &[u8; 32], inputs: &[&[u8]], output: &mut [u8]) {
+        let mut hasher = blake3::Hasher::new_keyed(key);
+        for input in inputs {
+            hasher.update(input);
+        }
+        hasher.finalize_xof().fill(output);
+    }
+
+    fn digest(inputs: &[&[u8]]) -> Hash {
+        let mut hasher = blake3::Hasher::new();
+        for input in inputs {
+            hasher.update(input);
+        }
+        hasher.finalize()
+    }
+}
+
+/// SHA-256. Outputs of at most 32 bytes are truncated digests, while longer
+/// outputs are the digests of the input followed by a 32-bit big-endian
+/// counter, as in MGF1. Keyed hashes prefix the input with the key.
+#[cfg(feature = "sha2")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sha256;
+
+#[cfg(feature = "sha2")]
+impl HashFunction for Sha256 {
+    const ID: u8 = 2;
+
+    fn hash_into(inputs: &[&[u8]], output: &mut [u8]) {
+        use sha2::Digest;
+
+        let digest = |counter: Option<u32>| {
+            let mut hasher = sha2::Sha256::new();
+            for input in inputs {
+                hasher.update(input);
+            }
+            if let Some(counter) = counter {
+                hasher.update(counter.to_be_bytes());
+            }
+            hasher.finalize()
+        };
+        if output.len() <= 32 {
+            let len = output.len();
+            output.copy_from_slice(&digest(None)[..len]);
+            return;
+        }
+        for (chunk, counter) in output.chunks_mut(32).zip(0u32..) {
+            let len = chunk.len();
+            chunk.copy_from_slice(&digest(Some(counter))[..len]);
+        }
+    }
+
+    fn keyed_hash_into(key: &[u8; 32], inputs: &[&[u8]], output: &mut [u8]) {
+        let mut keyed = vec![&key[..]];
+        keyed.extend_from_slice(inputs);
+        Sha256::hash_into(&keyed, output);
+    }
+}
+
+/// SHAKE256, using its extendable output. Keyed hashes prefix the input
+/// with the key.
+#[cfg(feature = "sha3")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Shake256;
+
+#[cfg(feature = "sha3")]
+impl HashFunction for Shake256 {
+    const ID: u8 = 3;
+
+    fn hash_into(inputs: &[&[u8]], output: &mut [u8]) {
+        use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+        let mut hasher = sha3::Shake256::default();
+        for input in inputs {
+            hasher.update(input);
+        }
+        hasher.finalize_xof().read(output);
+    }
+
+    fn keyed_hash_into(key: &[u8; 32], inputs: &[&[u8]], output: &mut [u8]) {
+        let mut keyed = vec![&key[..]];
+        keyed.extend_from_slice(inputs);
+        Shake256::hash_into(&keyed, output);
+    }
+}
+
+/// A hash function picked at run time, by the typecode of an LMS or XMSS
+/// parameter set. Every output is 32 bytes long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Family {
+    #[cfg(feature = "sha2")]
+    Sha256,
+    Blake3,
+}
+
+impl Family {
+    pub(crate) fn hash(self, inputs: &[&[u8]]) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        match self {
+            #[cfg(feature = "sha2")]
+            Family::Sha256 => Sha256::hash_into(inputs, &mut output),
+            Family::Blake3 => Blake3::hash_into(inputs, &mut output),
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blake3_matches_the_crate() {
+        assert_eq!(
+            Blake3::digest(&[b"hello, ", b"world"]),
+            blake3::hash(b"hello, world")
+        );
+        let mut output = [0u8; 100];
+        Blake3::hash_into(&[b"hello, world"], &mut output);
+        assert_eq!(&output[..32], blake3::hash(b"hello, world").as_bytes());
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_known_answer() {
+        // FIPS 180-2, appendix B.1
+        assert_eq!(
+            Sha256::digest(&[b"ab", b"c"]).to_hex().as_str(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let mut output = [0u8; 40];
+        Sha256::hash_into(&[b"abc"], &mut output);
+        let mut first = [0u8; 32];
+        Sha256::hash_into(&[b"abc", &0u32.to_be_bytes()], &mut first);
+        assert_eq!(&output[..32], &first);
+    }
+
+    #[cfg(feature = "sha3")]
+    #[test]
+    fn shake256_known_answer() {
+        // FIPS 202 SHAKE256 of the empty string
+        assert_eq!(
+            Shake256::digest(&[]).to_hex().as_str(),
+            "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762f"
+        );
+    }
+}
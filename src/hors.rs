@@ -0,0 +1,607 @@
+use blake3::Hash;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+use crate::merkle::internal::{Proof, ProofDecodingError, Reader, Tree};
+use crate::merkle::DecodingLimits;
+use crate::stateless::read_bits;
+use crate::wire::{self, Algorithm, Framed, Kind};
+
+/// The shape of a HORS key: `2^log_t` secret values, `k` of which are
+/// revealed by every signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Parameters {
+    k: u16,
+    log_t: u8,
+}
+
+impl Parameters {
+    /// 32 of 2^16 values: 352 bits of security after one signature, none
+    /// left after 2^11.
+    pub const K32_T65536: Parameters = Parameters { k: 32, log_t: 16 };
+
+    /// 16 of 1024 values: small keys for a single signature, after which
+    /// 96 bits of security remain.
+    pub const K16_T1024: Parameters = Parameters { k: 16, log_t: 10 };
+
+    /// Returns [`None`] unless `1 <= k <= 2^log_t` and `1 <= log_t <= 20`.
+    pub fn new(k: u16, log_t: u8) -> Option<Parameters> {
+        if log_t == 0 || log_t > 20 || k == 0 || k as u64 > 1 << log_t {
+            return None;
+        }
+        Some(Parameters { k, log_t })
+    }
+
+    pub fn k(&self) -> u16 {
+        self.k
+    }
+
+    pub fn log_t(&self) -> u8 {
+        self.log_t
+    }
+
+    pub fn t(&self) -> u64 {
+        1 << self.log_t
+    }
+
+    /// The bits of security left against forging a signature on a new
+    /// message once `signatures` messages have been signed, that is
+    /// `k * (log2 t - log2(signatures * k))`, or `k * log2 t` before the
+    /// first signature. Zero means that the revealed values may well cover
+    /// the values selected by any message.
+    pub fn security_bits(&self, signatures: u64) -> f64 {
+        let k = self.k as f64;
+        let log_t = self.log_t as f64;
+        if signatures == 0 {
+            return k * log_t;
+        }
+        let revealed = (signatures as f64 * k).log2();
+        (k * (log_t - revealed)).clamp(0.0, k * log_t)
+    }
+
+    /// The parameter set field of framed encodings: `k`, then `log_t`.
+    fn framed_parameter_set(&self) -> u32 {
+        ((self.k as u32) << 8) | self.log_t as u32
+    }
+
+    fn from_framed_parameter_set(parameter_set: u32) -> Result<Parameters, wire::DecodingError> {
+        u16::try_from(parameter_set >> 8)
+            .ok()
+            .and_then(|k| Parameters::new(k, parameter_set as u8))
+            .ok_or(wire::DecodingError::InvalidParameterSet { parameter_set })
+    }
+
+    /// The `k` values selected by the digest of `message`.
+    fn indices(&self, message: &[u8]) -> Vec<u64> {
+        let mut digest = vec![0u8; (self.k as usize * self.log_t as usize).div_ceil(8)];
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"hors message digest");
+        hasher.update(message);
+        hasher.finalize_xof().fill(&mut digest);
+        (0..self.k as usize)
+            .map(|i| read_bits(&digest, i * self.log_t as usize, self.log_t as u32))
+            .collect()
+    }
+}
+
+/// A HORS private key, whose secret values are derived from a seed. It
+/// counts the signatures made so that the security left can be reported.
+#[derive(PartialEq, Eq)]
+pub struct PrivateKey {
+    seed: [u8; 32],
+    parameters: Parameters,
+    signatures: u64,
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("parameters", &self.parameters)
+            .field("signatures", &self.signatures)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl PrivateKey {
+    pub fn generate(parameters: Parameters) -> Result<PrivateKey, rand::Error> {
+        let mut seed = [0u8; 32];
+        OsRng.try_fill_bytes(&mut seed)?;
+        Ok(PrivateKey::from_seed(seed, parameters))
+    }
+
+    pub fn from_seed(seed: [u8; 32], parameters: Parameters) -> PrivateKey {
+        PrivateKey {
+            seed,
+            parameters,
+            signatures: 0,
+        }
+    }
+
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    /// The number of messages signed so far, in either variant.
+    pub fn signatures(&self) -> u64 {
+        self.signatures
+    }
+
+    /// The bits of security left after the signatures made so far.
+    pub fn security_bits(&self) -> f64 {
+        self.parameters.security_bits(self.signatures)
+    }
+
+    fn secret(&self, index: u64) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_keyed(&self.seed);
+        hasher.update(b"hors secret");
+        hasher.update(&index.to_be_bytes());
+        *hasher.finalize().as_bytes()
+    }
+
+    fn secrets(&self) -> Vec<[u8; 32]> {
+        (0..self.parameters.t()).map(|i| self.secret(i)).collect()
+    }
+
+    fn tree(&self, secrets: &[[u8; 32]]) -> Tree {
+        Tree::new(&mut secrets.iter().map(|secret| secret.as_slice()))
+            .expect("hors keys are never empty")
+    }
+
+    /// The public key of the HORS variant: the hashes of all `t` values.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            parameters: self.parameters,
+            values: self
+                .secrets()
+                .iter()
+                .map(|secret| blake3::hash(secret))
+                .collect(),
+        }
+    }
+
+    /// The public key of the HORST variant: the root of a Merkle tree over
+    /// the hashes of all `t` values.
+    pub fn horst_public_key(&self) -> HorstPublicKey {
+        HorstPublicKey {
+            parameters: self.parameters,
+            root: self.tree(&self.secrets()).commitment().root,
+        }
+    }
+
+    /// Reveals the values selected by `message`.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Signature {
+        self.signatures += 1;
+        Signature(
+            self.parameters,
+            self.parameters
+                .indices(message.as_ref())
+                .into_iter()
+                .map(|i| self.secret(i))
+                .collect(),
+        )
+    }
+
+    /// Reveals the values selected by `message`, each with a proof of its
+    /// place in the tree committed to by the HORST public key.
+    pub fn sign_horst<A: AsRef<[u8]>>(&mut self, message: A) -> HorstSignature {
+        self.signatures += 1;
+        let secrets = self.secrets();
+        let tree = self.tree(&secrets);
+        HorstSignature(
+            self.parameters,
+            self.parameters
+                .indices(message.as_ref())
+                .into_iter()
+                .map(|i| {
+                    tree.prove(secrets[i as usize].to_vec(), i)
+                        .expect("the value was committed to at this index")
+                })
+                .collect(),
+        )
+    }
+}
+
+/// A HORS public key: the hash of every secret value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    parameters: Parameters,
+    values: Vec<Hash>,
+}
+
+impl PublicKey {
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    /// The framed encoding: the parameters, then the hash of every value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature) -> bool {
+        let indices = self.parameters.indices(message.as_ref());
+        signature.0 == self.parameters
+            && signature.1.len() == indices.len()
+            && indices
+                .iter()
+                .zip(signature.1.iter())
+                .fold(true, |acc, (&i, secret)| {
+                    acc & (blake3::hash(secret) == self.values[i as usize])
+                })
+    }
+}
+
+/// A HORST public key: the root of the tree over the hashes of every
+/// secret value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HorstPublicKey {
+    parameters: Parameters,
+    root: Hash,
+}
+
+impl HorstPublicKey {
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &HorstSignature) -> bool {
+        let indices = self.parameters.indices(message.as_ref());
+        signature.0 == self.parameters
+            && signature.1.len() == indices.len()
+            && indices.iter().zip(signature.1.iter()).all(|(&i, proof)| {
+                proof.index() == i && proof.root(self.parameters.t()) == Some(self.root)
+            })
+    }
+
+    /// The framed encoding, naming the parameters.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else a raw one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// Encodes `k`, `log_t` and the root.
+    pub fn to_raw_bytes(&self) -> [u8; 35] {
+        let mut out = [0u8; 35];
+        out[..2].copy_from_slice(&self.parameters.k.to_be_bytes());
+        out[2] = self.parameters.log_t;
+        out[3..].copy_from_slice(self.root.as_bytes());
+        out
+    }
+}
+
+impl TryFrom<&[u8; 35]> for HorstPublicKey {
+    type Error = SignatureDecodingError;
+
+    fn try_from(value: &[u8; 35]) -> Result<Self, Self::Error> {
+        let parameters = Parameters::new(u16::from_be_bytes([value[0], value[1]]), value[2])
+            .ok_or(SignatureDecodingError::InvalidParameters)?;
+        let mut root = [0u8; 32];
+        root.copy_from_slice(&value[3..]);
+        Ok(HorstPublicKey {
+            parameters,
+            root: Hash::from(root),
+        })
+    }
+}
+
+impl Framed for PublicKey {
+    const ALGORITHM: Algorithm = Algorithm::Hors;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        self.parameters.framed_parameter_set()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.values
+            .iter()
+            .flat_map(|value| *value.as_bytes())
+            .collect()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let parameters = Parameters::from_framed_parameter_set(parameter_set)?;
+        let mut reader = Reader::new(body, 0);
+        let values = (0..parameters.t())
+            .map(|_| reader.array::<32>().map(Hash::from))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|values| reader.finish().map(|()| values))
+            .map_err(|e| wire::invalid_body(SignatureDecodingError::from(e)))?;
+        Ok(PublicKey { parameters, values })
+    }
+}
+
+impl Framed for HorstPublicKey {
+    const ALGORITHM: Algorithm = Algorithm::Horst;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        self.parameters.framed_parameter_set()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.to_raw_bytes().to_vec()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let raw = <&[u8; 35]>::try_from(body).map_err(|_| wire::DecodingError::LengthMismatch {
+            declared: 35,
+            actual: body.len(),
+        })?;
+        let public_key = HorstPublicKey::try_from(raw).map_err(wire::invalid_body)?;
+        if public_key.parameter_set() != parameter_set {
+            return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+        }
+        Ok(public_key)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        HorstPublicKey::try_from(<&[u8; 35]>::try_from(bytes).ok()?).ok()
+    }
+}
+
+/// A HORS signature: the `k` revealed values, in the order selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature(Parameters, Vec<[u8; 32]>);
+
+impl Signature {
+    /// The framed encoding, naming the parameters.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// Decodes a signature made with `parameters`.
+    pub fn decode(
+        signature_bytes: &[u8],
+        parameters: &Parameters,
+    ) -> Result<Self, SignatureDecodingError> {
+        let mut reader = Reader::new(signature_bytes, 0);
+        let mut values = Vec::with_capacity(parameters.k as usize);
+        for _ in 0..parameters.k {
+            values.push(reader.array()?);
+        }
+        reader.finish()?;
+        Ok(Signature(*parameters, values))
+    }
+}
+
+impl From<&Signature> for Vec<u8> {
+    fn from(signature: &Signature) -> Self {
+        signature.1.concat()
+    }
+}
+
+impl Framed for Signature {
+    const ALGORITHM: Algorithm = Algorithm::Hors;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        self.0.framed_parameter_set()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let parameters = Parameters::from_framed_parameter_set(parameter_set)?;
+        Signature::decode(body, &parameters).map_err(wire::invalid_body)
+    }
+}
+
+/// A HORST signature: the `k` revealed values, each with its proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HorstSignature(Parameters, Vec<Proof>);
+
+impl HorstSignature {
+    /// The framed encoding, naming the parameters.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, bounding each proof by the default
+    /// [`DecodingLimits`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// Decodes a signature made with `parameters`, bounding each proof by
+    /// `limits`.
+    pub fn decode(
+        signature_bytes: &[u8],
+        parameters: &Parameters,
+        limits: &DecodingLimits,
+    ) -> Result<Self, SignatureDecodingError> {
+        let mut reader = Reader::new(signature_bytes, 0);
+        let mut proofs = Vec::with_capacity(parameters.k as usize);
+        for _ in 0..parameters.k {
+            proofs.push(Proof::decode_from(&mut reader, limits)?);
+        }
+        reader.finish()?;
+        Ok(HorstSignature(*parameters, proofs))
+    }
+}
+
+impl From<&HorstSignature> for Vec<u8> {
+    fn from(signature: &HorstSignature) -> Self {
+        signature.1.iter().flat_map(Vec::from).collect()
+    }
+}
+
+impl Framed for HorstSignature {
+    const ALGORITHM: Algorithm = Algorithm::Horst;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        self.0.framed_parameter_set()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let parameters = Parameters::from_framed_parameter_set(parameter_set)?;
+        HorstSignature::decode(body, &parameters, &DecodingLimits::default())
+            .map_err(wire::invalid_body)
+    }
+}
+
+/// An error encountered while decoding a signature or public key. Every
+/// offset is the position, in bytes, within the encoded signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureDecodingError {
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// A HORST proof could not be decoded.
+    InvalidProof(ProofDecodingError),
+    /// The signature ended at `offset`, but `remaining` bytes followed it.
+    TrailingBytes { offset: usize, remaining: usize },
+    /// A public key named parameters which [`Parameters::new`] rejects.
+    InvalidParameters,
+}
+
+impl From<ProofDecodingError> for SignatureDecodingError {
+    fn from(e: ProofDecodingError) -> Self {
+        match e {
+            ProofDecodingError::NotEnoughInput { offset, needed } => {
+                SignatureDecodingError::NotEnoughInput { offset, needed }
+            }
+            ProofDecodingError::TrailingBytes { offset, remaining } => {
+                SignatureDecodingError::TrailingBytes { offset, remaining }
+            }
+            e => SignatureDecodingError::InvalidProof(e),
+        }
+    }
+}
+
+impl std::fmt::Display for SignatureDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureDecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "signature truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            SignatureDecodingError::InvalidProof(e) => write!(f, "invalid horst proof: {e}"),
+            SignatureDecodingError::TrailingBytes { offset, remaining } => write!(
+                f,
+                "{remaining} trailing bytes after the signature ending at offset {offset}"
+            ),
+            SignatureDecodingError::InvalidParameters => write!(f, "invalid parameters"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureDecodingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SignatureDecodingError::InvalidProof(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn private_key() -> PrivateKey {
+        PrivateKey::from_seed([7u8; 32], Parameters::new(8, 8).unwrap())
+    }
+
+    #[test]
+    fn hors_signatures() {
+        let mut private_key = private_key();
+        let public_key = private_key.public_key();
+        let signature = private_key.sign(b"Hello, world!");
+        assert!(public_key.verify(b"Hello, world!", &signature));
+        assert!(!public_key.verify(b"Hello, world?", &signature));
+
+        let decoded = Signature::decode(&Vec::from(&signature), &private_key.parameters()).unwrap();
+        assert_eq!(decoded, signature);
+        assert!(matches!(
+            Signature::decode(&[0u8; 8 * 32 - 1], &private_key.parameters()),
+            Err(SignatureDecodingError::NotEnoughInput { .. })
+        ));
+
+        assert_eq!(
+            Signature::from_bytes(&signature.to_bytes()).unwrap(),
+            signature
+        );
+        let framed = public_key.to_bytes();
+        assert_eq!(PublicKey::from_bytes(&framed).unwrap(), public_key);
+        assert!(matches!(
+            HorstPublicKey::from_bytes(&framed),
+            Err(wire::DecodingError::WrongType { .. })
+        ));
+    }
+
+    #[test]
+    fn horst_signatures() {
+        let mut private_key = private_key();
+        let public_key =
+            HorstPublicKey::try_from(&private_key.horst_public_key().to_raw_bytes()).unwrap();
+        let signature = private_key.sign_horst(b"Hello, world!");
+        assert!(public_key.verify(b"Hello, world!", &signature));
+        assert!(!public_key.verify(b"Hello, world?", &signature));
+
+        let limits = DecodingLimits::default();
+        let encoded = Vec::from(&signature);
+        let decoded = HorstSignature::decode(&encoded, &private_key.parameters(), &limits).unwrap();
+        assert_eq!(decoded, signature);
+        assert_eq!(
+            HorstSignature::from_bytes(&signature.to_bytes()).unwrap(),
+            signature
+        );
+        let raw = private_key.horst_public_key().to_raw_bytes();
+        assert_eq!(HorstPublicKey::from_bytes(&raw).unwrap(), public_key);
+
+        let mut swapped = signature.clone();
+        swapped.1.swap(0, 1);
+        assert!(swapped == signature || !public_key.verify(b"Hello, world!", &swapped));
+    }
+
+    #[test]
+    fn security_decreases_with_use() {
+        let parameters = Parameters::K32_T65536;
+        assert_eq!(parameters.security_bits(0), 512.0);
+        assert_eq!(parameters.security_bits(1), 32.0 * 11.0);
+        assert_eq!(parameters.security_bits(2), 32.0 * 10.0);
+        assert_eq!(parameters.security_bits(1 << 12), 0.0);
+
+        let mut private_key = private_key();
+        let before = private_key.security_bits();
+        private_key.sign(b"one");
+        private_key.sign_horst(b"two");
+        assert_eq!(private_key.signatures(), 2);
+        assert!(private_key.security_bits() < before);
+    }
+
+    #[test]
+    fn validates_parameters() {
+        assert_eq!(Parameters::new(0, 10), None);
+        assert_eq!(Parameters::new(16, 0), None);
+        assert_eq!(Parameters::new(16, 21), None);
+        assert_eq!(Parameters::new(5, 2), None);
+        assert!(Parameters::new(4, 2).is_some());
+    }
+}
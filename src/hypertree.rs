@@ -0,0 +1,804 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+use crate::merkle::internal::{Commitment, Reader};
+use crate::merkle::seeded::{self, KeyDecodingError};
+use crate::merkle::{self, DecodingLimits, KeyGenerationError};
+use crate::state::{self, Reservation, SigningError, StateStore};
+use crate::wire::{self, Algorithm, Framed, Kind};
+
+/// The shape of a hypertree: a number of layers of Merkle trees, each tree
+/// having `2^tree_height` leaves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Parameters {
+    layers: u8,
+    tree_height: u8,
+}
+
+impl Parameters {
+    /// Two layers of trees with 1024 leaves, able to sign 2^20 messages.
+    pub const LAYERS_2_HEIGHT_10: Parameters = Parameters {
+        layers: 2,
+        tree_height: 10,
+    };
+
+    /// Four layers of trees with 1024 leaves, able to sign 2^40 messages.
+    pub const LAYERS_4_HEIGHT_10: Parameters = Parameters {
+        layers: 4,
+        tree_height: 10,
+    };
+
+    /// Returns [`None`] unless there is at least one layer, every tree has
+    /// between 2 and 2^32 leaves, and the capacity fits in 63 bits.
+    pub fn new(layers: u8, tree_height: u8) -> Option<Parameters> {
+        if layers == 0 || tree_height == 0 || tree_height > 32 {
+            return None;
+        }
+        if layers as u32 * tree_height as u32 > 63 {
+            return None;
+        }
+        Some(Parameters {
+            layers,
+            tree_height,
+        })
+    }
+
+    pub fn layers(&self) -> u8 {
+        self.layers
+    }
+
+    pub fn tree_height(&self) -> u8 {
+        self.tree_height
+    }
+
+    /// The number of messages a key with these parameters can sign.
+    pub fn capacity(&self) -> u64 {
+        1 << (self.layers as u32 * self.tree_height as u32)
+    }
+
+    fn leaves_per_tree(&self) -> u64 {
+        1 << self.tree_height
+    }
+}
+
+/// The seed of the `tree`th Merkle tree of `layer`, counting from the
+/// bottom layer.
+fn tree_seed(seed: &[u8; 32], layer: u8, tree: u64) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_keyed(seed);
+    hasher.update(b"hypertree tree seed");
+    hasher.update(&[layer]);
+    hasher.update(&tree.to_be_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// The tree currently in use at one layer, along with the signature of its
+/// public key by the layer above.
+#[derive(Debug, PartialEq, Eq)]
+struct Layer {
+    tree: u64,
+    key: seeded::PrivateKey,
+    signature: Option<merkle::Signature>,
+}
+
+impl Layer {
+    fn exhausted(&self) -> bool {
+        self.key.current_index() >= self.key.num_items()
+    }
+
+    /// Starts the tree of `layer` which the next leaf of `parent` signs, or
+    /// the first tree of the top layer when there is no parent. Returns
+    /// [`None`] if the parent has no leaves left.
+    fn start(
+        seed: &[u8; 32],
+        parameters: &Parameters,
+        layer: u8,
+        parent: Option<&mut Layer>,
+    ) -> Option<Layer> {
+        let tree = match &parent {
+            None => 0,
+            Some(parent) if parent.exhausted() => return None,
+            Some(parent) => parent.tree * parameters.leaves_per_tree() + parent.key.current_index(),
+        };
+        let key = seeded::PrivateKey::from_seed(
+            tree_seed(seed, layer, tree),
+            parameters.leaves_per_tree(),
+        )
+        .expect("parameters always describe non-empty trees");
+        let signature = match parent {
+            None => None,
+            Some(parent) => Some(parent.key.sign(<[u8; 40]>::from(key.public_key()))?),
+        };
+        Some(Layer {
+            tree,
+            key,
+            signature,
+        })
+    }
+}
+
+/// A hypertree private key. Only one tree per layer is held at a time, as
+/// a [`seeded::PrivateKey`], so generating a key builds just one small tree
+/// per layer however large its capacity.
+#[derive(PartialEq, Eq)]
+pub struct PrivateKey {
+    seed: [u8; 32],
+    parameters: Parameters,
+    // the bottom layer comes first
+    layers: Vec<Layer>,
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("parameters", &self.parameters)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl PrivateKey {
+    pub fn generate(parameters: Parameters) -> Result<PrivateKey, KeyGenerationError> {
+        let mut seed = [0u8; 32];
+        OsRng.try_fill_bytes(&mut seed)?;
+        Ok(PrivateKey::from_seed(seed, parameters))
+    }
+
+    /// Deterministically builds the key with the given `parameters` from a
+    /// secret `seed`.
+    pub fn from_seed(seed: [u8; 32], parameters: Parameters) -> PrivateKey {
+        // each layer is signed by the one above it, so build from the top
+        let mut layers: Vec<Layer> = Vec::with_capacity(parameters.layers as usize);
+        for layer in (0..parameters.layers).rev() {
+            let new_layer = Layer::start(&seed, &parameters, layer, layers.last_mut())
+                .expect("the first tree of a layer has unused leaves");
+            layers.push(new_layer);
+        }
+        layers.reverse();
+        PrivateKey {
+            seed,
+            parameters,
+            layers,
+        }
+    }
+
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        let top = &self.layers[self.layers.len() - 1];
+        PublicKey {
+            parameters: self.parameters,
+            commitment: top.key.public_key().0,
+        }
+    }
+
+    /// The index, across the whole hypertree, of the leaf the next
+    /// signature will use.
+    pub fn current_index(&self) -> u64 {
+        let bottom = &self.layers[0];
+        bottom.tree * self.parameters.leaves_per_tree() + bottom.key.current_index()
+    }
+
+    /// Signs with the next unused bottom-layer leaf, returning [`None`] once
+    /// every leaf has been used.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<Signature> {
+        if self.layers[0].exhausted() {
+            self.advance(0)?;
+        }
+        let mut signatures = vec![self.layers[0].key.sign(message)?];
+        for layer in &self.layers[..self.layers.len() - 1] {
+            signatures.push(
+                layer
+                    .signature
+                    .clone()
+                    .expect("every layer below the top is signed"),
+            );
+        }
+        Some(Signature(signatures))
+    }
+
+    /// Signs like [`PrivateKey::sign`], but only after durably recording in
+    /// `store` that the index is used, counting signatures as
+    /// [`PrivateKey::current_index`] does. If `store` is ahead of this key,
+    /// the layers are first advanced to where signing every index in
+    /// between would have left them.
+    pub fn sign_persisted<S: StateStore, A: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        message: A,
+    ) -> Result<Signature, SigningError<S::Error>> {
+        let mut reservation = self.reserve(store, 1)?;
+        Ok(self
+            .sign_reserved(&mut reservation, message)
+            .expect("reserved indices are in range"))
+    }
+
+    /// Durably reserves the next `count` indices in `store` with a single
+    /// write, like [`crate::merkle::PrivateKey::reserve`].
+    pub fn reserve<S: StateStore>(
+        &mut self,
+        store: &mut S,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        state::reserve(
+            store,
+            self.current_index(),
+            self.parameters.capacity(),
+            count,
+        )
+    }
+
+    /// Signs with the next index of `reservation`, which must have been
+    /// made for this key, first advancing past any indices skipped to reach
+    /// it. Returns [`None`] once the block is used up.
+    pub fn sign_reserved<A: AsRef<[u8]>>(
+        &mut self,
+        reservation: &mut Reservation,
+        message: A,
+    ) -> Option<Signature> {
+        let index = reservation.take_from(self.current_index())?;
+        self.skip_to(index)?;
+        self.sign(message)
+    }
+
+    /// Moves every layer to where signing up to `index` would have left
+    /// it. The layers below the highest one which moves to another tree
+    /// are replaced by the trees signed at their new positions, without
+    /// building those skipped.
+    fn skip_to(&mut self, index: u64) -> Option<()> {
+        if index <= self.current_index() {
+            return Some(());
+        }
+        if index >= self.parameters.capacity() {
+            return None;
+        }
+        let leaf_mask = self.parameters.leaves_per_tree() - 1;
+        // the tree of each layer on the way to signature `index`
+        let tree =
+            |layer: usize| index >> (self.parameters.tree_height as u32 * (layer as u32 + 1));
+        let top = self.layers.len() - 1;
+        if let Some(moved) = (0..top)
+            .rev()
+            .find(|&layer| self.layers[layer].tree != tree(layer))
+        {
+            for layer in (0..=moved).rev() {
+                let (below, above) = self.layers.split_at_mut(layer + 1);
+                above[0].key.skip_to(tree(layer) & leaf_mask);
+                below[layer] = Layer::start(
+                    &self.seed,
+                    &self.parameters,
+                    layer as u8,
+                    Some(&mut above[0]),
+                )?;
+            }
+        }
+        self.layers[0].key.skip_to(index & leaf_mask);
+        Some(())
+    }
+
+    /// Replaces the exhausted tree at `layer` with the next one, advancing
+    /// the layers above it as needed.
+    fn advance(&mut self, layer: usize) -> Option<()> {
+        if layer + 1 == self.layers.len() {
+            return None;
+        }
+        if self.layers[layer + 1].exhausted() {
+            self.advance(layer + 1)?;
+        }
+        let (below, above) = self.layers.split_at_mut(layer + 1);
+        below[layer] = Layer::start(
+            &self.seed,
+            &self.parameters,
+            layer as u8,
+            Some(&mut above[0]),
+        )?;
+        Some(())
+    }
+
+    /// Encodes the whole signing state, including the secret seed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(&self.seed);
+        output.push(self.parameters.layers);
+        output.push(self.parameters.tree_height);
+        for layer in self.layers.iter() {
+            output.extend(layer.tree.to_be_bytes());
+            let key = layer.key.to_bytes();
+            output.extend((key.len() as u64).to_be_bytes());
+            output.extend(key);
+            if let Some(signature) = &layer.signature {
+                let signature = Vec::from(signature);
+                output.extend((signature.len() as u64).to_be_bytes());
+                output.extend(signature);
+            }
+        }
+        output
+    }
+}
+
+/// Reads a `u64` length prefix followed by that many bytes.
+fn read_prefixed<'a>(reader: &mut Reader<'a>) -> Result<(usize, &'a [u8]), KeyDecodingError> {
+    let offset = reader.offset();
+    let length = u64::from_be_bytes(reader.array()?);
+    let length = usize::try_from(length).map_err(|_| KeyDecodingError::InvalidField { offset })?;
+    let start = reader.offset();
+    Ok((start, reader.take(length)?))
+}
+
+impl TryFrom<&[u8]> for PrivateKey {
+    type Error = KeyDecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let seed = reader.array::<32>()?;
+        let offset = reader.offset();
+        let [layers, tree_height] = reader.array::<2>()?;
+        let parameters = Parameters::new(layers, tree_height)
+            .ok_or(KeyDecodingError::InvalidField { offset })?;
+
+        let mut decoded = Vec::with_capacity(layers as usize);
+        for layer in 0..layers {
+            let tree = u64::from_be_bytes(reader.array()?);
+
+            let (offset, key) = read_prefixed(&mut reader)?;
+            let key = seeded::PrivateKey::try_from(key).map_err(|e| shift(e, offset))?;
+            if key.num_items() != parameters.leaves_per_tree() {
+                return Err(KeyDecodingError::InvalidField { offset });
+            }
+
+            let signature = if layer + 1 < layers {
+                let (offset, signature) = read_prefixed(&mut reader)?;
+                let signature = merkle::Signature::try_from(signature)
+                    .map_err(|_| KeyDecodingError::InvalidField { offset })?;
+                Some(signature)
+            } else {
+                None
+            };
+
+            decoded.push(Layer {
+                tree,
+                key,
+                signature,
+            });
+        }
+        reader.finish()?;
+
+        Ok(PrivateKey {
+            seed,
+            parameters,
+            layers: decoded,
+        })
+    }
+}
+
+/// Moves the offsets of an error in a nested encoding starting at `offset`
+/// so that they point into the enclosing encoding.
+fn shift(e: KeyDecodingError, offset: usize) -> KeyDecodingError {
+    match e {
+        KeyDecodingError::NotEnoughInput {
+            offset: inner,
+            needed,
+        } => KeyDecodingError::NotEnoughInput {
+            offset: offset + inner,
+            needed,
+        },
+        KeyDecodingError::InvalidField { offset: inner } => KeyDecodingError::InvalidField {
+            offset: offset + inner,
+        },
+        KeyDecodingError::TrailingBytes {
+            offset: inner,
+            remaining,
+        } => KeyDecodingError::TrailingBytes {
+            offset: offset + inner,
+            remaining,
+        },
+    }
+}
+
+/// The public key of a hypertree: the root of its single top-level tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    parameters: Parameters,
+    commitment: Commitment,
+}
+
+impl PublicKey {
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    /// Checks every Merkle signature in the chain, from the one over
+    /// `message` at the bottom to the one from the top-level tree.
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature) -> bool {
+        if signature.0.len() != self.parameters.layers as usize {
+            return false;
+        }
+        let num_items = self.parameters.leaves_per_tree();
+        let mut signatures = signature.0.iter();
+        let mut commitment = match signatures.next() {
+            Some(bottom) => match bottom.recover(message, num_items) {
+                Some(commitment) => commitment,
+                None => return false,
+            },
+            None => return false,
+        };
+        for signature in signatures {
+            let signed = commitment.to_bytes();
+            commitment = match signature.recover(signed, num_items) {
+                Some(commitment) => commitment,
+                None => return false,
+            };
+        }
+        commitment == self.commitment
+    }
+
+    /// The framed encoding, naming the parameters.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else a raw one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// Encodes the parameters followed by the root of the top-level tree.
+    pub fn to_raw_bytes(&self) -> [u8; 34] {
+        let mut out = [0u8; 34];
+        out[0] = self.parameters.layers;
+        out[1] = self.parameters.tree_height;
+        out[2..].copy_from_slice(self.commitment.root.as_bytes());
+        out
+    }
+}
+
+impl TryFrom<&[u8; 34]> for PublicKey {
+    type Error = KeyDecodingError;
+
+    fn try_from(value: &[u8; 34]) -> Result<Self, Self::Error> {
+        let parameters = Parameters::new(value[0], value[1])
+            .ok_or(KeyDecodingError::InvalidField { offset: 0 })?;
+        let mut root = [0u8; 32];
+        root.copy_from_slice(&value[2..]);
+        Ok(PublicKey {
+            parameters,
+            commitment: Commitment {
+                root: blake3::Hash::from(root),
+                num_items: parameters.leaves_per_tree(),
+            },
+        })
+    }
+}
+
+/// The parameter set field of framed encodings: the number of layers, then
+/// the height of each tree.
+fn framed_parameter_set(parameters: &Parameters) -> u32 {
+    ((parameters.layers as u32) << 8) | parameters.tree_height as u32
+}
+
+impl Framed for PublicKey {
+    const ALGORITHM: Algorithm = Algorithm::Hypertree;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        framed_parameter_set(&self.parameters)
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.to_raw_bytes().to_vec()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let raw = <&[u8; 34]>::try_from(body).map_err(|_| wire::DecodingError::LengthMismatch {
+            declared: 34,
+            actual: body.len(),
+        })?;
+        let public_key = PublicKey::try_from(raw).map_err(wire::invalid_body)?;
+        if framed_parameter_set(&public_key.parameters) != parameter_set {
+            return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+        }
+        Ok(public_key)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        PublicKey::try_from(<&[u8; 34]>::try_from(bytes).ok()?).ok()
+    }
+}
+
+/// A chain of Merkle signatures: the first over the message, and each of
+/// the others over the public key of the tree which made the one before.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature(Vec<merkle::Signature>);
+
+impl Signature {
+    /// The framed encoding, naming the number of layers and the height of
+    /// the trees.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an unframed one, bounding each
+    /// Merkle signature by the default [`DecodingLimits`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// The index, across the whole hypertree, of the leaf which made this
+    /// signature, given the height of each tree.
+    pub fn index(&self, parameters: &Parameters) -> u64 {
+        self.0.iter().rev().fold(0, |index, signature| {
+            (index << parameters.tree_height) + signature.index()
+        })
+    }
+
+    /// Decodes a signature from untrusted bytes, bounding each Merkle
+    /// signature by `limits` and rejecting any trailing input.
+    pub fn decode(
+        signature_bytes: &[u8],
+        limits: &DecodingLimits,
+    ) -> Result<Self, SignatureDecodingError> {
+        let mut reader = Reader::new(signature_bytes, 0);
+        let truncated = |e| match e {
+            merkle::ProofDecodingError::NotEnoughInput { offset, needed } => {
+                SignatureDecodingError::NotEnoughInput { offset, needed }
+            }
+            _ => unreachable!("only running out of input can fail a read"),
+        };
+        let layers = reader.array::<1>().map_err(truncated)?[0];
+
+        let mut signatures = Vec::with_capacity(layers as usize);
+        for layer in 0..layers as usize {
+            let length_offset = reader.offset();
+            let length = u64::from_be_bytes(reader.array().map_err(truncated)?);
+            let length = usize::try_from(length).unwrap_or(usize::MAX);
+            if length > reader.remaining() {
+                return Err(SignatureDecodingError::NotEnoughInput {
+                    offset: length_offset + 8,
+                    needed: length - reader.remaining(),
+                });
+            }
+            let offset = reader.offset();
+            let encoded = reader.take(length).map_err(truncated)?;
+            let signature = merkle::Signature::decode(encoded, limits).map_err(|error| {
+                SignatureDecodingError::InvalidLayer {
+                    layer,
+                    offset,
+                    error,
+                }
+            })?;
+            signatures.push(signature);
+        }
+
+        if reader.remaining() > 0 {
+            return Err(SignatureDecodingError::TrailingBytes {
+                offset: reader.offset(),
+                remaining: reader.remaining(),
+            });
+        }
+        Ok(Signature(signatures))
+    }
+}
+
+impl From<&Signature> for Vec<u8> {
+    fn from(signature: &Signature) -> Self {
+        let mut output = vec![signature.0.len() as u8];
+        for signature in signature.0.iter() {
+            let encoded = Vec::from(signature);
+            output.extend((encoded.len() as u64).to_be_bytes());
+            output.extend(encoded);
+        }
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for Signature {
+    type Error = SignatureDecodingError;
+
+    fn try_from(signature_bytes: &[u8]) -> Result<Self, Self::Error> {
+        Signature::decode(signature_bytes, &DecodingLimits::default())
+    }
+}
+
+impl Framed for Signature {
+    const ALGORITHM: Algorithm = Algorithm::Hypertree;
+    const KIND: Kind = Kind::Signature;
+
+    /// The number of layers, then the height of the trees, which is the
+    /// depth of the bottom Merkle proof.
+    fn parameter_set(&self) -> u32 {
+        let tree_height = self.0.first().map_or(0, |signature| signature.2.depth());
+        ((self.0.len() as u32) << 8) | tree_height as u32
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let signature = Signature::try_from(body).map_err(wire::invalid_body)?;
+        if signature.parameter_set() != parameter_set {
+            return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+        }
+        Ok(signature)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        Signature::try_from(bytes).ok()
+    }
+}
+
+/// An error encountered while decoding a [`Signature`]. Every offset is the
+/// position, in bytes, within the encoded signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureDecodingError {
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// The Merkle signature of `layer`, starting at `offset`, is malformed.
+    /// Offsets within `error` are relative to the start of that signature.
+    InvalidLayer {
+        layer: usize,
+        offset: usize,
+        error: merkle::SignatureDecodingError,
+    },
+    /// The signature ended at `offset`, but `remaining` bytes followed it.
+    TrailingBytes { offset: usize, remaining: usize },
+}
+
+impl std::fmt::Display for SignatureDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureDecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "signature truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            SignatureDecodingError::InvalidLayer {
+                layer,
+                offset,
+                error,
+            } => write!(
+                f,
+                "invalid signature for layer {layer} at offset {offset}: {error}"
+            ),
+            SignatureDecodingError::TrailingBytes { offset, remaining } => write!(
+                f,
+                "{remaining} trailing bytes after the signature ending at offset {offset}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SignatureDecodingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SignatureDecodingError::InvalidLayer { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MemoryStore;
+
+    #[test]
+    fn signs_every_leaf_once() {
+        let parameters = Parameters::new(3, 2).unwrap();
+        let mut private_key = PrivateKey::from_seed([5u8; 32], parameters);
+        let public_key = private_key.public_key();
+        for i in 0..parameters.capacity() {
+            assert_eq!(private_key.current_index(), i);
+            let message = i.to_be_bytes();
+            let signature = private_key.sign(message).unwrap();
+            assert_eq!(signature.index(&parameters), i);
+            assert!(public_key.verify(message, &signature));
+            assert!(!public_key.verify(b"something else", &signature));
+        }
+        assert!(private_key.sign(b"one too many").is_none());
+    }
+
+    #[test]
+    fn rejects_spliced_chains() {
+        let parameters = Parameters::new(2, 2).unwrap();
+        let mut private_key = PrivateKey::from_seed([6u8; 32], parameters);
+        let public_key = private_key.public_key();
+        let first = private_key.sign(b"first").unwrap();
+        for _ in 0..4 {
+            private_key.sign(b"skip");
+        }
+        let later = private_key.sign(b"later").unwrap();
+
+        // a bottom signature from one tree paired with another tree's chain
+        let spliced = Signature(vec![first.0[0].clone(), later.0[1].clone()]);
+        assert!(!public_key.verify(b"first", &spliced));
+        let truncated = Signature(vec![first.0[0].clone()]);
+        assert!(!public_key.verify(b"first", &truncated));
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let parameters = Parameters::new(2, 3).unwrap();
+        let mut private_key = PrivateKey::from_seed([8u8; 32], parameters);
+        for _ in 0..9 {
+            private_key.sign(b"advance");
+        }
+
+        let encoded = private_key.to_bytes();
+        let mut decoded = PrivateKey::try_from(encoded.as_slice()).unwrap();
+        assert_eq!(decoded, private_key);
+        let signature = private_key.sign(b"next").unwrap();
+        assert_eq!(decoded.sign(b"next").as_ref(), Some(&signature));
+
+        let public_key = PublicKey::try_from(&private_key.public_key().to_raw_bytes()).unwrap();
+        assert_eq!(public_key, private_key.public_key());
+        let framed = private_key.public_key().to_bytes();
+        assert_eq!(PublicKey::from_bytes(&framed).unwrap(), public_key);
+
+        let signature_bytes = Vec::from(&signature);
+        let decoded_signature = Signature::try_from(signature_bytes.as_slice()).unwrap();
+        assert_eq!(decoded_signature, signature);
+        assert_eq!(
+            Signature::from_bytes(&signature.to_bytes()).unwrap(),
+            signature
+        );
+        assert_eq!(Signature::from_bytes(&signature_bytes).unwrap(), signature);
+        assert!(public_key.verify(b"next", &decoded_signature));
+
+        let mut trailing = signature_bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Signature::try_from(trailing.as_slice()),
+            Err(SignatureDecodingError::TrailingBytes {
+                offset: signature_bytes.len(),
+                remaining: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn skips_to_stored_indices() {
+        let parameters = Parameters::new(3, 2).unwrap();
+        let mut sequential = PrivateKey::from_seed([9u8; 32], parameters);
+        for _ in 0..37 {
+            sequential.sign(b"skipped");
+        }
+
+        // the store is ahead by a bottom tree and a middle-layer tree
+        let mut private_key = PrivateKey::from_seed([9u8; 32], parameters);
+        let mut store = MemoryStore::new(37);
+        let signature = private_key.sign_persisted(&mut store, b"message").unwrap();
+        assert_eq!(signature.index(&parameters), 37);
+        assert!(private_key.public_key().verify(b"message", &signature));
+        assert_eq!(store.load(), Ok(38));
+        assert_eq!(sequential.sign(b"message"), Some(signature));
+        assert_eq!(private_key, sequential);
+
+        let mut reservation = private_key.reserve(&mut store, 30).unwrap();
+        assert_eq!(reservation.end(), parameters.capacity());
+        let mut reserved = Vec::new();
+        while let Some(signature) = private_key.sign_reserved(&mut reservation, b"block") {
+            reserved.push(signature.index(&parameters));
+        }
+        assert_eq!(reserved, (38..64).collect::<Vec<_>>());
+        assert!(matches!(
+            private_key.sign_persisted(&mut store, b"one too many"),
+            Err(SigningError::Exhausted)
+        ));
+    }
+
+    #[test]
+    fn validates_parameters() {
+        assert_eq!(Parameters::new(0, 10), None);
+        assert_eq!(Parameters::new(4, 0), None);
+        assert_eq!(Parameters::new(2, 33), None);
+        assert_eq!(Parameters::new(8, 8), None);
+        assert_eq!(Parameters::LAYERS_4_HEIGHT_10.capacity(), 1 << 40);
+    }
+}
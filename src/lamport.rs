@@ -1,27 +1,94 @@
-use blake3::hash;
+use std::marker::PhantomData;
+
+use crate::fingerprint::Fingerprint;
+use crate::hash::{Blake3, HashFunction};
+use crate::wire::{self, Algorithm, Framed, Kind};
 use rand::rngs::OsRng;
 use rand::Fill;
+use zeroize::Zeroize;
+
+/// A choice of message digest length and preimage size. Messages are
+/// hashed to `8 * N` bits, and every secret preimage and public hash is
+/// `N` bytes long.
+pub trait ParameterSet:
+    Copy + std::fmt::Debug + Default + Eq + Ord + std::hash::Hash + 'static
+{
+    /// The byte identifying this parameter set in encoded keys and
+    /// signatures.
+    const ID: u8;
+    /// The length in bytes of each preimage, and of the message digest.
+    const N: usize;
+}
+
+/// 128-bit digests and 16-byte preimages: 2 KiB signatures.
+///
+/// The message digest is not randomized, so a signer asked to sign one of
+/// two colliding messages has signed both. Finding such a pair takes about
+/// 2^64 work, which bounds the security of this set well below its
+/// preimage length. Only sign messages chosen by someone you trust.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lamport128;
+
+impl ParameterSet for Lamport128 {
+    const ID: u8 = 1;
+    const N: usize = 16;
+}
+
+/// 192-bit digests and 24-byte preimages: 4.5 KiB signatures.
+///
+/// Like [`Lamport128`], the digest is not randomized, so colliding
+/// messages, found with about 2^96 work, bound its security.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lamport192;
+
+impl ParameterSet for Lamport192 {
+    const ID: u8 = 2;
+    const N: usize = 24;
+}
+
+/// 256-bit digests and 32-byte preimages: 8 KiB signatures. This is the
+/// original parameter set, and the default for every type in this module.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lamport256;
+
+impl ParameterSet for Lamport256 {
+    const ID: u8 = 3;
+    const N: usize = 32;
+}
+
+/// The length in bytes of one side of a key, or of a signature.
+const fn side_len<P: ParameterSet>() -> usize {
+    8 * P::N * P::N
+}
 
 /// A private key is what you generate and keep in order to sign things.
 /// From it, you can generate a [`PublicKey`] and send that to others,
 /// allowing them to verify your signatures down the line.
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone)]
-pub struct PrivateKey {
-    left: [u8; 8192],
-    right: [u8; 8192],
+///
+/// It cannot be cloned, so that no copy of the secret escapes erasure, and
+/// its [`Debug`](std::fmt::Debug) output leaves the secret out.
+#[derive(PartialEq, PartialOrd, Eq, Ord)]
+pub struct PrivateKey<P: ParameterSet = Lamport256, H: HashFunction = Blake3> {
+    left: Vec<u8>,
+    right: Vec<u8>,
+    parameters: PhantomData<(P, H)>,
+}
+
+impl<P: ParameterSet, H: HashFunction> std::fmt::Debug for PrivateKey<P, H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("parameter_set", &P::ID)
+            .finish_non_exhaustive()
+    }
 }
 
 impl From<&[u8; 16384]> for PrivateKey {
     fn from(value: &[u8; 16384]) -> Self {
-        let mut left = [0u8; 8192];
-        let mut right = [0u8; 8192];
-        for i in 0..8192 {
-            left[i] = value[i];
-        }
-        for i in 0..8192 {
-            right[i] = value[i + 8192];
+        PrivateKey {
+            left: value[..8192].to_vec(),
+            right: value[8192..].to_vec(),
+            parameters: PhantomData,
         }
-        PrivateKey { left, right }
     }
 }
 
@@ -29,12 +96,8 @@ impl From<&PrivateKey> for [u8; 16384] {
     /// Turns the private key into a single byte array
     fn from(private_key: &PrivateKey) -> [u8; 16384] {
         let mut out = [0u8; 16384];
-        for i in 0..8192 {
-            out[i] = private_key.left[i];
-        }
-        for i in 0..8192 {
-            out[i + 8192] = private_key.right[i];
-        }
+        out[..8192].copy_from_slice(&private_key.left);
+        out[8192..].copy_from_slice(&private_key.right);
         out
     }
 }
@@ -43,51 +106,176 @@ impl PrivateKey {
     /// Generates a new private key using the operating system random
     /// number generator.
     pub fn generate() -> Result<PrivateKey, rand::Error> {
-        let mut left = [0u8; 8192];
-        let mut right = [0u8; 8192];
+        PrivateKey::generate_with(Lamport256)
+    }
+
+    /// Deterministically derives the `index`th private key from a secret
+    /// `seed`, so that any number of keys can be regenerated from one
+    /// 32-byte secret.
+    pub fn derive(seed: &[u8; 32], index: u64) -> PrivateKey {
+        PrivateKey::derive_with(Lamport256, seed, index)
+    }
+}
+
+impl<P: ParameterSet, H: HashFunction> PrivateKey<P, H> {
+    /// The length of [`PrivateKey::encode`]'s output.
+    pub(crate) const ENCODED_LEN: usize = 1 + 2 * side_len::<P>();
+
+    /// Generates a new private key for the parameter set `P` using the
+    /// operating system random number generator.
+    pub fn generate_with(_parameters: P) -> Result<PrivateKey<P, H>, rand::Error> {
+        let mut left = vec![0u8; side_len::<P>()];
+        let mut right = vec![0u8; side_len::<P>()];
         left.try_fill(&mut OsRng)?;
         right.try_fill(&mut OsRng)?;
-        Ok(PrivateKey { left, right })
+        Ok(PrivateKey {
+            left,
+            right,
+            parameters: PhantomData,
+        })
+    }
+
+    /// Deterministically derives the `index`th private key for the
+    /// parameter set `P` from a secret `seed`.
+    pub fn derive_with(_parameters: P, seed: &[u8; 32], index: u64) -> PrivateKey<P, H> {
+        let mut left = vec![0u8; 2 * side_len::<P>()];
+        H::keyed_hash_into(
+            seed,
+            &[b"lamport private key", &index.to_be_bytes()],
+            &mut left,
+        );
+        let right = left.split_off(side_len::<P>());
+        PrivateKey {
+            left,
+            right,
+            parameters: PhantomData,
+        }
     }
 
     /// Creates the [`PublicKey`] associated with this [`PrivateKey`].
-    pub fn public_key(&self) -> PublicKey {
-        let mut public_key: PublicKey = PublicKey {
-            left_hashes: [[0u8; 32]; 256],
-            right_hashes: [[0u8; 32]; 256],
+    pub fn public_key(&self) -> PublicKey<P, H> {
+        let mut public_key = PublicKey {
+            left_hashes: vec![0u8; side_len::<P>()],
+            right_hashes: vec![0u8; side_len::<P>()],
+            parameters: PhantomData,
         };
-        for ((lhash, rhash), i) in self
-            .left
-            .chunks(32)
-            .map(hash)
-            .zip(self.right.chunks(32).map(hash))
-            .zip(0..)
-        {
-            public_key.left_hashes[i] = lhash.as_bytes().clone();
-            public_key.right_hashes[i] = rhash.as_bytes().clone();
-        }
+        hash_chunks::<H>(&self.left, P::N, &mut public_key.left_hashes);
+        hash_chunks::<H>(&self.right, P::N, &mut public_key.right_hashes);
         public_key
     }
 
     /// Signs the message, producing a [`Signature`] which another party would
     /// be able to [`PublicKey::verify`] with access to the [`PublicKey`] generated
     /// from this [`PrivateKey`] with [`PrivateKey::public_key`].
-    pub fn sign<A: AsRef<[u8]>>(&self, message: A) -> Signature {
-        let hash = hash(message.as_ref());
-        let mut signature: Signature = Signature {
-            exposed: [0u8; 8192],
+    pub fn sign<A: AsRef<[u8]>>(&self, message: A) -> Signature<P, H> {
+        let hash = digest::<P, H>(message.as_ref());
+        let mut signature = Signature {
+            exposed: vec![0u8; side_len::<P>()],
+            parameters: PhantomData,
         };
-        for (chunk, i) in signature.exposed.chunks_mut(32).zip(0..) {
+        for (chunk, i) in signature.exposed.chunks_mut(P::N).zip(0..) {
             // TODO(sam) conditional, does this enable timing attacks?
-            let side = if bit_of_byteslice(i, hash.as_bytes()) {
-                self.left
+            let side = if bit_of_byteslice(i, &hash) {
+                &self.left
             } else {
-                self.right
+                &self.right
             };
-            chunk.clone_from_slice(&side[i * 32..(i + 1) * 32]);
+            chunk.clone_from_slice(&side[i * P::N..(i + 1) * P::N]);
         }
         signature
     }
+
+    /// Overwrites the secret with zeros, once it has signed, so that it can
+    /// never sign anything else. The writes are volatile, so the compiler
+    /// cannot drop them as dead stores, even when the key is about to be
+    /// freed.
+    pub(crate) fn erase(&mut self) {
+        self.left.as_mut_slice().zeroize();
+        self.right.as_mut_slice().zeroize();
+    }
+
+    /// A copy of the key, for tests which restore old copies of a key.
+    #[cfg(test)]
+    pub(crate) fn duplicate(&self) -> Self {
+        PrivateKey {
+            left: self.left.clone(),
+            right: self.right.clone(),
+            parameters: PhantomData,
+        }
+    }
+
+    /// Encodes the parameter set followed by the private key.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![P::ID];
+        out.extend_from_slice(&self.left);
+        out.extend_from_slice(&self.right);
+        out
+    }
+}
+
+// Secrets are wiped before their memory is freed, so that a used key
+// does not linger in memory for a later leak to reveal.
+impl<P: ParameterSet, H: HashFunction> Drop for PrivateKey<P, H> {
+    fn drop(&mut self) {
+        self.erase();
+    }
+}
+
+impl<P: ParameterSet, H: HashFunction> TryFrom<&[u8]> for PrivateKey<P, H> {
+    type Error = DecodingError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let body = decode_body::<P>(value, 2 * side_len::<P>())?;
+        Ok(PrivateKey {
+            left: body[..side_len::<P>()].to_vec(),
+            right: body[side_len::<P>()..].to_vec(),
+            parameters: PhantomData,
+        })
+    }
+}
+
+/// Hashes `message` to the `8 * N` bit digest whose bits select the
+/// preimages revealed. For [`Lamport256`] and [`Blake3`] this is the plain
+/// BLAKE3 hash.
+fn digest<P: ParameterSet, H: HashFunction>(message: &[u8]) -> Vec<u8> {
+    let mut digest = vec![0u8; P::N];
+    H::hash_into(&[message], &mut digest);
+    digest
+}
+
+/// Hashes each `size`-byte chunk of `chunks` into the corresponding
+/// `size`-byte chunk of `hashes`, as one pass over contiguous memory.
+///
+/// BLAKE3's multi-input SIMD kernel only accepts whole 64-byte blocks, and
+/// a 32-byte preimage is compressed with a different block length, so the
+/// chunks are hashed one at a time. Keeping every caller on this single
+/// loop means a batched kernel can be dropped in here without touching the
+/// key and signature formats.
+fn hash_chunks<H: HashFunction>(chunks: &[u8], size: usize, hashes: &mut [u8]) {
+    debug_assert_eq!(chunks.len(), hashes.len());
+    for (chunk, out) in chunks.chunks_exact(size).zip(hashes.chunks_exact_mut(size)) {
+        H::hash_into(&[chunk], out);
+    }
+}
+
+/// Checks the parameter set byte and length of an encoding, returning the
+/// bytes which follow the parameter set byte.
+fn decode_body<P: ParameterSet>(value: &[u8], len: usize) -> Result<&[u8], DecodingError> {
+    match value.split_first() {
+        None => Err(DecodingError::WrongLength {
+            expected: len + 1,
+            actual: 0,
+        }),
+        Some((&id, _)) if id != P::ID => Err(DecodingError::ParameterSetMismatch {
+            expected: P::ID,
+            found: id,
+        }),
+        Some((_, body)) if body.len() != len => Err(DecodingError::WrongLength {
+            expected: len + 1,
+            actual: value.len(),
+        }),
+        Some((_, body)) => Ok(body),
+    }
 }
 
 fn bit_of_byteslice(index: usize, bytes: &[u8]) -> bool {
@@ -131,101 +319,301 @@ fn test_bit_of_byte() {
 /// The public key associated with a given [`PrivateKey`], allowing any
 /// owner to [`PublicKey::verify`] a [`Signature`] produced by that
 /// [`PrivateKey`].
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct PublicKey {
-    left_hashes: [[u8; 32]; 256],
-    right_hashes: [[u8; 32]; 256],
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub struct PublicKey<P: ParameterSet = Lamport256, H: HashFunction = Blake3> {
+    left_hashes: Vec<u8>,
+    right_hashes: Vec<u8>,
+    parameters: PhantomData<(P, H)>,
 }
 
 impl From<&[u8; 16384]> for PublicKey {
     fn from(value: &[u8; 16384]) -> Self {
-        let mut left_hashes = [[0u8; 32]; 256];
-        let mut right_hashes = [[0u8; 32]; 256];
-
-        let mut i = 0;
-        for j in 0..256 {
-            for k in 0..32 {
-                left_hashes[j][k] = value[i];
-                i += 1;
-            }
-        }
-
-        for j in 0..256 {
-            for k in 0..32 {
-                right_hashes[j][k] = value[i];
-                i += 1;
-            }
-        }
-
-        PublicKey {
-            left_hashes,
-            right_hashes,
-        }
+        PublicKey::from_raw_bytes(value)
     }
 }
 
 impl From<&PublicKey> for [u8; 16384] {
     fn from(value: &PublicKey) -> Self {
+        value.to_raw_bytes()
+    }
+}
+
+impl<H: HashFunction> PublicKey<Lamport256, H> {
+    pub fn to_raw_bytes(&self) -> [u8; 16384] {
         let mut out = [0u8; 16384];
-        let mut i = 0;
-        for j in 0..256 {
-            for k in 0..32 {
-                out[i] = value.left_hashes[j][k];
-                i += 1;
-            }
-        }
-        for j in 0..256 {
-            for k in 0..32 {
-                out[i] = value.right_hashes[j][k];
-                i += 1;
-            }
-        }
+        out[..8192].copy_from_slice(&self.left_hashes);
+        out[8192..].copy_from_slice(&self.right_hashes);
         out
     }
-}
 
-impl PublicKey {
-    pub fn to_bytes(&self) -> [u8; 16384] {
-        self.into()
+    pub(crate) fn from_raw_bytes(value: &[u8; 16384]) -> Self {
+        PublicKey {
+            left_hashes: value[..8192].to_vec(),
+            right_hashes: value[8192..].to_vec(),
+            parameters: PhantomData,
+        }
     }
+}
 
-    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature) -> bool {
-        let msg_hash = hash(message.as_ref());
-        signature
-            .exposed
-            .chunks(32)
+impl<P: ParameterSet, H: HashFunction> PublicKey<P, H> {
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature<P, H>) -> bool {
+        let msg_hash = digest::<P, H>(message.as_ref());
+        let mut exposed_hashes = vec![0u8; side_len::<P>()];
+        hash_chunks::<H>(&signature.exposed, P::N, &mut exposed_hashes);
+        exposed_hashes
+            .chunks_exact(P::N)
             .zip(0..)
-            .fold(true, |acc, (chunk, i)| {
-                let public_hash = if bit_of_byteslice(i, msg_hash.as_bytes()) {
-                    self.left_hashes[i]
+            .fold(true, |acc, (exposed_hash, i)| {
+                let public_hashes = if bit_of_byteslice(i, &msg_hash) {
+                    &self.left_hashes
                 } else {
-                    self.right_hashes[i]
+                    &self.right_hashes
                 };
-                acc && hash(chunk).as_bytes() == &public_hash
+                acc & (exposed_hash == &public_hashes[i * P::N..(i + 1) * P::N])
             })
     }
+
+    /// The message digest which `signature` signs, read from which half of
+    /// the key each exposed value hashes into, or [`None`] if some value
+    /// hashes into neither.
+    pub(crate) fn signed_digest(&self, signature: &Signature<P, H>) -> Option<Vec<u8>> {
+        let mut exposed_hashes = vec![0u8; side_len::<P>()];
+        hash_chunks::<H>(&signature.exposed, P::N, &mut exposed_hashes);
+        let mut digest = vec![0u8; P::N];
+        for (exposed_hash, i) in exposed_hashes.chunks_exact(P::N).zip(0..) {
+            let range = i * P::N..(i + 1) * P::N;
+            if exposed_hash == &self.left_hashes[range.clone()] {
+                digest[i / 8] |= bitmask_for(i % 8);
+            } else if exposed_hash != &self.right_hashes[range] {
+                return None;
+            }
+        }
+        Some(digest)
+    }
+
+    /// Encodes the parameter set followed by the public key.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![P::ID];
+        out.extend_from_slice(&self.left_hashes);
+        out.extend_from_slice(&self.right_hashes);
+        out
+    }
+
+    /// The framed encoding, naming the hash function and parameter set.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an [`PublicKey::encode`]d or raw
+    /// one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// A short identifier of this key, covering its parameter set and hash
+    /// function.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::of(self)
+    }
+
+    fn from_body(body: &[u8]) -> Self {
+        PublicKey {
+            left_hashes: body[..side_len::<P>()].to_vec(),
+            right_hashes: body[side_len::<P>()..].to_vec(),
+            parameters: PhantomData,
+        }
+    }
+}
+
+impl<P: ParameterSet, H: HashFunction> TryFrom<&[u8]> for PublicKey<P, H> {
+    type Error = DecodingError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let body = decode_body::<P>(value, 2 * side_len::<P>())?;
+        Ok(PublicKey::from_body(body))
+    }
+}
+
+impl<P: ParameterSet, H: HashFunction> Framed for PublicKey<P, H> {
+    const ALGORITHM: Algorithm = Algorithm::Lamport;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        framed_parameter_set::<P, H>()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        [&self.left_hashes[..], &self.right_hashes[..]].concat()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        check_framed::<P, H>(parameter_set, body, 2 * side_len::<P>())?;
+        Ok(PublicKey::from_body(body))
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        match PublicKey::try_from(bytes) {
+            Ok(public_key) => Some(public_key),
+            Err(_) if bytes.len() == 2 * side_len::<P>() => Some(PublicKey::from_body(bytes)),
+            Err(_) => None,
+        }
+    }
 }
 
 /// The result of [`PrivateKey::sign`]ing a message. Can be verified
 /// to be from the [`PrivateKey`] associated with a [`PublicKey`]
 /// if you have that public key, the message, along with the signature.
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone)]
-pub struct Signature {
-    exposed: [u8; 8192],
+#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone, Hash)]
+pub struct Signature<P: ParameterSet = Lamport256, H: HashFunction = Blake3> {
+    exposed: Vec<u8>,
+    parameters: PhantomData<(P, H)>,
 }
 
 impl From<[u8; 8192]> for Signature {
     fn from(exposed: [u8; 8192]) -> Self {
-        Signature { exposed }
+        Signature::from_raw_bytes(exposed)
     }
 }
 
 impl From<Signature> for [u8; 8192] {
     fn from(signature: Signature) -> Self {
-        signature.exposed
+        signature.to_raw_bytes()
+    }
+}
+
+impl<H: HashFunction> Signature<Lamport256, H> {
+    pub(crate) fn to_raw_bytes(&self) -> [u8; 8192] {
+        let mut out = [0u8; 8192];
+        out.copy_from_slice(&self.exposed);
+        out
+    }
+
+    pub(crate) fn from_raw_bytes(exposed: [u8; 8192]) -> Self {
+        Signature {
+            exposed: exposed.to_vec(),
+            parameters: PhantomData,
+        }
+    }
+}
+
+impl<P: ParameterSet, H: HashFunction> Signature<P, H> {
+    /// Encodes the parameter set followed by the signature.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![P::ID];
+        out.extend_from_slice(&self.exposed);
+        out
+    }
+
+    /// The framed encoding, naming the hash function and parameter set.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an [`Signature::encode`]d or raw
+    /// one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// The framed encoding, carrying the [`PublicKey::fingerprint`] of the
+    /// signing key for [`wire::key_id`] to read back.
+    pub fn to_bytes_with_key_id(&self, key_id: &Fingerprint) -> Vec<u8> {
+        wire::to_bytes_with_key_id(self, key_id)
+    }
+}
+
+impl<P: ParameterSet, H: HashFunction> TryFrom<&[u8]> for Signature<P, H> {
+    type Error = DecodingError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let body = decode_body::<P>(value, side_len::<P>())?;
+        Ok(Signature {
+            exposed: body.to_vec(),
+            parameters: PhantomData,
+        })
     }
 }
 
+impl<P: ParameterSet, H: HashFunction> Framed for Signature<P, H> {
+    const ALGORITHM: Algorithm = Algorithm::Lamport;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        framed_parameter_set::<P, H>()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.exposed.clone()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        check_framed::<P, H>(parameter_set, body, side_len::<P>())?;
+        Ok(Signature {
+            exposed: body.to_vec(),
+            parameters: PhantomData,
+        })
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        match Signature::try_from(bytes) {
+            Ok(signature) => Some(signature),
+            Err(_) if bytes.len() == side_len::<P>() => Some(Signature {
+                exposed: bytes.to_vec(),
+                parameters: PhantomData,
+            }),
+            Err(_) => None,
+        }
+    }
+}
+
+/// The parameter set field of framed encodings: the hash function's byte
+/// followed by the parameter set's.
+fn framed_parameter_set<P: ParameterSet, H: HashFunction>() -> u32 {
+    ((H::ID as u32) << 8) | P::ID as u32
+}
+
+fn check_framed<P: ParameterSet, H: HashFunction>(
+    parameter_set: u32,
+    body: &[u8],
+    len: usize,
+) -> Result<(), wire::DecodingError> {
+    if parameter_set != framed_parameter_set::<P, H>() {
+        return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+    }
+    if body.len() != len {
+        return Err(wire::invalid_body(DecodingError::WrongLength {
+            expected: len,
+            actual: body.len(),
+        }));
+    }
+    Ok(())
+}
+
+/// An error encountered while decoding an encoded key or signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodingError {
+    /// The encoding was `actual` bytes long rather than `expected`.
+    WrongLength { expected: usize, actual: usize },
+    /// The encoding was made with the parameter set identified by `found`
+    /// rather than `expected`.
+    ParameterSetMismatch { expected: u8, found: u8 },
+}
+
+impl std::fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodingError::WrongLength { expected, actual } => {
+                write!(f, "expected {expected} bytes, found {actual}")
+            }
+            DecodingError::ParameterSetMismatch { expected, found } => {
+                write!(f, "expected parameter set {expected}, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -250,6 +638,93 @@ mod tests {
         Ok(())
     }
 
+    fn parameter_set_works<P: ParameterSet>(parameters: P) {
+        let private = PrivateKey::<P>::derive_with(parameters, &[1u8; 32], 0);
+        let public_key = private.public_key();
+        let signature = private.sign(b"Hello, world!");
+        assert_eq!(signature.encode().len(), 1 + 8 * P::N * P::N);
+        assert!(public_key.verify(b"Hello, world!", &signature));
+        assert!(!public_key.verify(b"Hello, not world!", &signature));
+
+        assert_eq!(PrivateKey::try_from(&private.encode()[..]), Ok(private));
+        assert_eq!(
+            PublicKey::try_from(&public_key.encode()[..]),
+            Ok(public_key)
+        );
+        assert_eq!(
+            Signature::try_from(&signature.encode()[..]),
+            Ok(signature.clone())
+        );
+        let encoded = signature.encode();
+        assert_eq!(
+            Signature::<P>::try_from(&encoded[..encoded.len() - 1]),
+            Err(DecodingError::WrongLength {
+                expected: encoded.len(),
+                actual: encoded.len() - 1,
+            })
+        );
+    }
+
+    #[test]
+    fn parameter_sets() {
+        parameter_set_works(Lamport128);
+        parameter_set_works(Lamport192);
+        parameter_set_works(Lamport256);
+    }
+
+    #[test]
+    fn rejects_other_parameter_sets() {
+        let signature =
+            PrivateKey::<Lamport128>::derive_with(Lamport128, &[1u8; 32], 0).sign(b"message");
+        assert_eq!(
+            Signature::<Lamport256>::try_from(&signature.encode()[..]),
+            Err(DecodingError::ParameterSetMismatch {
+                expected: Lamport256::ID,
+                found: Lamport128::ID,
+            })
+        );
+    }
+
+    #[test]
+    fn legacy_encodings() {
+        let private = PrivateKey::derive(&[2u8; 32], 7);
+        assert_eq!(private, PrivateKey::derive_with(Lamport256, &[2u8; 32], 7));
+        let raw = <[u8; 16384]>::from(&private);
+        assert_eq!(PrivateKey::from(&raw), private);
+
+        let public_key = private.public_key();
+        assert_eq!(PublicKey::from(&public_key.to_raw_bytes()), public_key);
+        assert_eq!(&public_key.encode()[1..], &public_key.to_raw_bytes()[..]);
+
+        let signature = private.sign(b"message");
+        let raw = <[u8; 8192]>::from(signature.clone());
+        assert_eq!(&signature.encode()[1..], &raw[..]);
+        assert!(public_key.verify(b"message", &Signature::from(raw)));
+    }
+
+    #[test]
+    fn debug_output_leaves_out_secrets() {
+        let private = PrivateKey::derive(&[3u8; 32], 0);
+        assert_eq!(
+            format!("{private:?}"),
+            "PrivateKey { parameter_set: 3, .. }"
+        );
+        let seeded = crate::merkle::seeded::PrivateKey::from_seed([3u8; 32], 2).unwrap();
+        assert_eq!(
+            format!("{seeded:?}"),
+            "PrivateKey { next_index: 0, num_items: 2, .. }"
+        );
+    }
+
+    #[test]
+    fn erasure_keeps_the_encoding_length() {
+        let mut private = PrivateKey::derive(&[4u8; 32], 0);
+        private.erase();
+        let encoded = private.encode();
+        assert_eq!(encoded.len(), PrivateKey::<Lamport256>::ENCODED_LEN);
+        assert!(encoded[1..].iter().all(|&byte| byte == 0));
+    }
+
     proptest! {
         #![proptest_config(ProptestConfig {
             cases: 999, .. ProptestConfig::default()
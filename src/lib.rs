@@ -1,7 +1,35 @@
+/// Short, checksummed identifiers of public keys, written `b3ls1...`.
+pub mod fingerprint;
+/// The hash functions which keys and trees can be built with: BLAKE3 by
+/// default, and SHA-256 and SHAKE256 behind the `sha2` and `sha3` features.
+pub mod hash;
+/// HORS and HORST few-time signatures, revealing a handful of secret values
+/// selected by the digest of each message.
+pub mod hors;
+/// Layers of Merkle trees, each tree's root signed by a leaf of the layer
+/// above, so that one small top-level commitment covers a vast number of
+/// signatures.
+pub mod hypertree;
 /// An implementation of Lamport signatures
 pub mod lamport;
+/// LMS and HSS signatures as specified by RFC 8554, with the standard
+/// SHA-256 parameter sets and private BLAKE3 ones.
+pub mod lms;
 /// Builds off of the Lamport signatures by implementing a
 /// Merkle commitment to a vector of Lamport public keys,
 /// with a Merkle proof coupled with a Lamport signature
 /// comprising a signature
 pub mod merkle;
+/// Durable storage for the index of a stateful key, reserved before each
+/// signature is released so that no leaf ever signs twice.
+pub mod state;
+/// Stateless signatures in the style of SPHINCS+: a few-time FORS layer
+/// signed by a hypertree of Lamport leaves, picked pseudorandomly from the
+/// message, so that no signing state needs to be kept.
+pub mod stateless;
+/// The framed encoding of public keys and signatures: a magic prefix,
+/// format version, algorithm, parameter set and length before every body.
+pub mod wire;
+/// XMSS signatures as specified by RFC 8391, with the standard SHA-256
+/// parameter sets and private BLAKE3 ones.
+pub mod xmss;
@@ -0,0 +1,1734 @@
+use blake3::Hash;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+use crate::hash::Family;
+use crate::merkle::internal::{ProofDecodingError, Reader};
+use crate::merkle::seeded::{Leaves, Traversal};
+use crate::state::{self, Reservation, SigningError, StateStore};
+use crate::stateless::read_bits;
+use crate::wire::{self, Algorithm, Framed, Kind};
+
+pub use crate::merkle::seeded::KeyDecodingError;
+
+const D_PBLC: [u8; 2] = [0x80, 0x80];
+const D_MESG: [u8; 2] = [0x81, 0x81];
+const D_LEAF: [u8; 2] = [0x82, 0x82];
+const D_INTR: [u8; 2] = [0x83, 0x83];
+
+/// An LM-OTS parameter set: the hash function, and the Winternitz parameter
+/// `w`, the number of bits of the message digest covered by each hash chain.
+///
+/// The SHA-256 sets carry their RFC 8554 typecodes and require the `sha2`
+/// feature. The BLAKE3 sets use typecodes from the range the RFC reserves
+/// for private use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OtsType {
+    #[cfg(feature = "sha2")]
+    Sha256N32W1,
+    #[cfg(feature = "sha2")]
+    Sha256N32W2,
+    #[cfg(feature = "sha2")]
+    Sha256N32W4,
+    #[cfg(feature = "sha2")]
+    Sha256N32W8,
+    Blake3N32W1,
+    Blake3N32W2,
+    Blake3N32W4,
+    Blake3N32W8,
+}
+
+impl OtsType {
+    const ALL: &'static [OtsType] = &[
+        #[cfg(feature = "sha2")]
+        OtsType::Sha256N32W1,
+        #[cfg(feature = "sha2")]
+        OtsType::Sha256N32W2,
+        #[cfg(feature = "sha2")]
+        OtsType::Sha256N32W4,
+        #[cfg(feature = "sha2")]
+        OtsType::Sha256N32W8,
+        OtsType::Blake3N32W1,
+        OtsType::Blake3N32W2,
+        OtsType::Blake3N32W4,
+        OtsType::Blake3N32W8,
+    ];
+
+    fn fields(self) -> (u32, Family, u8) {
+        match self {
+            #[cfg(feature = "sha2")]
+            OtsType::Sha256N32W1 => (1, Family::Sha256, 1),
+            #[cfg(feature = "sha2")]
+            OtsType::Sha256N32W2 => (2, Family::Sha256, 2),
+            #[cfg(feature = "sha2")]
+            OtsType::Sha256N32W4 => (3, Family::Sha256, 4),
+            #[cfg(feature = "sha2")]
+            OtsType::Sha256N32W8 => (4, Family::Sha256, 8),
+            OtsType::Blake3N32W1 => (0xE000_0001, Family::Blake3, 1),
+            OtsType::Blake3N32W2 => (0xE000_0002, Family::Blake3, 2),
+            OtsType::Blake3N32W4 => (0xE000_0003, Family::Blake3, 4),
+            OtsType::Blake3N32W8 => (0xE000_0004, Family::Blake3, 8),
+        }
+    }
+
+    pub fn typecode(self) -> u32 {
+        self.fields().0
+    }
+
+    /// The parameter set with `typecode`, if it is known and enabled.
+    pub fn from_typecode(typecode: u32) -> Option<OtsType> {
+        OtsType::ALL
+            .iter()
+            .copied()
+            .find(|ots_type| ots_type.typecode() == typecode)
+    }
+
+    fn family(self) -> Family {
+        self.fields().1
+    }
+
+    pub fn w(self) -> u8 {
+        self.fields().2
+    }
+
+    /// The number of digest coefficients, which is the number of chains
+    /// covering the message digest alone.
+    fn u1(self) -> u32 {
+        (8 * 32u32).div_ceil(self.w() as u32)
+    }
+
+    /// The number of hash chains, `p`, and the left shift applied to the
+    /// checksum, `ls`, as computed in appendix B of RFC 8554.
+    fn lengths(self) -> (usize, u32) {
+        let w = self.w() as u32;
+        let max_checksum = ((1 << w) - 1) * self.u1();
+        let u2 = (u32::BITS - max_checksum.leading_zeros()).div_ceil(w);
+        ((self.u1() + u2) as usize, 16 - u2 * w)
+    }
+
+    /// The number of hash chains, `p`.
+    pub fn p(self) -> usize {
+        self.lengths().0
+    }
+
+    /// The length in bytes of every encoded LM-OTS signature.
+    pub fn signature_len(self) -> usize {
+        4 + 32 + 32 * self.p()
+    }
+}
+
+/// An LMS parameter set: the hash function and the height of the tree, a
+/// key signing `2^height` messages.
+///
+/// The SHA-256 sets carry their RFC 8554 typecodes and require the `sha2`
+/// feature. The BLAKE3 sets use typecodes from the range the RFC reserves
+/// for private use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LmsType {
+    #[cfg(feature = "sha2")]
+    Sha256M32H5,
+    #[cfg(feature = "sha2")]
+    Sha256M32H10,
+    #[cfg(feature = "sha2")]
+    Sha256M32H15,
+    #[cfg(feature = "sha2")]
+    Sha256M32H20,
+    #[cfg(feature = "sha2")]
+    Sha256M32H25,
+    Blake3M32H5,
+    Blake3M32H10,
+    Blake3M32H15,
+    Blake3M32H20,
+    Blake3M32H25,
+}
+
+impl LmsType {
+    const ALL: &'static [LmsType] = &[
+        #[cfg(feature = "sha2")]
+        LmsType::Sha256M32H5,
+        #[cfg(feature = "sha2")]
+        LmsType::Sha256M32H10,
+        #[cfg(feature = "sha2")]
+        LmsType::Sha256M32H15,
+        #[cfg(feature = "sha2")]
+        LmsType::Sha256M32H20,
+        #[cfg(feature = "sha2")]
+        LmsType::Sha256M32H25,
+        LmsType::Blake3M32H5,
+        LmsType::Blake3M32H10,
+        LmsType::Blake3M32H15,
+        LmsType::Blake3M32H20,
+        LmsType::Blake3M32H25,
+    ];
+
+    fn fields(self) -> (u32, Family, u8) {
+        match self {
+            #[cfg(feature = "sha2")]
+            LmsType::Sha256M32H5 => (5, Family::Sha256, 5),
+            #[cfg(feature = "sha2")]
+            LmsType::Sha256M32H10 => (6, Family::Sha256, 10),
+            #[cfg(feature = "sha2")]
+            LmsType::Sha256M32H15 => (7, Family::Sha256, 15),
+            #[cfg(feature = "sha2")]
+            LmsType::Sha256M32H20 => (8, Family::Sha256, 20),
+            #[cfg(feature = "sha2")]
+            LmsType::Sha256M32H25 => (9, Family::Sha256, 25),
+            LmsType::Blake3M32H5 => (0xE000_0005, Family::Blake3, 5),
+            LmsType::Blake3M32H10 => (0xE000_0006, Family::Blake3, 10),
+            LmsType::Blake3M32H15 => (0xE000_0007, Family::Blake3, 15),
+            LmsType::Blake3M32H20 => (0xE000_0008, Family::Blake3, 20),
+            LmsType::Blake3M32H25 => (0xE000_0009, Family::Blake3, 25),
+        }
+    }
+
+    pub fn typecode(self) -> u32 {
+        self.fields().0
+    }
+
+    /// The parameter set with `typecode`, if it is known and enabled.
+    pub fn from_typecode(typecode: u32) -> Option<LmsType> {
+        LmsType::ALL
+            .iter()
+            .copied()
+            .find(|lms_type| lms_type.typecode() == typecode)
+    }
+
+    fn family(self) -> Family {
+        self.fields().1
+    }
+
+    pub fn height(self) -> u8 {
+        self.fields().2
+    }
+
+    /// The number of messages a key can sign.
+    pub fn num_items(self) -> u64 {
+        1 << self.height()
+    }
+}
+
+/// The secret value starting hash chain `i` of the LM-OTS key at leaf `q`,
+/// derived as in appendix A of RFC 8554.
+fn ots_secret(
+    ots_type: OtsType,
+    identifier: &[u8; 16],
+    q: u32,
+    i: u16,
+    seed: &[u8; 32],
+) -> [u8; 32] {
+    ots_type.family().hash(&[
+        identifier,
+        &q.to_be_bytes(),
+        &i.to_be_bytes(),
+        &[0xff],
+        seed,
+    ])
+}
+
+/// Applies the given `steps` of hash chain `i` of the LM-OTS key at leaf
+/// `q` to `value`.
+fn chain(
+    ots_type: OtsType,
+    identifier: &[u8; 16],
+    q: u32,
+    i: u16,
+    steps: std::ops::Range<u32>,
+    mut value: [u8; 32],
+) -> [u8; 32] {
+    for j in steps {
+        value = ots_type.family().hash(&[
+            identifier,
+            &q.to_be_bytes(),
+            &i.to_be_bytes(),
+            &[j as u8],
+            &value,
+        ]);
+    }
+    value
+}
+
+/// The public key `K` of the LM-OTS key at leaf `q`, given the ends of its
+/// hash chains.
+fn ots_public_key(ots_type: OtsType, identifier: &[u8; 16], q: u32, ends: &[[u8; 32]]) -> [u8; 32] {
+    let q = q.to_be_bytes();
+    let mut inputs: Vec<&[u8]> = vec![identifier, &q, &D_PBLC];
+    inputs.extend(ends.iter().map(|end| end.as_slice()));
+    ots_type.family().hash(&inputs)
+}
+
+/// The number of chain steps signing each coefficient of the digest of
+/// `message`, the last few covering its checksum.
+fn ots_coefficients(
+    ots_type: OtsType,
+    identifier: &[u8; 16],
+    q: u32,
+    randomizer: &[u8; 32],
+    message: &[u8],
+) -> Vec<u32> {
+    let w = ots_type.w() as u32;
+    let max = (1 << w) - 1;
+    let digest =
+        ots_type
+            .family()
+            .hash(&[identifier, &q.to_be_bytes(), &D_MESG, randomizer, message]);
+    let coefficient = |bytes: &[u8], i: usize| read_bits(bytes, i * w as usize, w) as u32;
+
+    let checksum: u32 = (0..ots_type.u1() as usize)
+        .map(|i| max - coefficient(&digest, i))
+        .sum();
+    let (p, ls) = ots_type.lengths();
+    let mut digest_with_checksum = digest.to_vec();
+    digest_with_checksum.extend(((checksum << ls) as u16).to_be_bytes());
+    (0..p)
+        .map(|i| coefficient(&digest_with_checksum, i))
+        .collect()
+}
+
+/// The public key of the LM-OTS key at leaf `q`.
+fn ots_derive_public_key(
+    ots_type: OtsType,
+    identifier: &[u8; 16],
+    q: u32,
+    seed: &[u8; 32],
+) -> [u8; 32] {
+    let max = (1 << ots_type.w()) - 1;
+    let ends: Vec<[u8; 32]> = (0..ots_type.p() as u16)
+        .map(|i| {
+            let secret = ots_secret(ots_type, identifier, q, i, seed);
+            chain(ots_type, identifier, q, i, 0..max, secret)
+        })
+        .collect();
+    ots_public_key(ots_type, identifier, q, &ends)
+}
+
+/// Signs `message` with the LM-OTS key at leaf `q`. The randomizer `C` is
+/// derived from the seed like the chain secrets, with the otherwise unused
+/// chain index `0xfffd`, so signing needs no randomness.
+fn ots_sign(
+    ots_type: OtsType,
+    identifier: &[u8; 16],
+    q: u32,
+    seed: &[u8; 32],
+    message: &[u8],
+) -> OtsSignature {
+    let randomizer = ots_secret(ots_type, identifier, q, 0xfffd, seed);
+    let coefficients = ots_coefficients(ots_type, identifier, q, &randomizer, message);
+    let chains = coefficients
+        .iter()
+        .zip(0u16..)
+        .map(|(&steps, i)| {
+            let secret = ots_secret(ots_type, identifier, q, i, seed);
+            chain(ots_type, identifier, q, i, 0..steps, secret)
+        })
+        .collect();
+    OtsSignature {
+        ots_type,
+        randomizer,
+        chains,
+    }
+}
+
+/// The LM-OTS public key `signature` would verify `message` under, as in
+/// algorithm 4b of RFC 8554.
+fn ots_recover(
+    identifier: &[u8; 16],
+    q: u32,
+    signature: &OtsSignature,
+    message: &[u8],
+) -> [u8; 32] {
+    let ots_type = signature.ots_type;
+    let max = (1 << ots_type.w()) - 1;
+    let coefficients = ots_coefficients(ots_type, identifier, q, &signature.randomizer, message);
+    let ends: Vec<[u8; 32]> = coefficients
+        .iter()
+        .zip(signature.chains.iter())
+        .zip(0u16..)
+        .map(|((&steps, &value), i)| chain(ots_type, identifier, q, i, steps..max, value))
+        .collect();
+    ots_public_key(ots_type, identifier, q, &ends)
+}
+
+/// The tree node numbered `r`, counting from 1 at the root, whose leaf is
+/// the LM-OTS public key `ots_public_key`.
+fn leaf_node(lms_type: LmsType, identifier: &[u8; 16], r: u32, ots_public_key: &[u8; 32]) -> Hash {
+    Hash::from(
+        lms_type
+            .family()
+            .hash(&[identifier, &r.to_be_bytes(), &D_LEAF, ots_public_key]),
+    )
+}
+
+/// The tree node numbered `r`, counting from 1 at the root, whose children
+/// are `left` and `right`.
+fn interior_node(
+    lms_type: LmsType,
+    identifier: &[u8; 16],
+    r: u32,
+    left: &Hash,
+    right: &Hash,
+) -> Hash {
+    Hash::from(lms_type.family().hash(&[
+        identifier,
+        &r.to_be_bytes(),
+        &D_INTR,
+        left.as_bytes(),
+        right.as_bytes(),
+    ]))
+}
+
+/// The leaves of an LMS tree, regenerated from its seed.
+struct LmsLeaves<'a> {
+    lms_type: LmsType,
+    ots_type: OtsType,
+    identifier: &'a [u8; 16],
+    seed: &'a [u8; 32],
+}
+
+impl Leaves for LmsLeaves<'_> {
+    fn leaf(&self, index: u64) -> Hash {
+        let q = index as u32;
+        let ots_public_key = ots_derive_public_key(self.ots_type, self.identifier, q, self.seed);
+        let r = (1 << self.lms_type.height()) + q;
+        leaf_node(self.lms_type, self.identifier, r, &ots_public_key)
+    }
+
+    fn parent(&self, level: usize, index: u64, left: &Hash, right: &Hash) -> Hash {
+        let r = (1 << (self.lms_type.height() as usize - level)) + index as u32;
+        interior_node(self.lms_type, self.identifier, r, left, right)
+    }
+}
+
+/// An LMS private key: a seed from which LM-OTS keys are derived, the tree
+/// identifier `I`, and the state of the traversal shared with
+/// [`crate::merkle::seeded::PrivateKey`], which keeps only `O(log n)` nodes.
+///
+/// RFC 8554 leaves the private key format to implementations, and
+/// [`PrivateKey::to_bytes`] writes the typecodes, identifier and seed
+/// followed by the traversal state.
+///
+/// Like a seeded Merkle key, it keeps the seed of used leaves, so it has no
+/// forward security.
+#[derive(PartialEq, Eq)]
+pub struct PrivateKey {
+    lms_type: LmsType,
+    ots_type: OtsType,
+    identifier: [u8; 16],
+    seed: [u8; 32],
+    traversal: Traversal,
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("lms_type", &self.lms_type)
+            .field("ots_type", &self.ots_type)
+            .field("next_index", &self.current_index())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl PrivateKey {
+    pub fn generate(lms_type: LmsType, ots_type: OtsType) -> Result<PrivateKey, rand::Error> {
+        let mut identifier = [0u8; 16];
+        let mut seed = [0u8; 32];
+        OsRng.try_fill_bytes(&mut identifier)?;
+        OsRng.try_fill_bytes(&mut seed)?;
+        Ok(PrivateKey::from_seed(lms_type, ots_type, identifier, seed))
+    }
+
+    /// Builds the key with tree identifier `identifier` whose LM-OTS keys
+    /// are derived from `seed`. This computes every leaf once, so it takes
+    /// time proportional to the number of messages the key can sign.
+    pub fn from_seed(
+        lms_type: LmsType,
+        ots_type: OtsType,
+        identifier: [u8; 16],
+        seed: [u8; 32],
+    ) -> PrivateKey {
+        let leaves = LmsLeaves {
+            lms_type,
+            ots_type,
+            identifier: &identifier,
+            seed: &seed,
+        };
+        let traversal =
+            Traversal::new(&leaves, lms_type.num_items()).expect("LMS trees are never empty");
+        PrivateKey {
+            lms_type,
+            ots_type,
+            identifier,
+            seed,
+            traversal,
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            lms_type: self.lms_type,
+            ots_type: self.ots_type,
+            identifier: self.identifier,
+            root: self.traversal.root(),
+        }
+    }
+
+    /// The index `q` of the leaf the next signature will use.
+    pub fn current_index(&self) -> u64 {
+        self.traversal.next_index()
+    }
+
+    pub fn num_items(&self) -> u64 {
+        self.traversal.num_items()
+    }
+
+    /// Signs with the next unused leaf, returning [`None`] once every leaf
+    /// has been used.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<Signature> {
+        let index = self.traversal.next_index();
+        if index >= self.traversal.num_items() {
+            return None;
+        }
+        let q = index as u32;
+        let ots = ots_sign(
+            self.ots_type,
+            &self.identifier,
+            q,
+            &self.seed,
+            message.as_ref(),
+        );
+        let path = self
+            .traversal
+            .auth_path()
+            .iter()
+            .map(|sibling| sibling.expect("LMS trees are perfect"))
+            .collect();
+
+        let leaves = LmsLeaves {
+            lms_type: self.lms_type,
+            ots_type: self.ots_type,
+            identifier: &self.identifier,
+            seed: &self.seed,
+        };
+        let leaf = leaves.leaf(index);
+        self.traversal.advance(&leaves, leaf);
+
+        Some(Signature {
+            q,
+            ots,
+            lms_type: self.lms_type,
+            path,
+        })
+    }
+
+    /// Signs like [`PrivateKey::sign`], but only after durably recording in
+    /// `store` that the index is used. If `store` is ahead of this key, as
+    /// it is for a key restored from an old copy, the traversal is first
+    /// advanced past the indices in between.
+    pub fn sign_persisted<S: StateStore, A: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        message: A,
+    ) -> Result<Signature, SigningError<S::Error>> {
+        let mut reservation = self.reserve(store, 1)?;
+        Ok(self
+            .sign_reserved(&mut reservation, message)
+            .expect("reserved indices are in range"))
+    }
+
+    /// Durably reserves the next `count` indices in `store` with a single
+    /// write, like [`crate::merkle::PrivateKey::reserve`].
+    pub fn reserve<S: StateStore>(
+        &mut self,
+        store: &mut S,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        state::reserve(store, self.current_index(), self.num_items(), count)
+    }
+
+    /// Signs with the next index of `reservation`, which must have been
+    /// made for this key, first advancing the traversal past any indices
+    /// skipped to reach it. Returns [`None`] once the block is used up.
+    pub fn sign_reserved<A: AsRef<[u8]>>(
+        &mut self,
+        reservation: &mut Reservation,
+        message: A,
+    ) -> Option<Signature> {
+        let index = reservation.take_from(self.current_index())?;
+        self.skip_to(index);
+        self.sign(message)
+    }
+
+    /// Advances the traversal to leaf `index` without signing with the
+    /// leaves before it.
+    fn skip_to(&mut self, index: u64) {
+        let leaves = LmsLeaves {
+            lms_type: self.lms_type,
+            ots_type: self.ots_type,
+            identifier: &self.identifier,
+            seed: &self.seed,
+        };
+        while self.traversal.next_index() < index.min(self.traversal.num_items()) {
+            let leaf = leaves.leaf(self.traversal.next_index());
+            self.traversal.advance(&leaves, leaf);
+        }
+    }
+
+    /// Encodes the whole signing state, including the secret seed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.encode(&mut output);
+        output
+    }
+
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.extend(self.lms_type.typecode().to_be_bytes());
+        output.extend(self.ots_type.typecode().to_be_bytes());
+        output.extend_from_slice(&self.identifier);
+        output.extend_from_slice(&self.seed);
+        self.traversal.encode(output);
+    }
+
+    fn decode_from(reader: &mut Reader) -> Result<PrivateKey, KeyDecodingError> {
+        let offset = reader.offset();
+        let lms_type = LmsType::from_typecode(u32::from_be_bytes(reader.array()?))
+            .ok_or(KeyDecodingError::InvalidField { offset })?;
+        let offset = reader.offset();
+        let ots_type = OtsType::from_typecode(u32::from_be_bytes(reader.array()?))
+            .ok_or(KeyDecodingError::InvalidField { offset })?;
+        let identifier = reader.array()?;
+        let seed = reader.array()?;
+        let offset = reader.offset();
+        let traversal = Traversal::decode(reader)?;
+        if traversal.num_items() != lms_type.num_items() {
+            return Err(KeyDecodingError::InvalidField { offset });
+        }
+        Ok(PrivateKey {
+            lms_type,
+            ots_type,
+            identifier,
+            seed,
+            traversal,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for PrivateKey {
+    type Error = KeyDecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let private_key = PrivateKey::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(private_key)
+    }
+}
+
+/// An LMS public key, encoded as in section 5.3 of RFC 8554.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PublicKey {
+    lms_type: LmsType,
+    ots_type: OtsType,
+    identifier: [u8; 16],
+    root: Hash,
+}
+
+impl PublicKey {
+    pub fn lms_type(&self) -> LmsType {
+        self.lms_type
+    }
+
+    pub fn ots_type(&self) -> OtsType {
+        self.ots_type
+    }
+
+    /// Verifies `signature` as in algorithm 6a of RFC 8554.
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature) -> bool {
+        if signature.lms_type != self.lms_type || signature.ots.ots_type != self.ots_type {
+            return false;
+        }
+        let height = self.lms_type.height();
+        if signature.q as u64 >= self.lms_type.num_items()
+            || signature.path.len() != height as usize
+        {
+            return false;
+        }
+
+        let ots_public_key = ots_recover(
+            &self.identifier,
+            signature.q,
+            &signature.ots,
+            message.as_ref(),
+        );
+        let mut r = (1 << height) + signature.q;
+        let mut node = leaf_node(self.lms_type, &self.identifier, r, &ots_public_key);
+        for sibling in signature.path.iter() {
+            node = if r % 2 == 1 {
+                interior_node(self.lms_type, &self.identifier, r / 2, sibling, &node)
+            } else {
+                interior_node(self.lms_type, &self.identifier, r / 2, &node, sibling)
+            };
+            r /= 2;
+        }
+        node == self.root
+    }
+
+    /// The framed encoding, whose body is the RFC 8554 encoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an RFC 8554 one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// The RFC 8554 encoding.
+    pub fn to_raw_bytes(&self) -> [u8; 56] {
+        let mut output = [0u8; 56];
+        output[..4].copy_from_slice(&self.lms_type.typecode().to_be_bytes());
+        output[4..8].copy_from_slice(&self.ots_type.typecode().to_be_bytes());
+        output[8..24].copy_from_slice(&self.identifier);
+        output[24..].copy_from_slice(self.root.as_bytes());
+        output
+    }
+
+    fn decode_from(reader: &mut Reader) -> Result<PublicKey, DecodingError> {
+        let lms_type = read_typecode(reader, LmsType::from_typecode)?;
+        let ots_type = read_typecode(reader, OtsType::from_typecode)?;
+        let identifier = reader.array()?;
+        let root = Hash::from(reader.array::<32>()?);
+        Ok(PublicKey {
+            lms_type,
+            ots_type,
+            identifier,
+            root,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = DecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let public_key = PublicKey::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(public_key)
+    }
+}
+
+/// An LM-OTS signature: the randomizer `C` and one value from each chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct OtsSignature {
+    ots_type: OtsType,
+    randomizer: [u8; 32],
+    chains: Vec<[u8; 32]>,
+}
+
+/// An LMS signature, encoded as in section 5.4 of RFC 8554.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    q: u32,
+    ots: OtsSignature,
+    lms_type: LmsType,
+    path: Vec<Hash>,
+}
+
+impl Signature {
+    /// The index `q` of the leaf which produced this signature.
+    pub fn index(&self) -> u32 {
+        self.q
+    }
+
+    /// The framed encoding, whose body is the RFC 8554 encoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an RFC 8554 one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.extend(self.q.to_be_bytes());
+        output.extend(self.ots.ots_type.typecode().to_be_bytes());
+        output.extend_from_slice(&self.ots.randomizer);
+        for value in self.ots.chains.iter() {
+            output.extend_from_slice(value);
+        }
+        output.extend(self.lms_type.typecode().to_be_bytes());
+        for sibling in self.path.iter() {
+            output.extend_from_slice(sibling.as_bytes());
+        }
+    }
+
+    fn decode_from(reader: &mut Reader) -> Result<Signature, DecodingError> {
+        let q_offset = reader.offset();
+        let q = u32::from_be_bytes(reader.array()?);
+        let ots_type = read_typecode(reader, OtsType::from_typecode)?;
+        let randomizer = reader.array()?;
+        let chains = (0..ots_type.p())
+            .map(|_| reader.array())
+            .collect::<Result<Vec<_>, _>>()?;
+        let lms_type = read_typecode(reader, LmsType::from_typecode)?;
+        if q as u64 >= lms_type.num_items() {
+            return Err(DecodingError::InvalidField { offset: q_offset });
+        }
+        let path = (0..lms_type.height())
+            .map(|_| reader.array::<32>().map(Hash::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Signature {
+            q,
+            ots: OtsSignature {
+                ots_type,
+                randomizer,
+                chains,
+            },
+            lms_type,
+            path,
+        })
+    }
+}
+
+impl From<&Signature> for Vec<u8> {
+    fn from(signature: &Signature) -> Self {
+        let mut output = Vec::new();
+        signature.encode(&mut output);
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for Signature {
+    type Error = DecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let signature = Signature::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(signature)
+    }
+}
+
+/// The most levels an HSS key may have, per section 6 of RFC 8554.
+const MAX_LEVELS: usize = 8;
+
+/// An HSS private key: a stack of LMS keys, each but the last signing the
+/// public key of the one below it, with the last signing messages.
+///
+/// Once the bottom key is exhausted, the lowest key with leaves left signs
+/// a fresh key for each level below it. The seed and identifier of a child
+/// key are derived from its parent's seed and the leaf signing it, like the
+/// chain secrets of an LM-OTS key.
+#[derive(PartialEq, Eq)]
+pub struct HssPrivateKey {
+    keys: Vec<PrivateKey>,
+    signed_keys: Vec<Signature>,
+}
+
+/// An error encountered while generating an [`HssPrivateKey`].
+#[derive(Debug)]
+pub enum KeyGenerationError {
+    /// The operating system random number generator failed.
+    Rng(rand::Error),
+    /// `levels` parameter sets were given, but HSS keys have between one
+    /// and eight levels.
+    InvalidLevels { levels: usize },
+}
+
+impl std::fmt::Display for KeyGenerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyGenerationError::Rng(e) => write!(f, "random number generation failed: {e}"),
+            KeyGenerationError::InvalidLevels { levels } => {
+                write!(f, "{levels} levels given, but HSS keys have one to eight")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeyGenerationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KeyGenerationError::Rng(e) => Some(e),
+            KeyGenerationError::InvalidLevels { .. } => None,
+        }
+    }
+}
+
+impl From<rand::Error> for KeyGenerationError {
+    fn from(e: rand::Error) -> Self {
+        KeyGenerationError::Rng(e)
+    }
+}
+
+/// The key signed by the next leaf of `parent`.
+fn child_key(parent: &PrivateKey, lms_type: LmsType, ots_type: OtsType) -> PrivateKey {
+    let q = parent.current_index() as u32;
+    let family = parent.ots_type.family();
+    let derive = |i: u16| {
+        family.hash(&[
+            &parent.identifier,
+            &q.to_be_bytes(),
+            &i.to_be_bytes(),
+            &[0xff],
+            &parent.seed,
+        ])
+    };
+    let seed = derive(0xfffe);
+    let mut identifier = [0u8; 16];
+    identifier.copy_from_slice(&derive(0xffff)[..16]);
+    PrivateKey::from_seed(lms_type, ots_type, identifier, seed)
+}
+
+impl std::fmt::Debug for HssPrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HssPrivateKey")
+            .field("levels", &self.keys.len())
+            .field("next_index", &self.current_index())
+            .finish_non_exhaustive()
+    }
+}
+
+impl HssPrivateKey {
+    /// Generates a key whose levels, top first, use the given parameter
+    /// sets.
+    pub fn generate(levels: &[(LmsType, OtsType)]) -> Result<HssPrivateKey, KeyGenerationError> {
+        let mut identifier = [0u8; 16];
+        let mut seed = [0u8; 32];
+        OsRng.try_fill_bytes(&mut identifier)?;
+        OsRng.try_fill_bytes(&mut seed)?;
+        HssPrivateKey::from_seed(levels, identifier, seed)
+    }
+
+    /// Builds the key whose top-level LMS key has tree identifier
+    /// `identifier` and is derived from `seed`, and whose levels, top first,
+    /// use the given parameter sets.
+    pub fn from_seed(
+        levels: &[(LmsType, OtsType)],
+        identifier: [u8; 16],
+        seed: [u8; 32],
+    ) -> Result<HssPrivateKey, KeyGenerationError> {
+        let (&(lms_type, ots_type), lower) = match levels.split_first() {
+            Some(split) if levels.len() <= MAX_LEVELS => split,
+            _ => {
+                return Err(KeyGenerationError::InvalidLevels {
+                    levels: levels.len(),
+                })
+            }
+        };
+        let mut private_key = HssPrivateKey {
+            keys: vec![PrivateKey::from_seed(lms_type, ots_type, identifier, seed)],
+            signed_keys: Vec::with_capacity(lower.len()),
+        };
+        for &(lms_type, ots_type) in lower {
+            let parent = private_key.keys.last_mut().expect("the top level exists");
+            let child = child_key(parent, lms_type, ots_type);
+            let signature = parent
+                .sign(child.public_key().to_raw_bytes())
+                .expect("fresh keys have unused leaves");
+            private_key.signed_keys.push(signature);
+            private_key.keys.push(child);
+        }
+        Ok(private_key)
+    }
+
+    pub fn public_key(&self) -> HssPublicKey {
+        HssPublicKey {
+            levels: self.keys.len() as u32,
+            key: self.keys[0].public_key(),
+        }
+    }
+
+    /// The number of signatures made so far, counting those of every
+    /// bottom key before the current one.
+    pub fn current_index(&self) -> u64 {
+        let bottom = self.keys.len() - 1;
+        // each upper key has used its last leaf on the key below it
+        self.keys[..bottom]
+            .iter()
+            .zip(&self.keys[1..])
+            .fold(0u64, |index, (key, child)| {
+                index
+                    .saturating_add(key.current_index() - 1)
+                    .saturating_mul(child.num_items())
+            })
+            .saturating_add(self.keys[bottom].current_index())
+    }
+
+    /// The number of messages the key can sign, the product of the number
+    /// of leaves of every level, or [`u64::MAX`] if that is larger.
+    pub fn num_items(&self) -> u64 {
+        self.keys
+            .iter()
+            .fold(1u64, |count, key| count.saturating_mul(key.num_items()))
+    }
+
+    /// Signs with the next unused leaf of the bottom key, first replacing
+    /// any exhausted lower levels, and returns [`None`] once every level is
+    /// exhausted.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<HssSignature> {
+        let exhausted = |key: &PrivateKey| key.current_index() >= key.num_items();
+        let bottom = self.keys.len() - 1;
+        if exhausted(&self.keys[bottom]) {
+            let level = (0..bottom)
+                .rev()
+                .find(|&level| !exhausted(&self.keys[level]))?;
+            for level in level + 1..=bottom {
+                let (upper, lower) = self.keys.split_at_mut(level);
+                let parent = &mut upper[level - 1];
+                let child = child_key(parent, lower[0].lms_type, lower[0].ots_type);
+                self.signed_keys[level - 1] = parent.sign(child.public_key().to_raw_bytes())?;
+                lower[0] = child;
+            }
+        }
+
+        let signature = self.keys[bottom].sign(message)?;
+        Some(HssSignature {
+            signed_keys: self
+                .signed_keys
+                .iter()
+                .cloned()
+                .zip(self.keys[1..].iter().map(PrivateKey::public_key))
+                .collect(),
+            signature,
+        })
+    }
+
+    /// Signs like [`HssPrivateKey::sign`], but only after durably recording
+    /// in `store` that the index is used, counting signatures as
+    /// [`HssPrivateKey::current_index`] does. If `store` is ahead of this
+    /// key, the levels are first advanced to where signing every index in
+    /// between would have left them.
+    pub fn sign_persisted<S: StateStore, A: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        message: A,
+    ) -> Result<HssSignature, SigningError<S::Error>> {
+        let mut reservation = self.reserve(store, 1)?;
+        Ok(self
+            .sign_reserved(&mut reservation, message)
+            .expect("reserved indices are in range"))
+    }
+
+    /// Durably reserves the next `count` indices in `store` with a single
+    /// write, like [`crate::merkle::PrivateKey::reserve`].
+    pub fn reserve<S: StateStore>(
+        &mut self,
+        store: &mut S,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        state::reserve(store, self.current_index(), self.num_items(), count)
+    }
+
+    /// Signs with the next index of `reservation`, which must have been
+    /// made for this key, first advancing past any indices skipped to reach
+    /// it. Returns [`None`] once the block is used up.
+    pub fn sign_reserved<A: AsRef<[u8]>>(
+        &mut self,
+        reservation: &mut Reservation,
+        message: A,
+    ) -> Option<HssSignature> {
+        let index = reservation.take_from(self.current_index())?;
+        self.skip_to(index)?;
+        self.sign(message)
+    }
+
+    /// Moves every level to where signing up to `index` would have left
+    /// it. The levels below the highest one which moves are replaced by the
+    /// keys signed at their new positions, without building those skipped.
+    fn skip_to(&mut self, index: u64) -> Option<()> {
+        if index <= self.current_index() {
+            return Some(());
+        }
+        // the leaf of each level on the way to signature `index`
+        let mut leaves = vec![0u64; self.keys.len()];
+        let mut rest = index;
+        for (leaf, key) in leaves.iter_mut().zip(&self.keys).rev() {
+            *leaf = rest % key.num_items();
+            rest /= key.num_items();
+        }
+        if rest > 0 {
+            return None;
+        }
+
+        let bottom = self.keys.len() - 1;
+        let on_path = |level: usize, key: &PrivateKey| match level {
+            level if level == bottom => key.current_index() == leaves[level],
+            _ => key.current_index() - 1 == leaves[level],
+        };
+        let level = (0..bottom)
+            .find(|&level| !on_path(level, &self.keys[level]))
+            .unwrap_or(bottom);
+        self.keys[level].skip_to(leaves[level]);
+        for level in level + 1..=bottom {
+            let (upper, lower) = self.keys.split_at_mut(level);
+            let parent = &mut upper[level - 1];
+            parent.skip_to(leaves[level - 1]);
+            let mut child = child_key(parent, lower[0].lms_type, lower[0].ots_type);
+            self.signed_keys[level - 1] = parent.sign(child.public_key().to_raw_bytes())?;
+            child.skip_to(leaves[level]);
+            lower[0] = child;
+        }
+        Some(())
+    }
+
+    /// Encodes the whole signing state of every level, including the
+    /// secret seeds.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend((self.keys.len() as u32).to_be_bytes());
+        self.keys[0].encode(&mut output);
+        for (signature, key) in self.signed_keys.iter().zip(self.keys[1..].iter()) {
+            signature.encode(&mut output);
+            key.encode(&mut output);
+        }
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for HssPrivateKey {
+    type Error = KeyDecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let offset = reader.offset();
+        let levels = u32::from_be_bytes(reader.array()?) as usize;
+        if levels == 0 || levels > MAX_LEVELS {
+            return Err(KeyDecodingError::InvalidField { offset });
+        }
+        let mut keys = vec![PrivateKey::decode_from(&mut reader)?];
+        let mut signed_keys = Vec::with_capacity(levels - 1);
+        for _ in 1..levels {
+            let offset = reader.offset();
+            let signature = Signature::decode_from(&mut reader)?;
+            let key = PrivateKey::decode_from(&mut reader)?;
+            // each upper key must have signed the key below it with its last
+            // used leaf, which signing and skipping count on
+            let parent = keys.last().expect("the top level exists");
+            if parent.current_index() != u64::from(signature.q) + 1
+                || !parent
+                    .public_key()
+                    .verify(key.public_key().to_raw_bytes(), &signature)
+            {
+                return Err(KeyDecodingError::InvalidField { offset });
+            }
+            signed_keys.push(signature);
+            keys.push(key);
+        }
+        reader.finish()?;
+        Ok(HssPrivateKey { keys, signed_keys })
+    }
+}
+
+/// An HSS public key: the number of levels and the top-level LMS public
+/// key, encoded as in section 6.1 of RFC 8554.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HssPublicKey {
+    levels: u32,
+    key: PublicKey,
+}
+
+impl HssPublicKey {
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    /// Verifies `signature` as in algorithm 8 of RFC 8554.
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &HssSignature) -> bool {
+        if signature.signed_keys.len() + 1 != self.levels as usize {
+            return false;
+        }
+        let mut key = &self.key;
+        for (key_signature, signed_key) in signature.signed_keys.iter() {
+            if !key.verify(signed_key.to_raw_bytes(), key_signature) {
+                return false;
+            }
+            key = signed_key;
+        }
+        key.verify(message, &signature.signature)
+    }
+
+    /// The framed encoding, whose body is the RFC 8554 encoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an RFC 8554 one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// The RFC 8554 encoding.
+    pub fn to_raw_bytes(&self) -> [u8; 60] {
+        let mut output = [0u8; 60];
+        output[..4].copy_from_slice(&self.levels.to_be_bytes());
+        output[4..].copy_from_slice(&self.key.to_raw_bytes());
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for HssPublicKey {
+    type Error = DecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let offset = reader.offset();
+        let levels = u32::from_be_bytes(reader.array()?);
+        if levels == 0 || levels as usize > MAX_LEVELS {
+            return Err(DecodingError::InvalidField { offset });
+        }
+        let key = PublicKey::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(HssPublicKey { levels, key })
+    }
+}
+
+/// An HSS signature: the signed public key of every level below the top,
+/// then the bottom key's signature of the message, encoded as in section
+/// 6.2 of RFC 8554.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HssSignature {
+    signed_keys: Vec<(Signature, PublicKey)>,
+    signature: Signature,
+}
+
+impl HssSignature {
+    /// The framed encoding, whose body is the RFC 8554 encoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an RFC 8554 one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+}
+
+impl From<&HssSignature> for Vec<u8> {
+    fn from(signature: &HssSignature) -> Self {
+        let mut output = Vec::new();
+        output.extend((signature.signed_keys.len() as u32).to_be_bytes());
+        for (key_signature, signed_key) in signature.signed_keys.iter() {
+            key_signature.encode(&mut output);
+            output.extend_from_slice(&signed_key.to_raw_bytes());
+        }
+        signature.signature.encode(&mut output);
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for HssSignature {
+    type Error = DecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let offset = reader.offset();
+        let signed = u32::from_be_bytes(reader.array()?) as usize;
+        if signed >= MAX_LEVELS {
+            return Err(DecodingError::InvalidField { offset });
+        }
+        let mut signed_keys = Vec::with_capacity(signed);
+        for _ in 0..signed {
+            let key_signature = Signature::decode_from(&mut reader)?;
+            signed_keys.push((key_signature, PublicKey::decode_from(&mut reader)?));
+        }
+        let signature = Signature::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(HssSignature {
+            signed_keys,
+            signature,
+        })
+    }
+}
+
+impl Framed for PublicKey {
+    const ALGORITHM: Algorithm = Algorithm::Lms;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        self.lms_type.typecode()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.to_raw_bytes().to_vec()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        from_checked_body(parameter_set, body)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        PublicKey::try_from(bytes).ok()
+    }
+}
+
+impl Framed for Signature {
+    const ALGORITHM: Algorithm = Algorithm::Lms;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        self.lms_type.typecode()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        from_checked_body(parameter_set, body)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        Signature::try_from(bytes).ok()
+    }
+}
+
+impl Framed for HssPublicKey {
+    const ALGORITHM: Algorithm = Algorithm::Hss;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        self.levels
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.to_raw_bytes().to_vec()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        from_checked_body(parameter_set, body)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        HssPublicKey::try_from(bytes).ok()
+    }
+}
+
+impl Framed for HssSignature {
+    const ALGORITHM: Algorithm = Algorithm::Hss;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        self.signed_keys.len() as u32 + 1
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        from_checked_body(parameter_set, body)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        HssSignature::try_from(bytes).ok()
+    }
+}
+
+/// Decodes an RFC 8554 body, which names its own parameters, and checks
+/// they are the ones named by the frame: the LMS typecode, or the number
+/// of HSS levels.
+fn from_checked_body<T>(parameter_set: u32, body: &[u8]) -> Result<T, wire::DecodingError>
+where
+    T: Framed + for<'a> TryFrom<&'a [u8], Error = DecodingError>,
+{
+    let decoded = T::try_from(body).map_err(wire::invalid_body)?;
+    if decoded.parameter_set() != parameter_set {
+        return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+    }
+    Ok(decoded)
+}
+
+fn read_typecode<T>(
+    reader: &mut Reader,
+    from_typecode: impl Fn(u32) -> Option<T>,
+) -> Result<T, DecodingError> {
+    let offset = reader.offset();
+    let typecode = u32::from_be_bytes(reader.array()?);
+    from_typecode(typecode).ok_or(DecodingError::UnknownTypecode { offset, typecode })
+}
+
+/// An error encountered while decoding an LMS or HSS public key or
+/// signature. Every offset is the position, in bytes, within the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodingError {
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// The typecode at `offset` is unknown, or belongs to a parameter set
+    /// whose feature is disabled.
+    UnknownTypecode { offset: usize, typecode: u32 },
+    /// The field at `offset` holds a value no signer could have written.
+    InvalidField { offset: usize },
+    /// The input ended at `offset`, but `remaining` bytes followed it.
+    TrailingBytes { offset: usize, remaining: usize },
+}
+
+impl std::fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "input truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            DecodingError::UnknownTypecode { offset, typecode } => {
+                write!(f, "unknown typecode {typecode:#010x} at offset {offset}")
+            }
+            DecodingError::InvalidField { offset } => {
+                write!(f, "invalid field at offset {offset}")
+            }
+            DecodingError::TrailingBytes { offset, remaining } => write!(
+                f,
+                "{remaining} trailing bytes after the input ending at offset {offset}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
+impl From<ProofDecodingError> for DecodingError {
+    fn from(e: ProofDecodingError) -> Self {
+        match e {
+            ProofDecodingError::NotEnoughInput { offset, needed } => {
+                DecodingError::NotEnoughInput { offset, needed }
+            }
+            ProofDecodingError::TrailingBytes { offset, remaining } => {
+                DecodingError::TrailingBytes { offset, remaining }
+            }
+            ProofDecodingError::InvalidProofNodeType { offset, .. }
+            | ProofDecodingError::ItemTooLong { offset, .. }
+            | ProofDecodingError::TooDeep { offset, .. } => DecodingError::InvalidField { offset },
+        }
+    }
+}
+
+impl From<DecodingError> for KeyDecodingError {
+    fn from(e: DecodingError) -> Self {
+        match e {
+            DecodingError::NotEnoughInput { offset, needed } => {
+                KeyDecodingError::NotEnoughInput { offset, needed }
+            }
+            DecodingError::TrailingBytes { offset, remaining } => {
+                KeyDecodingError::TrailingBytes { offset, remaining }
+            }
+            DecodingError::UnknownTypecode { offset, .. }
+            | DecodingError::InvalidField { offset } => KeyDecodingError::InvalidField { offset },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ots_parameters_match_the_rfc() {
+        // table 1 of RFC 8554, for n = 32
+        let expected = [(1, 265, 7), (2, 133, 6), (4, 67, 4), (8, 34, 0)];
+        let types = [
+            OtsType::Blake3N32W1,
+            OtsType::Blake3N32W2,
+            OtsType::Blake3N32W4,
+            OtsType::Blake3N32W8,
+        ];
+        for (ots_type, (w, p, ls)) in types.into_iter().zip(expected) {
+            assert_eq!(ots_type.w(), w);
+            assert_eq!(ots_type.lengths(), (p, ls));
+        }
+        for &ots_type in OtsType::ALL {
+            assert_eq!(OtsType::from_typecode(ots_type.typecode()), Some(ots_type));
+        }
+        for &lms_type in LmsType::ALL {
+            assert_eq!(LmsType::from_typecode(lms_type.typecode()), Some(lms_type));
+        }
+        assert_eq!(OtsType::from_typecode(0), None);
+    }
+
+    #[cfg(feature = "sha2")]
+    fn from_hex(s: &str) -> Vec<u8> {
+        wire::from_hex(&s.split_whitespace().collect::<String>()).unwrap()
+    }
+
+    /// Test case 2 of appendix F of RFC 8554, rebuilt from the private keys
+    /// it publishes. The expected values are the RFC's own bytes.
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn rfc_8554_test_case_2() {
+        let public_key = HssPublicKey::try_from(
+            &from_hex(
+                "00000002 00000006 00000003 d08fabd4a2091ff0a8cb4ed834e74534
+                 32a58885cd9ba0431235466bff9651c6c92124404d45fa53cf161c28f1ad5a8e",
+            )[..],
+        )
+        .unwrap();
+        let message = b"The enumeration in the Constitution, of certain rights, shall \
+            not be construed to deny or disparage others retained by the people.\n";
+
+        let mut top = PrivateKey::from_seed(
+            LmsType::Sha256M32H10,
+            OtsType::Sha256N32W4,
+            from_hex("d08fabd4a2091ff0a8cb4ed834e74534")
+                .try_into()
+                .unwrap(),
+            from_hex("558b8966c48ae9cb898b423c83443aae014a72f1b1ab5cc85cf1d892903b5439")
+                .try_into()
+                .unwrap(),
+        );
+        let mut child = PrivateKey::from_seed(
+            LmsType::Sha256M32H5,
+            OtsType::Sha256N32W8,
+            from_hex("215f83b7ccb9acbcd08db97b0d04dc2b")
+                .try_into()
+                .unwrap(),
+            from_hex("a1c4696e2608035a886100d05cd99945eb3370731884a8235e2fb3d4d71f2547")
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(top.public_key(), public_key.key);
+        assert_eq!(
+            child.public_key().to_raw_bytes()[..],
+            from_hex(
+                "00000005 00000004 215f83b7ccb9acbcd08db97b0d04dc2b
+                 a1cd035833e0e90059603f26e07ad2aad152338e7a5e5984bcd5f7bb4eba40b7"
+            )
+        );
+
+        // the RFC signs with leaf 3 of the top key and leaf 4 of the other
+        for _ in 0..3 {
+            top.sign(b"").unwrap();
+        }
+        for _ in 0..4 {
+            child.sign(b"").unwrap();
+        }
+        let signature = HssSignature {
+            signed_keys: vec![(
+                top.sign(child.public_key().to_raw_bytes()).unwrap(),
+                child.public_key(),
+            )],
+            signature: child.sign(message).unwrap(),
+        };
+        let encoded = Vec::from(&signature);
+        assert_eq!(
+            encoded[..140],
+            from_hex(
+                "00000001 00000003 00000003
+                 3d46bee8660f8f215d3f96408a7a64cf1c4da02b63a55f62c666ef5707a914ce
+                 0674e8cb7a55f0c48d484f31f3aa4af9719a74f22cf823b94431d01c926e2a76
+                 bb71226d279700ec81c9e95fb11a0d10d065279a5796e265ae17737c44eb8c59
+                 4508e126a9a7870bf4360820bdeb9a01d9693779e416828e75bddd7d8c70d50a"
+            )
+        );
+        // the bottom signature starts after the top one and the signed key
+        let bottom = 4 + 8 + OtsType::Sha256N32W4.signature_len() + 32 * 10 + 56;
+        assert_eq!(
+            encoded[bottom..bottom + 40],
+            from_hex(
+                "00000004 00000004
+                 0eb1ed54a2460d512388cad533138d240534e97b1e82d33bd927d201dfc24ebb"
+            )
+        );
+
+        let decoded = HssSignature::try_from(&encoded[..]).unwrap();
+        assert!(public_key.verify(message, &decoded));
+        for offset in [20, 2000, bottom + 100, encoded.len() - 1] {
+            let mut tampered = encoded.clone();
+            tampered[offset] ^= 1;
+            let tampered = HssSignature::try_from(&tampered[..]).unwrap();
+            assert!(!public_key.verify(message, &tampered));
+        }
+    }
+
+    #[test]
+    fn lms_end_to_end() {
+        let mut private_key = PrivateKey::from_seed(
+            LmsType::Blake3M32H5,
+            OtsType::Blake3N32W4,
+            [1u8; 16],
+            [2u8; 32],
+        );
+        let public_key = private_key.public_key();
+        assert_eq!(
+            PublicKey::try_from(&public_key.to_raw_bytes()[..]),
+            Ok(public_key)
+        );
+
+        for i in 0..32u32 {
+            if i == 13 {
+                let encoded = private_key.to_bytes();
+                assert_eq!(
+                    PrivateKey::try_from(&encoded[..]).as_ref(),
+                    Ok(&private_key)
+                );
+            }
+            let message = i.to_be_bytes();
+            let signature = private_key.sign(message).unwrap();
+            assert_eq!(signature.index(), i);
+            assert!(public_key.verify(message, &signature));
+            assert!(!public_key.verify(b"something else", &signature));
+
+            let encoded = Vec::from(&signature);
+            assert_eq!(
+                encoded.len(),
+                8 + OtsType::Blake3N32W4.signature_len() + 32 * 5
+            );
+            assert_eq!(Signature::try_from(&encoded[..]), Ok(signature));
+        }
+        assert_eq!(private_key.sign(b"one too many"), None);
+    }
+
+    #[test]
+    fn lms_rejects_tampering() {
+        let mut private_key = PrivateKey::from_seed(
+            LmsType::Blake3M32H5,
+            OtsType::Blake3N32W8,
+            [3u8; 16],
+            [4u8; 32],
+        );
+        let public_key = private_key.public_key();
+        let signature = private_key.sign(b"message").unwrap();
+
+        let mut moved = signature.clone();
+        moved.q = 1;
+        assert!(!public_key.verify(b"message", &moved));
+        let mut bent = signature.clone();
+        bent.ots.chains[0][0] ^= 1;
+        assert!(!public_key.verify(b"message", &bent));
+
+        let encoded = Vec::from(&signature);
+        assert!(matches!(
+            Signature::try_from(&encoded[..encoded.len() - 1]),
+            Err(DecodingError::NotEnoughInput { .. })
+        ));
+        let mut unknown = encoded.clone();
+        unknown[4..8].copy_from_slice(&0u32.to_be_bytes());
+        assert_eq!(
+            Signature::try_from(&unknown[..]),
+            Err(DecodingError::UnknownTypecode {
+                offset: 4,
+                typecode: 0
+            })
+        );
+    }
+
+    #[test]
+    fn hss_end_to_end() {
+        let levels = [
+            (LmsType::Blake3M32H5, OtsType::Blake3N32W8),
+            (LmsType::Blake3M32H5, OtsType::Blake3N32W8),
+        ];
+        let mut private_key = HssPrivateKey::from_seed(&levels, [5u8; 16], [6u8; 32]).unwrap();
+        let public_key = private_key.public_key();
+        assert_eq!(
+            HssPublicKey::try_from(&public_key.to_raw_bytes()[..]),
+            Ok(public_key)
+        );
+
+        // crosses into the second bottom-level key
+        for i in 0..40u32 {
+            let message = i.to_be_bytes();
+            let signature = private_key.sign(message).unwrap();
+            assert!(public_key.verify(message, &signature));
+            assert!(!public_key.verify(b"something else", &signature));
+            let encoded = Vec::from(&signature);
+            assert_eq!(HssSignature::try_from(&encoded[..]), Ok(signature.clone()));
+            assert_eq!(
+                HssSignature::from_bytes(&signature.to_bytes()).unwrap(),
+                signature
+            );
+        }
+        assert_eq!(
+            HssPublicKey::from_bytes(&public_key.to_bytes()).unwrap(),
+            public_key
+        );
+        assert_eq!(
+            HssPublicKey::from_bytes(&public_key.to_raw_bytes()).unwrap(),
+            public_key
+        );
+        assert!(matches!(
+            PublicKey::from_bytes(&public_key.to_bytes()),
+            Err(wire::DecodingError::WrongType { .. })
+        ));
+
+        let encoded = private_key.to_bytes();
+        assert_eq!(HssPrivateKey::try_from(&encoded[..]), Ok(private_key));
+        assert!(matches!(
+            HssPrivateKey::from_seed(&[], [0u8; 16], [0u8; 32]),
+            Err(KeyGenerationError::InvalidLevels { levels: 0 })
+        ));
+    }
+
+    #[test]
+    fn hss_private_keys_are_checked_on_decoding() {
+        let levels = [
+            (LmsType::Blake3M32H5, OtsType::Blake3N32W8),
+            (LmsType::Blake3M32H5, OtsType::Blake3N32W8),
+        ];
+        let private_key = HssPrivateKey::from_seed(&levels, [5u8; 16], [6u8; 32]).unwrap();
+        let other = HssPrivateKey::from_seed(&levels, [7u8; 16], [8u8; 32]).unwrap();
+        let encode = |top: &PrivateKey, signature: &Signature, child: &PrivateKey| {
+            let mut output = 2u32.to_be_bytes().to_vec();
+            top.encode(&mut output);
+            signature.encode(&mut output);
+            child.encode(&mut output);
+            output
+        };
+        let offset = 4 + private_key.keys[0].to_bytes().len();
+
+        let encoded = encode(
+            &private_key.keys[0],
+            &private_key.signed_keys[0],
+            &private_key.keys[1],
+        );
+        assert_eq!(encoded, private_key.to_bytes());
+        assert!(HssPrivateKey::try_from(&encoded[..]).is_ok());
+        // a bottom key the top key never signed
+        let encoded = encode(
+            &private_key.keys[0],
+            &private_key.signed_keys[0],
+            &other.keys[1],
+        );
+        assert_eq!(
+            HssPrivateKey::try_from(&encoded[..]),
+            Err(KeyDecodingError::InvalidField { offset })
+        );
+        // a top key which has not signed anything yet
+        let fresh = PrivateKey::from_seed(
+            LmsType::Blake3M32H5,
+            OtsType::Blake3N32W8,
+            [5u8; 16],
+            [6u8; 32],
+        );
+        let encoded = encode(&fresh, &private_key.signed_keys[0], &private_key.keys[1]);
+        assert_eq!(
+            HssPrivateKey::try_from(&encoded[..]),
+            Err(KeyDecodingError::InvalidField { offset })
+        );
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_parameter_sets() {
+        assert_eq!(OtsType::Sha256N32W8.typecode(), 4);
+        assert_eq!(LmsType::Sha256M32H5.typecode(), 5);
+        assert_eq!(LmsType::Sha256M32H25.typecode(), 9);
+
+        let mut private_key = PrivateKey::from_seed(
+            LmsType::Sha256M32H5,
+            OtsType::Sha256N32W8,
+            [7u8; 16],
+            [8u8; 32],
+        );
+        let public_key = private_key.public_key();
+        assert_eq!(&public_key.to_raw_bytes()[..8], &[0, 0, 0, 5, 0, 0, 0, 4]);
+        let signature = private_key.sign(b"message").unwrap();
+        assert!(public_key.verify(b"message", &signature));
+        assert_eq!(Vec::from(&signature).len(), 8 + 4 + 32 + 34 * 32 + 5 * 32);
+    }
+}
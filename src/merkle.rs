@@ -1,134 +1,442 @@
+/// Detection of leaves which signed more than one message.
+pub mod audit;
 pub mod internal;
-
-use crate::lamport;
+/// The public part of a key, for a proof server which turns bare Lamport
+/// signatures into Merkle signatures.
+pub mod public_tree;
+/// A Merkle signer which regenerates its Lamport keys from a seed, holding
+/// only a logarithmic number of tree nodes.
+pub mod seeded;
+/// A Merkle signer which many threads can sign with at once.
+pub mod shared;
+/// Sub-keys which sign with disjoint ranges of one key's indices, for
+/// signing under one public key from several hosts.
+pub mod split;
+/// Trees saved beside their keys and loaded, or memory-mapped, without
+/// being rebuilt.
+pub mod stored;
+
+use std::marker::PhantomData;
+
+use crate::fingerprint::Fingerprint;
+use crate::hash::{Blake3, HashFunction};
+use crate::lamport::{self, Lamport256};
 use crate::merkle::internal::*;
-
-pub use crate::merkle::internal::ProofDecodingError;
+use crate::state::{self, Reservation, SigningError, StateStore};
+use crate::wire::{self, Algorithm, Framed, Kind};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+pub use crate::merkle::audit::{detect_reuse, IndexTracker, TrackingError};
+pub use crate::merkle::internal::{
+    DecodingLimits, ProofDecodingError, TreeDecodingError, TreeError,
+};
+pub use crate::merkle::public_tree::PublicTree;
+pub use crate::merkle::shared::SharedSigner;
+pub use crate::merkle::split::{SplitError, SubKey};
+pub use crate::merkle::stored::StoredTree;
 
 /// A public key is the Merkle root of the tree in your [`PrivateKey`].
-pub struct PublicKey(Commitment);
+///
+/// It displays as the hex of its framed encoding, which [`str::parse`]
+/// reads back.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PublicKey<H: HashFunction = Blake3>(pub(crate) Commitment, PhantomData<H>);
 
-impl From<[u8; 40]> for PublicKey {
+impl<H: HashFunction> From<[u8; 40]> for PublicKey<H> {
     fn from(value: [u8; 40]) -> Self {
         let mut hash_arr: [u8; 32] = [0u8; 32];
-        for i in 0..32 {
-            hash_arr[i] = value[i];
-        }
+        hash_arr.copy_from_slice(&value[..32]);
         let mut u64_arr: [u8; 8] = [0u8; 8];
-        for i in 0..8 {
-            u64_arr[i] = value[32 + i];
-        }
-        PublicKey(Commitment {
+        u64_arr.copy_from_slice(&value[32..]);
+        PublicKey::new(Commitment {
             root: blake3::Hash::from(hash_arr),
             num_items: u64::from_be_bytes(u64_arr),
         })
     }
 }
 
-impl From<PublicKey> for [u8; 40] {
-    fn from(value: PublicKey) -> Self {
-        let mut arr = [0u8; 40];
-        for i in 0..32 {
-            arr[i] = value.0.root.as_bytes()[i];
-        }
-        let u64_arr = value.0.num_items.to_be_bytes();
-        for i in 0..8 {
-            arr[i + 32] = u64_arr[i];
-        }
-        arr
+impl<H: HashFunction> From<PublicKey<H>> for [u8; 40] {
+    fn from(value: PublicKey<H>) -> Self {
+        value.to_raw_bytes()
     }
 }
 
-impl PublicKey {
-    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature) -> bool {
-        self.0.verify(&signature.2) && signature.1.verify(message, &signature.0)
+impl<H: HashFunction> std::fmt::Display for PublicKey<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        wire::write_hex(f, &self.to_bytes())
+    }
+}
+
+impl<H: HashFunction> std::str::FromStr for PublicKey<H> {
+    type Err = wire::DecodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PublicKey::from_bytes(&wire::from_hex(s)?)
+    }
+}
+
+impl<H: HashFunction> PublicKey<H> {
+    pub(crate) fn new(commitment: Commitment) -> Self {
+        PublicKey(commitment, PhantomData)
+    }
+
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature<H>) -> bool {
+        signature.recover(message, self.0.num_items).as_ref() == Some(&self.0)
+    }
+
+    /// The root of the Merkle tree over the Lamport public keys.
+    pub fn root(&self) -> blake3::Hash {
+        self.0.root
+    }
+
+    /// The number of Lamport keys in the tree, and so of messages the key
+    /// can sign.
+    pub fn num_items(&self) -> u64 {
+        self.0.num_items
+    }
+
+    /// The root followed by the number of leaves, as encoded before
+    /// framing.
+    pub fn to_raw_bytes(&self) -> [u8; 40] {
+        self.0.to_bytes()
+    }
+
+    /// The framed encoding, naming the hash function.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else the raw 40 bytes of the root and
+    /// the number of leaves.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// A short identifier of this key, covering its hash function.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::of(self)
+    }
+}
+
+impl<H: HashFunction> Framed for PublicKey<H> {
+    const ALGORITHM: Algorithm = Algorithm::Merkle;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        H::ID as u32
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.to_raw_bytes().to_vec()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        if parameter_set != H::ID as u32 {
+            return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+        }
+        let raw = <[u8; 40]>::try_from(body).map_err(|_| wire::DecodingError::LengthMismatch {
+            declared: 40,
+            actual: body.len(),
+        })?;
+        Ok(PublicKey::from(raw))
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        <[u8; 40]>::try_from(bytes).ok().map(PublicKey::from)
     }
 }
 
 /// A private key consists of a Merkle tree committing to a sequence
 /// of Lamport public keys, one for each message you plan to sign.
-pub struct PrivateKey(Vec<lamport::PrivateKey>, Tree, usize);
+///
+/// Each Lamport secret is overwritten with zeros once its index is used, so
+/// that a later leak of the key cannot forge signatures with leaves which
+/// already signed. The tree of a key whose used secrets were erased can no
+/// longer be rebuilt from them, so such keys are loaded with
+/// [`PrivateKey::from_stored_tree`].
+pub struct PrivateKey<H: HashFunction = Blake3>(
+    Vec<lamport::PrivateKey<Lamport256, H>>,
+    Tree<H>,
+    usize,
+);
+
+impl<H: HashFunction> TryFrom<(Vec<lamport::PrivateKey<Lamport256, H>>, usize)> for PrivateKey<H> {
+    type Error = TreeError;
+
+    fn try_from(
+        (private_keys, current_index): (Vec<lamport::PrivateKey<Lamport256, H>>, usize),
+    ) -> Result<Self, Self::Error> {
+        let public_key_bytes = |private_key: &lamport::PrivateKey<Lamport256, H>| {
+            private_key.public_key().to_raw_bytes().to_vec()
+        };
+        #[cfg(feature = "rayon")]
+        let encoded_public_keys: Vec<Vec<u8>> =
+            private_keys.par_iter().map(public_key_bytes).collect();
+        #[cfg(not(feature = "rayon"))]
+        let encoded_public_keys: Vec<Vec<u8>> = private_keys.iter().map(public_key_bytes).collect();
+        let tree = Tree::new(&mut encoded_public_keys.iter().map(|v| v.as_slice()))?;
+        let mut private_key = PrivateKey(private_keys, tree, 0);
+        private_key.advance_to(current_index);
+        Ok(private_key)
+    }
+}
 
-impl From<(Vec<lamport::PrivateKey>, usize)> for PrivateKey {
-    fn from((private_keys, current_index): (Vec<lamport::PrivateKey>, usize)) -> Self {
-        let encoded_public_keys: Vec<Vec<u8>> = private_keys
-            .iter()
-            .map(|private_key| {
-                private_key
-                    .public_key()
-                    .to_bytes()
-                    .iter()
-                    .copied()
-                    .collect()
-            })
-            .collect();
-        let tree = Tree::new(&mut encoded_public_keys.iter().map(|v| v.as_slice()));
-        PrivateKey(private_keys, tree, current_index)
+/// An error encountered while generating a [`PrivateKey`].
+#[derive(Debug)]
+pub enum KeyGenerationError {
+    /// The operating system random number generator failed.
+    Rng(rand::Error),
+    /// The Merkle tree over the Lamport public keys could not be built,
+    /// for instance because no keys were requested.
+    Tree(TreeError),
+}
+
+impl std::fmt::Display for KeyGenerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyGenerationError::Rng(e) => write!(f, "random number generation failed: {e}"),
+            KeyGenerationError::Tree(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyGenerationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KeyGenerationError::Rng(e) => Some(e),
+            KeyGenerationError::Tree(e) => Some(e),
+        }
+    }
+}
+
+impl From<rand::Error> for KeyGenerationError {
+    fn from(e: rand::Error) -> Self {
+        KeyGenerationError::Rng(e)
+    }
+}
+
+impl From<TreeError> for KeyGenerationError {
+    fn from(e: TreeError) -> Self {
+        KeyGenerationError::Tree(e)
     }
 }
 
 /// A signature consists of a lamport signature and a merkle proof of the
 /// public key used.
-#[derive(Debug, Eq, PartialEq)]
-pub struct Signature(lamport::Signature, lamport::PublicKey, Proof);
-
-#[derive(Debug)]
+///
+/// It displays as the hex of its framed encoding, which [`str::parse`]
+/// reads back.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct Signature<H: HashFunction = Blake3>(
+    pub(crate) lamport::Signature<Lamport256, H>,
+    pub(crate) lamport::PublicKey<Lamport256, H>,
+    pub(crate) Proof<H>,
+);
+
+/// An error encountered while decoding a [`Signature`]. Every offset is the
+/// position, in bytes, within the encoded signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SignatureDecodingError {
-    NotEnoughInput(usize),
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// The Merkle proof starting at offset 24576 could not be decoded.
     MerkleProofDecodingError(ProofDecodingError),
+    /// The item proven by the Merkle proof, which starts at `offset`, is
+    /// not the Lamport public key carried by the signature.
+    PublicKeyMismatch { offset: usize },
 }
 
-impl From<&Signature> for Vec<u8> {
-    fn from(sig: &Signature) -> Self {
-        let mut output = Vec::new();
-        let lamport_sig_bytes: [u8; 8192] = sig.0.clone().into();
-        output.extend(lamport_sig_bytes.into_iter());
+impl std::fmt::Display for SignatureDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureDecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "signature truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            SignatureDecodingError::MerkleProofDecodingError(e) => {
+                write!(f, "invalid merkle proof: {e}")
+            }
+            SignatureDecodingError::PublicKeyMismatch { offset } => write!(
+                f,
+                "merkle proof at offset {offset} does not prove the signature's public key"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SignatureDecodingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SignatureDecodingError::MerkleProofDecodingError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
-        let lamport_pub_key_bytes: [u8; 16384] = (&sig.1).into();
-        output.extend(lamport_pub_key_bytes.into_iter());
+impl<H: HashFunction> From<&Signature<H>> for Vec<u8> {
+    fn from(sig: &Signature<H>) -> Self {
+        let mut output = Vec::new();
+        output.extend(sig.0.to_raw_bytes());
+        output.extend(sig.1.to_raw_bytes());
 
         let proof_bytes: Vec<u8> = (&sig.2).into();
-        output.extend(proof_bytes.into_iter());
+        output.extend(proof_bytes);
 
         output
     }
 }
 
-impl TryFrom<&[u8]> for Signature {
-    type Error = SignatureDecodingError;
-    fn try_from(signature_bytes: &[u8]) -> Result<Self, Self::Error> {
-        let mut i = 0;
-        let next_byte = |i: &mut usize| {
-            if let Some(b) = signature_bytes.get(*i) {
-                *i += 1;
-                Ok(*b)
-            } else {
-                Err(SignatureDecodingError::NotEnoughInput(
-                    signature_bytes.len(),
-                ))
+impl<H: HashFunction> Signature<H> {
+    /// The framed encoding, naming the hash function.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an unframed one, bounding the
+    /// Merkle proof by the default [`DecodingLimits`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// The framed encoding, carrying the [`PublicKey::fingerprint`] of the
+    /// signing key for [`wire::key_id`] to read back.
+    pub fn to_bytes_with_key_id(&self, key_id: &Fingerprint) -> Vec<u8> {
+        wire::to_bytes_with_key_id(self, key_id)
+    }
+
+    /// The index of the Lamport key which made this signature.
+    pub fn index(&self) -> u64 {
+        self.2.index()
+    }
+
+    pub fn lamport_signature(&self) -> &lamport::Signature<Lamport256, H> {
+        &self.0
+    }
+
+    /// The Lamport public key which made this signature, proven to be a
+    /// leaf of the tree.
+    pub fn lamport_public_key(&self) -> &lamport::PublicKey<Lamport256, H> {
+        &self.1
+    }
+
+    /// The commitment of the tree over `num_items` Lamport public keys
+    /// which this signature over `message` leads to, or [`None`] if the
+    /// signature is not valid for any such tree.
+    pub(crate) fn recover<A: AsRef<[u8]>>(&self, message: A, num_items: u64) -> Option<Commitment> {
+        if self.2.item() != self.1.to_raw_bytes() || !self.1.verify(message, &self.0) {
+            return None;
+        }
+        let root = self.2.root(num_items)?;
+        Some(Commitment { root, num_items })
+    }
+
+    /// Decodes a signature from untrusted bytes, bounding the embedded
+    /// Merkle proof by `limits` and rejecting any trailing input.
+    pub fn decode(
+        signature_bytes: &[u8],
+        limits: &DecodingLimits,
+    ) -> Result<Self, SignatureDecodingError> {
+        let mut reader = Reader::new(signature_bytes, 0);
+        let not_enough_input = |e| match e {
+            ProofDecodingError::NotEnoughInput { offset, needed } => {
+                SignatureDecodingError::NotEnoughInput { offset, needed }
             }
+            e => SignatureDecodingError::MerkleProofDecodingError(e),
         };
-        let mut lamport_signature_bytes = [0u8; 8192];
-        for j in 0..8192 {
-            lamport_signature_bytes[j] = next_byte(&mut i)?;
-        }
-        let lamport_signature = lamport::Signature::from(lamport_signature_bytes);
 
-        let mut lamport_public_key_bytes = [0u8; 16384];
-        for j in 0..16384 {
-            lamport_public_key_bytes[j] = next_byte(&mut i)?;
-        }
-        let lamport_public_key = lamport::PublicKey::from(&lamport_public_key_bytes);
+        let lamport_signature =
+            lamport::Signature::from_raw_bytes(reader.array::<8192>().map_err(not_enough_input)?);
+        let lamport_public_key =
+            lamport::PublicKey::from_raw_bytes(&reader.array::<16384>().map_err(not_enough_input)?);
 
-        let proof = Proof::try_from(&signature_bytes[i..])
-            .map_err(|e| SignatureDecodingError::MerkleProofDecodingError(e))?;
+        let proof_offset = reader.offset();
+        let proof = Proof::decode_from(&mut reader, limits)
+            .and_then(|proof| reader.finish().map(|()| proof))
+            .map_err(SignatureDecodingError::MerkleProofDecodingError)?;
+
+        if proof.item() != lamport_public_key.to_raw_bytes() {
+            return Err(SignatureDecodingError::PublicKeyMismatch {
+                offset: proof_offset,
+            });
+        }
 
         Ok(Signature(lamport_signature, lamport_public_key, proof))
     }
 }
 
+impl<H: HashFunction> std::fmt::Display for Signature<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        wire::write_hex(f, &self.to_bytes())
+    }
+}
+
+impl<H: HashFunction> std::str::FromStr for Signature<H> {
+    type Err = wire::DecodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Signature::from_bytes(&wire::from_hex(s)?)
+    }
+}
+
+impl<H: HashFunction> TryFrom<&[u8]> for Signature<H> {
+    type Error = SignatureDecodingError;
+    fn try_from(signature_bytes: &[u8]) -> Result<Self, Self::Error> {
+        Signature::decode(signature_bytes, &DecodingLimits::default())
+    }
+}
+
+impl<H: HashFunction> Framed for Signature<H> {
+    const ALGORITHM: Algorithm = Algorithm::Merkle;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        H::ID as u32
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        if parameter_set != H::ID as u32 {
+            return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+        }
+        Signature::try_from(body).map_err(wire::invalid_body)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        Signature::try_from(bytes).ok()
+    }
+}
+
 impl PrivateKey {
-    pub fn inner_keys(&self) -> &Vec<lamport::PrivateKey> {
+    /// Generates a key able to sign `n` messages, failing if `n` is zero.
+    /// With the `rayon` feature, the Lamport keys, their public keys and
+    /// the Merkle tree are all computed in parallel.
+    pub fn generate(n: usize) -> Result<PrivateKey, KeyGenerationError> {
+        PrivateKey::generate_with(Blake3, n)
+    }
+}
+
+impl<H: HashFunction> PrivateKey<H> {
+    /// Generates a key able to sign `n` messages using the hash function
+    /// `H`, failing if `n` is zero.
+    pub fn generate_with(_hash: H, n: usize) -> Result<PrivateKey<H>, KeyGenerationError> {
+        #[cfg(feature = "rayon")]
+        let private_keys: Result<Vec<lamport::PrivateKey<Lamport256, H>>, rand::Error> = (0..n)
+            .into_par_iter()
+            .map(|_i| lamport::PrivateKey::generate_with(Lamport256))
+            .collect();
+        #[cfg(not(feature = "rayon"))]
+        let private_keys: Result<Vec<lamport::PrivateKey<Lamport256, H>>, rand::Error> = (0..n)
+            .map(|_i| lamport::PrivateKey::generate_with(Lamport256))
+            .collect();
+        let private_keys = private_keys?;
+        Ok((private_keys, 0).try_into()?)
+    }
+
+    pub fn inner_keys(&self) -> &Vec<lamport::PrivateKey<Lamport256, H>> {
         &self.0
     }
 
@@ -136,18 +444,13 @@ impl PrivateKey {
         self.2
     }
 
-    pub fn public_key(&self) -> PublicKey {
-        PublicKey(self.1.commitment())
-    }
-
-    pub fn generate(n: usize) -> Result<PrivateKey, rand::Error> {
-        let private_keys: Result<Vec<lamport::PrivateKey>, rand::Error> =
-            (0..n).map(|_i| lamport::PrivateKey::generate()).collect();
-        let private_keys = private_keys?;
-        Ok((private_keys, 0).into())
+    pub fn public_key(&self) -> PublicKey<H> {
+        PublicKey::new(self.1.commitment())
     }
 
-    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<Signature> {
+    /// Signs with the next unused Lamport key, returning [`None`] once
+    /// every key has been used.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<Signature<H>> {
         let index = self.2;
 
         if index >= self.0.len() {
@@ -157,14 +460,66 @@ impl PrivateKey {
         let merkle_tree = &self.1;
         let lamport_private_key = &self.0[index];
         let lamport_public_key = lamport_private_key.public_key();
-        let lamport_public_key_bytes = lamport_public_key.to_bytes().iter().copied().collect();
+        let lamport_public_key_bytes = lamport_public_key.to_raw_bytes().to_vec();
 
-        let proof = merkle_tree.prove(lamport_public_key_bytes, index as u64);
+        let proof = merkle_tree
+            .prove(lamport_public_key_bytes, index as u64)
+            .ok()?;
 
-        proof.map(|proof| {
-            let lamport_signature = lamport_private_key.sign(message);
-            Signature(lamport_signature, lamport_public_key, proof)
-        })
+        let lamport_signature = lamport_private_key.sign(message);
+        self.advance_to(index + 1);
+        Some(Signature(lamport_signature, lamport_public_key, proof))
+    }
+
+    /// Moves the key on to `index`, erasing the secrets of every Lamport key
+    /// it passes, so that a later leak of the key cannot forge signatures
+    /// with the leaves already used.
+    fn advance_to(&mut self, index: usize) {
+        let end = index.min(self.0.len());
+        for key in self.0.get_mut(self.2..end).into_iter().flatten() {
+            key.erase();
+        }
+        self.2 = self.2.max(index);
+    }
+
+    /// Signs like [`PrivateKey::sign`], but only after durably recording in
+    /// `store` that the index is used. If `store` is ahead of this key, as
+    /// when the key was restored from a stale copy, the indices in between
+    /// are skipped.
+    pub fn sign_persisted<S: StateStore, A: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        message: A,
+    ) -> Result<Signature<H>, SigningError<S::Error>> {
+        let mut reservation = self.reserve(store, 1)?;
+        Ok(self
+            .sign_reserved(&mut reservation, message)
+            .expect("reserved indices are in range"))
+    }
+
+    /// Durably reserves the next `count` indices in `store` with a single
+    /// write, or fewer if the key runs out first, for
+    /// [`PrivateKey::sign_reserved`] to sign with. Indices left unused are
+    /// burned, and [`Reservation::skipped`] tells how many were lost before
+    /// this block.
+    pub fn reserve<S: StateStore>(
+        &mut self,
+        store: &mut S,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        state::reserve(store, self.2 as u64, self.0.len() as u64, count)
+    }
+
+    /// Signs with the next index of `reservation`, which must have been
+    /// made for this key, returning [`None`] once the block is used up.
+    pub fn sign_reserved<A: AsRef<[u8]>>(
+        &mut self,
+        reservation: &mut Reservation,
+        message: A,
+    ) -> Option<Signature<H>> {
+        let index = reservation.take_from(self.2 as u64)?;
+        self.advance_to(index as usize);
+        self.sign(message)
     }
 }
 
@@ -177,6 +532,177 @@ mod tests {
     #[test]
     fn test_generation() {
         let _private_key = PrivateKey::generate(1000);
+        assert!(matches!(
+            PrivateKey::generate(0),
+            Err(KeyGenerationError::Tree(TreeError::EmptyInput))
+        ));
+    }
+
+    #[test]
+    fn test_every_key_signs_once() {
+        let mut private_key = PrivateKey::generate(5).unwrap();
+        let public_key = private_key.public_key();
+        for i in 0..5 {
+            assert_eq!(private_key.current_index(), i);
+            let signature = private_key.sign(b"hello").unwrap();
+            assert_eq!(signature.2.index(), i as u64);
+            assert!(public_key.verify(b"hello", &signature));
+        }
+        assert!(private_key.sign(b"hello").is_none());
+    }
+
+    #[test]
+    fn test_signature_decoding() {
+        let mut private_key = PrivateKey::generate(2).unwrap();
+        let signature = private_key.sign(b"hello").unwrap();
+        let encoded: Vec<u8> = (&signature).into();
+
+        assert_eq!(
+            Signature::<Blake3>::try_from(encoded.as_slice()),
+            Ok(signature)
+        );
+
+        assert_eq!(
+            Signature::<Blake3>::try_from(&encoded[..100]),
+            Err(SignatureDecodingError::NotEnoughInput {
+                offset: 0,
+                needed: 8092,
+            })
+        );
+
+        let mut trailing = encoded.clone();
+        trailing.extend_from_slice(b"junk");
+        assert_eq!(
+            Signature::<Blake3>::try_from(trailing.as_slice()),
+            Err(SignatureDecodingError::MerkleProofDecodingError(
+                ProofDecodingError::TrailingBytes {
+                    offset: encoded.len(),
+                    remaining: 4,
+                }
+            ))
+        );
+
+        // swap in a different Lamport public key than the one proven
+        let mut mismatched = encoded.clone();
+        mismatched[8192] ^= 1;
+        assert_eq!(
+            Signature::<Blake3>::try_from(mismatched.as_slice()),
+            Err(SignatureDecodingError::PublicKeyMismatch { offset: 24576 })
+        );
+    }
+
+    #[cfg(any(feature = "sha2", feature = "sha3"))]
+    fn signs_with<H: HashFunction>(hash: H) {
+        let mut private_key = PrivateKey::generate_with(hash, 3).unwrap();
+        let public_key = private_key.public_key();
+        let signature = private_key.sign(b"hello").unwrap();
+        assert!(public_key.verify(b"hello", &signature));
+        assert!(!public_key.verify(b"goodbye", &signature));
+
+        let encoded: Vec<u8> = (&signature).into();
+        let decoded = Signature::<H>::try_from(encoded.as_slice()).unwrap();
+        assert!(public_key.verify(b"hello", &decoded));
+
+        // the same bytes mean nothing under another hash function
+        let blake3_public_key = PublicKey::<Blake3>::from(<[u8; 40]>::from(public_key));
+        let blake3_signature = Signature::<Blake3>::try_from(encoded.as_slice()).unwrap();
+        assert!(!blake3_public_key.verify(b"hello", &blake3_signature));
+    }
+
+    #[test]
+    fn test_used_keys_are_erased() {
+        let keys: Vec<lamport::PrivateKey> = (0..5)
+            .map(|_| lamport::PrivateKey::generate().unwrap())
+            .collect();
+        let erased = |key: &lamport::PrivateKey| key.encode()[1..].iter().all(|&b| b == 0);
+
+        // keys loaded past their first leaves forget them at once
+        let mut private_key =
+            PrivateKey::try_from((keys.iter().map(|key| key.duplicate()).collect(), 1)).unwrap();
+        assert!(erased(&private_key.inner_keys()[0]));
+        assert!(!erased(&private_key.inner_keys()[1]));
+
+        private_key.sign(b"message").unwrap();
+        assert!(erased(&private_key.inner_keys()[1]));
+        let mut store = state::MemoryStore::new(4);
+        private_key.sign_persisted(&mut store, b"message").unwrap();
+        assert!(private_key.inner_keys().iter().all(erased));
+
+        // an erased key cannot be made to sign by rewinding
+        let public_key = private_key.public_key();
+        let tree = StoredTree::from_bytes(private_key.tree_to_bytes()).unwrap();
+        let mut rewound = PrivateKey::from_stored_tree(
+            private_key
+                .inner_keys()
+                .iter()
+                .map(|key| key.duplicate())
+                .collect(),
+            tree,
+            0,
+        )
+        .unwrap();
+        assert!(rewound.sign(b"forgery").is_none());
+        assert!(!public_key.verify(
+            b"forgery",
+            &Signature(
+                private_key.inner_keys()[0].sign(b"forgery"),
+                keys[0].public_key(),
+                private_key
+                    .1
+                    .prove(keys[0].public_key().to_raw_bytes().to_vec(), 0)
+                    .unwrap(),
+            )
+        ));
+    }
+
+    #[test]
+    fn test_keys_and_signatures_as_values() {
+        let mut private_key = PrivateKey::generate(3).unwrap();
+        let public_key = private_key.public_key();
+        assert_eq!(public_key.num_items(), 3);
+        assert_eq!(
+            &public_key.to_raw_bytes()[..32],
+            public_key.root().as_bytes()
+        );
+
+        let parsed: PublicKey = public_key.to_string().parse().unwrap();
+        assert_eq!(parsed, public_key);
+        let other = PrivateKey::generate(3).unwrap().public_key();
+        let keys = std::collections::BTreeSet::from([public_key.clone(), other.clone()]);
+        assert!(keys.contains(&other));
+        assert!(std::collections::HashSet::from([public_key.clone()]).contains(&parsed));
+
+        let leaf_public_key = private_key.inner_keys()[1].public_key();
+        private_key.sign(b"first").unwrap();
+        let signature = private_key.sign(b"second").unwrap();
+        assert_eq!(signature.index(), 1);
+        assert_eq!(signature.lamport_public_key(), &leaf_public_key);
+        assert!(signature
+            .lamport_public_key()
+            .verify(b"second", signature.lamport_signature()));
+        let parsed: Signature = signature.to_string().to_uppercase().parse().unwrap();
+        assert_eq!(parsed, signature);
+
+        assert!(matches!(
+            "b3ls".parse::<PublicKey>(),
+            Err(wire::DecodingError::InvalidHex { offset: 2 })
+        ));
+        assert!(matches!(
+            public_key.to_string()[1..].parse::<PublicKey>(),
+            Err(wire::DecodingError::InvalidHex { offset: 109 })
+        ));
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_sha256_signatures() {
+        signs_with(crate::hash::Sha256);
+    }
+
+    #[cfg(feature = "sha3")]
+    #[test]
+    fn test_shake256_signatures() {
+        signs_with(crate::hash::Shake256);
     }
 
     proptest! {
@@ -188,7 +714,7 @@ mod tests {
         fn test_merkle_signatures(s in "\\PC*") {
             let mut private_key = PrivateKey::generate(1).unwrap();
             let public_key = private_key.public_key();
-            let signature = private_key.sign(&s.as_bytes()).unwrap();
+            let signature = private_key.sign(s.as_bytes()).unwrap();
             let signature_bytes: Vec<u8> = (&signature).into();
             let signature_bytes_ref: &[u8] = &signature_bytes;
             let signature_2: Signature = signature_bytes_ref.try_into().unwrap();
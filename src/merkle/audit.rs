@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::fingerprint::Fingerprint;
+use crate::hash::HashFunction;
+use crate::merkle::{PublicKey, Signature};
+
+/// The message digest which `signature` signs under `public_key`, or
+/// [`None`] if it is not valid under `public_key` for any message.
+fn signed_digest<H: HashFunction>(
+    public_key: &PublicKey<H>,
+    signature: &Signature<H>,
+) -> Option<Vec<u8>> {
+    if signature.2.item() != signature.1.to_raw_bytes() || !public_key.0.verify(&signature.2) {
+        return None;
+    }
+    signature.1.signed_digest(&signature.0)
+}
+
+/// Whether `a` and `b` are both valid under `public_key` and were made with
+/// the same Lamport key over different messages.
+///
+/// Such a pair proves that the private key signed twice with one leaf,
+/// whether through misuse or after being stolen, and that forgeries with
+/// that leaf may follow. The messages are not needed, because a Lamport
+/// signature reveals the digest it signs.
+pub fn detect_reuse<H: HashFunction>(
+    public_key: &PublicKey<H>,
+    a: &Signature<H>,
+    b: &Signature<H>,
+) -> bool {
+    if a.index() != b.index() {
+        return false;
+    }
+    match (signed_digest(public_key, a), signed_digest(public_key, b)) {
+        (Some(a), Some(b)) => a != b,
+        _ => false,
+    }
+}
+
+/// Records the leaf index of every signature a verifier accepts, for each
+/// key, and flags a second message signed with any of them.
+///
+/// Only the digest signed at each index is kept, so tracking a key costs 32
+/// bytes per signature seen. Seeing the same signature again is not reuse.
+#[derive(Clone, Debug, Default)]
+pub struct IndexTracker {
+    seen: HashMap<Fingerprint, HashMap<u64, Vec<u8>>>,
+}
+
+/// A signature which an [`IndexTracker`] refused to record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrackingError {
+    /// The signature is not valid under the key for any message.
+    Invalid,
+    /// The leaf at `index` already signed a different message.
+    Reused { index: u64 },
+}
+
+impl std::fmt::Display for TrackingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrackingError::Invalid => write!(f, "signature is not valid under this key"),
+            TrackingError::Reused { index } => {
+                write!(f, "leaf {index} has already signed a different message")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrackingError {}
+
+impl IndexTracker {
+    pub fn new() -> IndexTracker {
+        IndexTracker::default()
+    }
+
+    /// Records that `public_key` made `signature`, failing if the signature
+    /// is invalid or its leaf has been seen signing something else. Neither
+    /// failure records anything.
+    pub fn record<H: HashFunction>(
+        &mut self,
+        public_key: &PublicKey<H>,
+        signature: &Signature<H>,
+    ) -> Result<(), TrackingError> {
+        let digest = signed_digest(public_key, signature).ok_or(TrackingError::Invalid)?;
+        let index = signature.index();
+        let seen = self.seen.entry(public_key.fingerprint()).or_default();
+        match seen.get(&index) {
+            Some(earlier) if *earlier != digest => Err(TrackingError::Reused { index }),
+            Some(_) => Ok(()),
+            None => {
+                seen.insert(index, digest);
+                Ok(())
+            }
+        }
+    }
+
+    /// The number of indices of `public_key` seen signing so far.
+    pub fn count<H: HashFunction>(&self, public_key: &PublicKey<H>) -> usize {
+        self.seen
+            .get(&public_key.fingerprint())
+            .map_or(0, HashMap::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::PrivateKey;
+
+    /// A key which signs `first` and then, restored from a backup, signs
+    /// `second` with the same leaf.
+    fn reused(first: &[u8], second: &[u8]) -> (PublicKey, Signature, Signature) {
+        let mut private_key = PrivateKey::generate(4).unwrap();
+        let public_key = private_key.public_key();
+        let mut backup = PrivateKey::from_stored_tree(
+            private_key
+                .inner_keys()
+                .iter()
+                .map(|key| key.duplicate())
+                .collect(),
+            crate::merkle::StoredTree::from_bytes(private_key.tree_to_bytes()).unwrap(),
+            0,
+        )
+        .unwrap();
+        let a = private_key.sign(first).unwrap();
+        let b = backup.sign(second).unwrap();
+        (public_key, a, b)
+    }
+
+    #[test]
+    fn detects_reused_leaves() {
+        let (public_key, a, b) = reused(b"pay alice", b"pay mallory");
+        assert!(public_key.verify(b"pay alice", &a));
+        assert!(public_key.verify(b"pay mallory", &b));
+        assert!(detect_reuse(&public_key, &a, &b));
+        assert!(!detect_reuse(&public_key, &a, &a));
+
+        let (public_key, a, b) = reused(b"same", b"same");
+        assert!(!detect_reuse(&public_key, &a, &b));
+    }
+
+    #[test]
+    fn ignores_invalid_signatures() {
+        let (public_key, a, b) = reused(b"first", b"second");
+        let other_key = PrivateKey::generate(4).unwrap().public_key();
+        assert!(!detect_reuse(&other_key, &a, &b));
+
+        let mut forged = b.clone();
+        forged.0 = crate::lamport::Signature::from_raw_bytes([7u8; 8192]);
+        assert!(!detect_reuse(&public_key, &a, &forged));
+    }
+
+    #[test]
+    fn tracks_indices_per_key() {
+        let (public_key, a, b) = reused(b"first", b"second");
+        let mut private_key = PrivateKey::generate(2).unwrap();
+        let other_key = private_key.public_key();
+        let c = private_key.sign(b"first").unwrap();
+
+        let mut tracker = IndexTracker::new();
+        assert_eq!(tracker.record(&public_key, &a), Ok(()));
+        assert_eq!(tracker.record(&public_key, &a), Ok(()));
+        assert_eq!(tracker.record(&other_key, &c), Ok(()));
+        assert_eq!(tracker.record(&other_key, &a), Err(TrackingError::Invalid));
+        assert_eq!(
+            tracker.record(&public_key, &b),
+            Err(TrackingError::Reused { index: 0 })
+        );
+        assert_eq!(tracker.count(&public_key), 1);
+        assert_eq!(tracker.count(&other_key), 1);
+    }
+}
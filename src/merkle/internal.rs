@@ -1,68 +1,124 @@
-use std::collections::VecDeque;
+use std::marker::PhantomData;
 
-use blake3::{Hash, Hasher};
+use crate::hash::{Blake3, HashFunction};
+use blake3::Hash;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-fn hash_two_hashes(h1: &Hash, h2: &Hash) -> Hash {
-    let mut hasher = Hasher::new();
-    hasher.update(h1.as_bytes());
-    hasher.update(h2.as_bytes());
-    hasher.finalize()
+pub(crate) fn hash_two_hashes<H: HashFunction>(h1: &Hash, h2: &Hash) -> Hash {
+    H::digest(&[h1.as_bytes(), h2.as_bytes()])
+}
+
+/// Hashes adjacent pairs of a level to form the level above it. An
+/// unpaired node at the end of a level is carried up unchanged.
+fn hash_pair<H: HashFunction>(pair: &[Hash]) -> Hash {
+    match pair {
+        [left, right] => hash_two_hashes::<H>(left, right),
+        [only] => *only,
+        _ => unreachable!(),
+    }
+}
+
+fn hash_leaves<H: HashFunction>(leaves: &[&[u8]]) -> Vec<Hash> {
+    #[cfg(feature = "rayon")]
+    return leaves.par_iter().map(|leaf| H::digest(&[leaf])).collect();
+    #[cfg(not(feature = "rayon"))]
+    return leaves.iter().map(|leaf| H::digest(&[leaf])).collect();
+}
+
+fn next_level<H: HashFunction>(level: &[Hash]) -> Vec<Hash> {
+    #[cfg(feature = "rayon")]
+    return level.par_chunks(2).map(hash_pair::<H>).collect();
+    #[cfg(not(feature = "rayon"))]
+    return level.chunks(2).map(hash_pair::<H>).collect();
 }
 
 /// A binary Merkle tree, forming a commitment scheme to an underlying
 /// sequence of binary strings.
 ///
-/// The bottom level is the length of the input sequence of binary strings.
-/// The top level is the second-to-tallest level in the tree, with the root
-/// being contained within the [`Tree`] directly.
+/// Every level below the root is kept in [`Nodes`], from the leaves up,
+/// as consecutive 32 byte hashes. The width of each level follows from the
+/// number of items, and the root is contained within the [`Tree`] directly.
 #[derive(PartialEq, Eq, Clone, Debug)]
-pub(crate) struct Tree {
+pub(crate) struct Tree<H: HashFunction = Blake3> {
     root: Hash,
-    levels: VecDeque<Vec<Hash>>,
+    num_items: u64,
+    nodes: Nodes,
+    hash: PhantomData<H>,
+}
+
+/// The bytes of the levels of a [`Tree`] below its root: built in memory,
+/// or mapped from a file written by [`Tree::encode`].
+#[derive(Clone)]
+pub(crate) enum Nodes {
+    Owned(Vec<u8>),
+    #[cfg(feature = "memmap2")]
+    Mapped(std::sync::Arc<memmap2::Mmap>, usize),
+}
+
+impl Nodes {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Nodes::Owned(bytes) => bytes,
+            #[cfg(feature = "memmap2")]
+            Nodes::Mapped(map, offset) => &map[*offset..],
+        }
+    }
+}
+
+impl PartialEq for Nodes {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Nodes {}
+
+impl std::fmt::Debug for Nodes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Nodes({} bytes)", self.as_bytes().len())
+    }
+}
+
+/// The widths of the levels below the root of a tree over `num_items`
+/// items, from the leaves up.
+fn level_widths(num_items: u64) -> impl Iterator<Item = u64> {
+    std::iter::successors(Some(num_items), |width| Some(width.div_ceil(2)))
+        .take_while(|&width| width > 1)
 }
 
 /// A commitment to a binary Merkle tree.
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub(crate) struct Commitment {
     pub(crate) root: Hash,
     pub(crate) num_items: u64,
 }
 
-impl Commitment {
-    pub(crate) fn verify(&self, pf: &Proof) -> bool {
-        let mut current_hash = blake3::hash(&pf.item);
-        let mut current_index = pf.index;
-        let mut width = self.num_items;
-        for i in 0..(pf.frontier.len() as u64) {
-            let odd = width % 2;
-            match &pf.frontier[i as usize] {
-                ProofNode::NodeWithoutSibling => {
-                    if current_index != width && odd != 1 {
-                        return false;
-                    }
-                    current_index = current_index / 2;
-                }
-                ProofNode::LeftChildWithSibling(right_sibling_hash) => {
-                    if current_index % 2 != 0 {
-                        return false;
-                    }
+// `blake3::Hash` only offers constant-time equality, so order by bytes.
+impl PartialOrd for Commitment {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-                    current_hash = hash_two_hashes(&current_hash, right_sibling_hash);
-                    current_index = current_index / 2;
-                }
-                ProofNode::RightChildWithSibling(left_sibling_hash) => {
-                    if current_index % 2 != 1 {
-                        return false;
-                    }
+impl Ord for Commitment {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.to_bytes().cmp(&other.to_bytes())
+    }
+}
 
-                    current_hash = hash_two_hashes(left_sibling_hash, &current_hash);
-                    current_index = (current_index - 1) / 2;
-                }
-            }
-            width = width / 2 + odd;
-        }
+impl Commitment {
+    /// Encodes the root followed by the number of items, as in the
+    /// encoding of a [`crate::merkle::PublicKey`].
+    pub(crate) fn to_bytes(&self) -> [u8; 40] {
+        let mut arr = [0u8; 40];
+        arr[..32].copy_from_slice(self.root.as_bytes());
+        arr[32..].copy_from_slice(&self.num_items.to_be_bytes());
+        arr
+    }
 
-        current_hash == self.root
+    pub(crate) fn verify<H: HashFunction>(&self, pf: &Proof<H>) -> bool {
+        pf.root(self.num_items) == Some(self.root)
     }
 }
 
@@ -86,111 +142,332 @@ impl Commitment {
 /// can show B, 1, 2 and the consumer of this proof can re-construct
 /// A from 1 and 2. We reveal ancillary commitments to other data,
 /// such as 2 and B, but those commitments are zero-knowledge unless
-/// you can find collisions for the [`blake3::hash`] function.
-#[derive(Debug, Eq, PartialEq)]
-pub(crate) struct Proof {
+/// you can find collisions for the hash function `H`.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub(crate) struct Proof<H: HashFunction = Blake3> {
     item: Vec<u8>,
     index: u64,
     frontier: Vec<ProofNode>,
+    hash: PhantomData<H>,
+}
+
+/// Bounds applied while decoding a [`Proof`] from untrusted bytes, so
+/// that a hostile length prefix cannot make the decoder allocate or loop
+/// without limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodingLimits {
+    /// The longest item, in bytes, that a proof may carry.
+    pub max_item_len: u64,
+    /// The largest number of frontier nodes, i.e. the deepest tree, that a
+    /// proof may carry.
+    pub max_depth: u64,
+}
+
+impl Default for DecodingLimits {
+    /// Admits items the size of an encoded Lamport public key and trees
+    /// of any depth reachable with a `u64` index.
+    fn default() -> Self {
+        DecodingLimits {
+            max_item_len: 16384,
+            max_depth: 64,
+        }
+    }
 }
 
-#[derive(Debug)]
+/// An error encountered while decoding a [`Proof`]. Every offset is the
+/// position, in bytes, within the input handed to the decoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ProofDecodingError {
-    NotEnoughInput(usize),
-    InvalidProofNodeType(u8),
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// The frontier node starting at `offset` has an unknown tag.
+    InvalidProofNodeType { offset: usize, tag: u8 },
+    /// The item length read at `offset` exceeds the configured limit.
+    ItemTooLong {
+        offset: usize,
+        length: u64,
+        limit: u64,
+    },
+    /// The frontier length read at `offset` exceeds the configured limit.
+    TooDeep {
+        offset: usize,
+        depth: u64,
+        limit: u64,
+    },
+    /// The proof ended at `offset`, but `remaining` bytes followed it.
+    TrailingBytes { offset: usize, remaining: usize },
 }
 
-impl TryFrom<&[u8]> for Proof {
-    type Error = ProofDecodingError;
+impl std::fmt::Display for ProofDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofDecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "proof truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            ProofDecodingError::InvalidProofNodeType { offset, tag } => {
+                write!(f, "invalid proof node type {tag} at offset {offset}")
+            }
+            ProofDecodingError::ItemTooLong {
+                offset,
+                length,
+                limit,
+            } => write!(
+                f,
+                "proof item of {length} bytes at offset {offset} exceeds the limit of {limit}"
+            ),
+            ProofDecodingError::TooDeep {
+                offset,
+                depth,
+                limit,
+            } => write!(
+                f,
+                "proof depth of {depth} at offset {offset} exceeds the limit of {limit}"
+            ),
+            ProofDecodingError::TrailingBytes { offset, remaining } => write!(
+                f,
+                "{remaining} trailing bytes after the proof ending at offset {offset}"
+            ),
+        }
+    }
+}
 
-    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
-        if encoded.len() < 8 {
-            return Err(ProofDecodingError::NotEnoughInput(encoded.len()));
+impl std::error::Error for ProofDecodingError {}
+
+/// A cursor over untrusted input which never reads past its end.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Starts reading `bytes` at `offset`, so that errors report positions
+    /// within the whole of `bytes`.
+    pub(crate) fn new(bytes: &'a [u8], offset: usize) -> Self {
+        Reader { bytes, offset }
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.offset)
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], ProofDecodingError> {
+        if self.remaining() < n {
+            return Err(ProofDecodingError::NotEnoughInput {
+                offset: self.offset,
+                needed: n - self.remaining(),
+            });
         }
+        let taken = &self.bytes[self.offset..self.offset + n];
+        self.offset += n;
+        Ok(taken)
+    }
 
-        let mut i = 0;
-        let next_byte = |i: &mut usize| {
-            if let Some(&b) = encoded.get(*i) {
-                *i += 1;
-                Ok(b)
-            } else {
-                Err(ProofDecodingError::NotEnoughInput(encoded.len()))
-            }
-        };
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], ProofDecodingError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
 
-        let next_u64 = |mut i: &mut usize| {
-            let mut u64_bytes = [0u8; 8];
-            for j in 0..8 {
-                u64_bytes[j] = next_byte(&mut i)?;
-            }
-            Ok(u64::from_be_bytes(u64_bytes))
-        };
+    fn byte(&mut self) -> Result<u8, ProofDecodingError> {
+        Ok(self.take(1)?[0])
+    }
 
-        let next_n_bytes = |mut i: &mut usize, n: u64| {
-            let mut v = Vec::new();
-            for _ in 0..n {
-                v.push(next_byte(&mut i)?);
-            }
-            Ok(v)
-        };
+    fn u64(&mut self) -> Result<u64, ProofDecodingError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
 
-        let next_hash = |mut i: &mut usize| {
-            let mut hash_bytes = [0u8; 32];
-            for j in 0..32 {
-                hash_bytes[j] = next_byte(&mut i)?;
-            }
-            Ok(Hash::from(hash_bytes))
-        };
+    fn hash(&mut self) -> Result<Hash, ProofDecodingError> {
+        Ok(Hash::from(self.array::<32>()?))
+    }
+
+    /// Fails if any input is left over.
+    pub(crate) fn finish(&self) -> Result<(), ProofDecodingError> {
+        match self.remaining() {
+            0 => Ok(()),
+            remaining => Err(ProofDecodingError::TrailingBytes {
+                offset: self.offset,
+                remaining,
+            }),
+        }
+    }
+}
 
-        let next_frontier_node = |mut i: &mut usize| {
-            let tag = next_byte(&mut i)?;
-            match tag {
-                0 => Ok(ProofNode::NodeWithoutSibling),
-                1 => Ok(ProofNode::LeftChildWithSibling(next_hash(&mut i)?)),
-                2 => Ok(ProofNode::RightChildWithSibling(next_hash(&mut i)?)),
-                b => Err(ProofDecodingError::InvalidProofNodeType(b)),
+impl<H: HashFunction> Proof<H> {
+    /// The root of the tree over `num_items` items that this proof leads
+    /// to, or [`None`] if the proof does not fit the shape of such a tree.
+    pub(crate) fn root(&self, num_items: u64) -> Option<Hash> {
+        if self.index >= num_items {
+            return None;
+        }
+
+        let mut current_hash = H::digest(&[&self.item]);
+        let mut current_index = self.index;
+        let mut width = num_items;
+        for node in self.frontier.iter() {
+            let odd = width % 2;
+            match node {
+                ProofNode::NodeWithoutSibling => {
+                    if current_index != width - 1 || odd != 1 {
+                        return None;
+                    }
+                    current_index /= 2;
+                }
+                ProofNode::LeftChildWithSibling(right_sibling_hash) => {
+                    if !current_index.is_multiple_of(2) || current_index + 1 >= width {
+                        return None;
+                    }
+
+                    current_hash = hash_two_hashes::<H>(&current_hash, right_sibling_hash);
+                    current_index /= 2;
+                }
+                ProofNode::RightChildWithSibling(left_sibling_hash) => {
+                    if current_index % 2 != 1 {
+                        return None;
+                    }
+
+                    current_hash = hash_two_hashes::<H>(left_sibling_hash, &current_hash);
+                    current_index = (current_index - 1) / 2;
+                }
             }
-        };
+            width = width / 2 + odd;
+        }
 
-        let length = next_u64(&mut i)?;
-        let item: Vec<u8> = next_n_bytes(&mut i, length)?;
+        // a proof which stops short of the root proves an inner node, not a leaf
+        if width != 1 {
+            return None;
+        }
+        Some(current_hash)
+    }
 
-        let index = next_u64(&mut i)?;
-        let length = next_u64(&mut i)?;
+    /// Assembles a proof from a frontier computed outside of a [`Tree`].
+    pub(crate) fn new(item: Vec<u8>, index: u64, frontier: Vec<ProofNode>) -> Self {
+        Proof {
+            item,
+            index,
+            frontier,
+            hash: PhantomData,
+        }
+    }
+
+    /// Decodes a proof occupying the whole of `encoded`, subject to `limits`.
+    pub(crate) fn decode(
+        encoded: &[u8],
+        limits: &DecodingLimits,
+    ) -> Result<Self, ProofDecodingError> {
+        let mut reader = Reader::new(encoded, 0);
+        let proof = Proof::decode_from(&mut reader, limits)?;
+        reader.finish()?;
+        Ok(proof)
+    }
+
+    /// Decodes a proof from the current position of `reader`, leaving it
+    /// just past the end of the proof.
+    pub(crate) fn decode_from(
+        reader: &mut Reader,
+        limits: &DecodingLimits,
+    ) -> Result<Self, ProofDecodingError> {
+        let length_offset = reader.offset();
+        let length = reader.u64()?;
+        if length > limits.max_item_len {
+            return Err(ProofDecodingError::ItemTooLong {
+                offset: length_offset,
+                length,
+                limit: limits.max_item_len,
+            });
+        }
+        let length = usize::try_from(length).map_err(|_| ProofDecodingError::ItemTooLong {
+            offset: length_offset,
+            length,
+            limit: limits.max_item_len,
+        })?;
+        let item = reader.take(length)?.to_vec();
+
+        let index = reader.u64()?;
+
+        let depth_offset = reader.offset();
+        let depth = reader.u64()?;
+        if depth > limits.max_depth {
+            return Err(ProofDecodingError::TooDeep {
+                offset: depth_offset,
+                depth,
+                limit: limits.max_depth,
+            });
+        }
 
         let mut frontier: Vec<ProofNode> = Vec::new();
-        for _ in 0..length {
-            frontier.push(next_frontier_node(&mut i)?);
+        for _ in 0..depth {
+            let tag_offset = reader.offset();
+            let node = match reader.byte()? {
+                0 => ProofNode::NodeWithoutSibling,
+                1 => ProofNode::LeftChildWithSibling(reader.hash()?),
+                2 => ProofNode::RightChildWithSibling(reader.hash()?),
+                tag => {
+                    return Err(ProofDecodingError::InvalidProofNodeType {
+                        offset: tag_offset,
+                        tag,
+                    })
+                }
+            };
+            frontier.push(node);
         }
 
         Ok(Proof {
             item,
             index,
             frontier,
+            hash: PhantomData,
         })
     }
+
+    /// This proof, but for `item`, which must be the item committed at the
+    /// same index for it to verify.
+    pub(crate) fn with_item(self, item: Vec<u8>) -> Self {
+        Proof { item, ..self }
+    }
+
+    /// The item this proof shows to be in the committed sequence.
+    pub(crate) fn item(&self) -> &[u8] {
+        &self.item
+    }
+
+    /// The position of the item in the committed sequence.
+    pub(crate) fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// The number of levels between the item and the root.
+    pub(crate) fn depth(&self) -> usize {
+        self.frontier.len()
+    }
+}
+
+impl<H: HashFunction> TryFrom<&[u8]> for Proof<H> {
+    type Error = ProofDecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        Proof::decode(encoded, &DecodingLimits::default())
+    }
 }
 
-impl From<&Proof> for Vec<u8> {
-    fn from(pf: &Proof) -> Self {
+impl<H: HashFunction> From<&Proof<H>> for Vec<u8> {
+    fn from(pf: &Proof<H>) -> Self {
         fn encode_proof_node(pf_node: &ProofNode, output: &mut Vec<u8>) {
             match pf_node {
                 ProofNode::NodeWithoutSibling => {
                     output.push(0);
                 }
                 ProofNode::LeftChildWithSibling(hash) => {
-                    let bytes: [u8; 32] = hash.clone().into();
                     output.push(1);
-                    for i in 0..32 {
-                        output.push(bytes[i]);
-                    }
+                    output.extend_from_slice(hash.as_bytes());
                 }
                 ProofNode::RightChildWithSibling(hash) => {
-                    let bytes: [u8; 32] = hash.clone().into();
                     output.push(2);
-                    for i in 0..32 {
-                        output.push(bytes[i]);
-                    }
+                    output.extend_from_slice(hash.as_bytes());
                 }
             }
         }
@@ -198,7 +475,7 @@ impl From<&Proof> for Vec<u8> {
         let mut output = Vec::new();
         output.extend((pf.item.len() as u64).to_be_bytes().iter().copied());
         output.extend(pf.item.iter().copied());
-        output.extend((pf.index as u64).to_be_bytes().iter().copied());
+        output.extend(pf.index.to_be_bytes().iter().copied());
         output.extend((pf.frontier.len() as u64).to_be_bytes().iter().copied());
 
         for node in pf.frontier.iter() {
@@ -209,123 +486,152 @@ impl From<&Proof> for Vec<u8> {
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
-enum ProofNode {
+#[allow(clippy::enum_variant_names)]
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub(crate) enum ProofNode {
     NodeWithoutSibling,
     LeftChildWithSibling(Hash),
     RightChildWithSibling(Hash),
 }
 
-/// A proof from a binary Merkle tree, representing evidence that a
-/// particular index contains a particular element.
+/// An error encountered while building a [`Tree`] or proving one of its
+/// items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeError {
+    /// A tree must commit to at least one item.
+    EmptyInput,
+    /// The tree only has `num_items` items, so there is nothing at `index`.
+    IndexOutOfRange { index: u64, num_items: u64 },
+    /// The item offered for `index` is not the one the tree commits to.
+    LeafMismatch { index: u64 },
+    /// The stored nodes on the path from `index` do not lead to the root.
+    Corrupted { index: u64 },
+    /// Every index of the reservation given for signing is used up.
+    Unreserved,
+}
 
-impl Tree {
-    pub(crate) fn prove(&self, item: Vec<u8>, index: u64) -> Option<Proof> {
-        let mut depth = self.levels.len();
-        if depth == 0 {
-            if index != 0 {
-                return None;
+impl std::fmt::Display for TreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeError::EmptyInput => write!(f, "a merkle tree needs at least one item"),
+            TreeError::IndexOutOfRange { index, num_items } => write!(
+                f,
+                "index {index} is out of range for a tree of {num_items} items"
+            ),
+            TreeError::LeafMismatch { index } => {
+                write!(f, "the item at index {index} does not match the tree")
             }
-            if self.root == blake3::hash(&item) {
-                return Some(Proof {
-                    item,
-                    index,
-                    frontier: Vec::new(),
-                });
+            TreeError::Corrupted { index } => {
+                write!(f, "the path from index {index} does not match the root")
             }
+            TreeError::Unreserved => write!(f, "the reservation has no indices left"),
         }
-        if let Some(hash) = self.levels[depth - 1].get(index as usize) {
-            // reject the proof if the hash at the leaf is incorrect
-            if *hash != blake3::hash(&item) {
-                return None;
-            }
-        } else {
-            // reject the proof if that index is absent
-            return None;
+    }
+}
+
+impl std::error::Error for TreeError {}
+
+impl<H: HashFunction> Tree<H> {
+    /// Produces a [`Proof`] that `item` is at `index` in the committed sequence.
+    ///
+    /// The proof is checked against the root before it is returned, so
+    /// that nodes corrupted in a stored tree fail here rather than in the
+    /// hands of a verifier.
+    pub(crate) fn prove(&self, item: Vec<u8>, index: u64) -> Result<Proof<H>, TreeError> {
+        let num_items = self.num_items();
+        if index >= num_items {
+            return Err(TreeError::IndexOutOfRange { index, num_items });
+        }
+
+        let leaf = match num_items {
+            1 => self.root,
+            _ => self.node(0, index),
+        };
+        // reject the proof if the hash at the leaf is incorrect
+        if leaf != H::digest(&[&item]) {
+            return Err(TreeError::LeafMismatch { index });
         }
 
         let mut frontier: Vec<ProofNode> = Vec::new();
-        let mut width = self.num_items();
         let mut current_index = index;
-        loop {
-            let odd = width % 2;
-            if current_index == width && odd == 1 {
-                frontier.push(ProofNode::NodeWithoutSibling);
-                current_index = width / 2;
-            } else if current_index % 2 == 0 {
-                frontier.push(ProofNode::LeftChildWithSibling(
-                    self.levels[depth - 1][(current_index + 1) as usize],
-                ));
-                current_index = current_index / 2;
-            } else if current_index % 2 == 1 {
+        for (level, width) in level_widths(num_items).enumerate() {
+            if current_index.is_multiple_of(2) {
+                frontier.push(match current_index + 1 < width {
+                    true => ProofNode::LeftChildWithSibling(self.node(level, current_index + 1)),
+                    false => ProofNode::NodeWithoutSibling,
+                });
+            } else {
                 frontier.push(ProofNode::RightChildWithSibling(
-                    self.levels[depth - 1][(current_index - 1) as usize],
+                    self.node(level, current_index - 1),
                 ));
-                current_index = (current_index - 1) / 2;
             }
-            depth -= 1;
-            if depth == 0 {
-                break;
-            }
-            width = width / 2 + odd;
+            current_index /= 2;
         }
 
-        Some(Proof {
+        let proof = Proof {
             item,
             index,
             frontier,
-        })
+            hash: PhantomData,
+        };
+        match self.commitment().verify(&proof) {
+            true => Ok(proof),
+            false => Err(TreeError::Corrupted { index }),
+        }
     }
 
     pub(crate) fn num_items(&self) -> u64 {
-        if self.levels.len() == 0 {
-            return 1;
-        } else {
-            self.levels[self.levels.len() - 1].len() as u64
-        }
+        self.num_items
     }
 
-    pub(crate) fn new<'a>(leaves: &mut impl Iterator<Item = &'a [u8]>) -> Self {
-        let mut levels: VecDeque<Vec<Hash>> = VecDeque::new();
-        levels.push_front(leaves.map(blake3::hash).collect());
-        if levels[0].len() == 1 {
-            return Tree {
-                root: levels[0][0],
-                levels: VecDeque::new(),
-            };
+    /// The node at `index` of `level`, counting levels from the leaves up.
+    fn node(&self, level: usize, index: u64) -> Hash {
+        let offset: u64 = level_widths(self.num_items).take(level).sum::<u64>() + index;
+        let offset = offset as usize * 32;
+        let bytes: [u8; 32] = self.nodes.as_bytes()[offset..offset + 32]
+            .try_into()
+            .expect("nodes are 32 bytes");
+        Hash::from(bytes)
+    }
+
+    /// Builds the tree over `leaves`, hashing each level on all cores when
+    /// the `rayon` feature is enabled.
+    pub(crate) fn new<'a>(leaves: &mut impl Iterator<Item = &'a [u8]>) -> Result<Self, TreeError> {
+        let leaves: Vec<&[u8]> = leaves.collect();
+        let mut levels: Vec<Vec<Hash>> = vec![hash_leaves::<H>(&leaves)];
+        if levels[0].is_empty() {
+            return Err(TreeError::EmptyInput);
         }
 
-        loop {
-            let n = levels[0].len();
-            if n == 2 {
-                let root = hash_two_hashes(&levels[0][0], &levels[0][1]);
+        while levels[levels.len() - 1].len() > 1 {
+            let level = next_level::<H>(&levels[levels.len() - 1]);
+            levels.push(level);
+        }
 
-                return Tree { root, levels };
-            } else {
-                let odd = if n % 2 == 0 { 0 } else { 1 };
-                let m = n - odd;
-                let mut level: Vec<Hash> = vec![Hash::from([0u8; 32]); m / 2 + odd];
-                let mut i = 0;
-                loop {
-                    if i == m / 2 {
-                        break;
-                    }
+        let root = levels.pop().expect("a tree has a root")[0];
+        Ok(Tree::from_levels(root, levels))
+    }
 
-                    level[i] = hash_two_hashes(&levels[0][i * 2], &levels[0][i * 2 + 1]);
-                    i += 1;
-                }
-                if odd == 1 {
-                    level[i] = levels[0][i * 2];
-                }
-                levels.push_front(level);
-            }
+    /// Assembles a tree from its root and the levels below it, leaves
+    /// first.
+    fn from_levels(root: Hash, levels: Vec<Vec<Hash>>) -> Self {
+        let num_items = levels.first().map_or(1, |leaves| leaves.len() as u64);
+        let nodes = levels
+            .iter()
+            .flatten()
+            .flat_map(|node| *node.as_bytes())
+            .collect();
+        Tree {
+            root,
+            num_items,
+            nodes: Nodes::Owned(nodes),
+            hash: PhantomData,
         }
     }
 
     #[cfg(test)]
     pub(crate) fn verify<'a>(&self, leaves: &mut impl Iterator<Item = &'a [u8]>) -> bool {
-        let other = Tree::new(leaves);
-        *self == other
+        Tree::new(leaves).as_ref() == Ok(self)
     }
 
     pub(crate) fn commitment(&self) -> Commitment {
@@ -334,17 +640,145 @@ impl Tree {
             num_items: self.num_items(),
         }
     }
+
+    /// Encodes the hash function, the commitment and then every node
+    /// below the root, so that the tree can be stored and later loaded
+    /// with [`Tree::decode`] without being rebuilt.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(TREE_HEADER_LEN + self.nodes.as_bytes().len());
+        output.extend_from_slice(&TREE_MAGIC);
+        output.push(H::ID);
+        output.extend_from_slice(&self.commitment().to_bytes());
+        output.extend_from_slice(self.nodes.as_bytes());
+        output
+    }
+
+    /// Loads a tree written by [`Tree::encode`], which `nodes` holds in
+    /// full. Only the header, the length and the level just below the root
+    /// are checked here, so that loading does not scale with the size of
+    /// the tree; every proof is checked as it is made.
+    pub(crate) fn decode(nodes: Nodes) -> Result<Self, TreeDecodingError> {
+        let bytes = nodes.as_bytes();
+        let mut reader = Reader::new(bytes, 0);
+        let header = reader.array::<{ TREE_MAGIC.len() + 1 }>();
+        match header {
+            Ok(header) if header[..4] == TREE_MAGIC && header[4] == H::ID => {}
+            _ => return Err(TreeDecodingError::BadHeader),
+        }
+        let root = Hash::from(
+            reader
+                .array::<32>()
+                .map_err(|_| TreeDecodingError::BadHeader)?,
+        );
+        let num_items = u64::from_be_bytes(
+            reader
+                .array::<8>()
+                .map_err(|_| TreeDecodingError::BadHeader)?,
+        );
+        if num_items == 0 {
+            return Err(TreeDecodingError::BadHeader);
+        }
+        let expected = Tree::<H>::encoded_len(num_items);
+        if expected != Some(bytes.len() as u64) {
+            return Err(TreeDecodingError::WrongLength {
+                expected,
+                found: bytes.len() as u64,
+            });
+        }
+
+        let nodes = match nodes {
+            Nodes::Owned(mut bytes) => {
+                bytes.drain(..TREE_HEADER_LEN);
+                Nodes::Owned(bytes)
+            }
+            #[cfg(feature = "memmap2")]
+            Nodes::Mapped(map, offset) => Nodes::Mapped(map, offset + TREE_HEADER_LEN),
+        };
+        let tree = Tree {
+            root,
+            num_items,
+            nodes,
+            hash: PhantomData,
+        };
+        let height = level_widths(num_items).count();
+        if height > 0
+            && hash_two_hashes::<H>(&tree.node(height - 1, 0), &tree.node(height - 1, 1)) != root
+        {
+            return Err(TreeDecodingError::Corrupted);
+        }
+        Ok(tree)
+    }
+
+    /// The length of the encoding of a tree over `num_items` items, or
+    /// [`None`] if it does not fit in a `u64`.
+    fn encoded_len(num_items: u64) -> Option<u64> {
+        level_widths(num_items)
+            .sum::<u64>()
+            .checked_mul(32)
+            .and_then(|len| len.checked_add(TREE_HEADER_LEN as u64))
+    }
+
+    /// Loads a tree written by [`Tree::encode`] from the start of `bytes`,
+    /// checked as [`Tree::decode`] checks it, along with the number of bytes
+    /// it took.
+    pub(crate) fn decode_prefix(bytes: &[u8]) -> Result<(Self, usize), TreeDecodingError> {
+        let len = bytes
+            .get(TREE_HEADER_LEN - 8..TREE_HEADER_LEN)
+            .and_then(|num_items| {
+                Tree::<H>::encoded_len(u64::from_be_bytes(num_items.try_into().ok()?))
+            })
+            .filter(|&len| len <= bytes.len() as u64)
+            // otherwise decoding the whole input reports what is wrong
+            .map_or(bytes.len(), |len| len as usize);
+        Tree::decode(Nodes::Owned(bytes[..len].to_vec())).map(|tree| (tree, len))
+    }
+}
+
+const TREE_MAGIC: [u8; 4] = *b"B3LT";
+/// The magic, the hash function and the commitment preceding the nodes.
+const TREE_HEADER_LEN: usize = TREE_MAGIC.len() + 1 + 40;
+
+/// An error encountered while loading a [`Tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeDecodingError {
+    /// The input does not start with the header of a tree over the
+    /// expected hash function.
+    BadHeader,
+    /// The input holds `found` bytes, but the tree its header describes
+    /// takes `expected`.
+    WrongLength { expected: Option<u64>, found: u64 },
+    /// The nodes below the root do not hash to it.
+    Corrupted,
+}
+
+impl std::fmt::Display for TreeDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeDecodingError::BadHeader => write!(f, "not a stored merkle tree"),
+            TreeDecodingError::WrongLength {
+                expected: Some(expected),
+                found,
+            } => write!(f, "stored tree is {found} bytes long, not {expected}"),
+            TreeDecodingError::WrongLength {
+                expected: None,
+                found,
+            } => write!(f, "stored tree of {found} bytes claims too many items"),
+            TreeDecodingError::Corrupted => write!(f, "stored tree does not match its root"),
+        }
+    }
 }
 
+impl std::error::Error for TreeDecodingError {}
+
 #[test]
 fn test_tree() {
-    fn test_verify(v: &Vec<&[u8]>) {
-        let tree = Tree::new(&mut v.clone().into_iter());
-        assert!(tree.verify(&mut v.clone().into_iter()));
+    fn test_verify(v: &[&[u8]]) {
+        let tree = Tree::<Blake3>::new(&mut v.iter().copied()).unwrap();
+        assert!(tree.verify(&mut v.iter().copied()));
     }
 
-    fn modify_frontier(frontier: &mut Vec<ProofNode>) -> bool {
-        if frontier.len() > 0 {
+    fn modify_frontier(frontier: &mut [ProofNode]) -> bool {
+        if !frontier.is_empty() {
             if frontier[0] == ProofNode::NodeWithoutSibling {
                 frontier[0] = ProofNode::LeftChildWithSibling(blake3::hash(b"hello, world"));
             } else {
@@ -356,8 +790,8 @@ fn test_tree() {
         }
     }
 
-    fn test_prove(v: &Vec<&[u8]>) {
-        let tree = Tree::new(&mut v.clone().into_iter());
+    fn test_prove(v: &[&[u8]]) {
+        let tree = Tree::<Blake3>::new(&mut v.iter().copied()).unwrap();
         let mut proof = tree.prove(v[0].into(), 0).unwrap();
         let v: Vec<u8> = (&proof).into();
         let v_ref: &[u8] = &v;
@@ -381,3 +815,205 @@ fn test_tree() {
         test_prove(&test_vector);
     }
 }
+
+#[test]
+fn test_proof_decoding() {
+    let leaves: Vec<&[u8]> = vec![b"one", b"two", b"three"];
+    let tree = Tree::<Blake3>::new(&mut leaves.iter().copied()).unwrap();
+    let proof = tree.prove(leaves[0].into(), 0).unwrap();
+    let encoded: Vec<u8> = (&proof).into();
+    let limits = DecodingLimits::default();
+
+    assert_eq!(Proof::<Blake3>::decode(&encoded, &limits), Ok(proof));
+
+    assert_eq!(
+        Proof::<Blake3>::decode(&encoded[..encoded.len() - 1], &limits),
+        Err(ProofDecodingError::NotEnoughInput {
+            offset: encoded.len() - 32,
+            needed: 1,
+        })
+    );
+
+    let mut trailing = encoded.clone();
+    trailing.push(0);
+    assert_eq!(
+        Proof::<Blake3>::decode(&trailing, &limits),
+        Err(ProofDecodingError::TrailingBytes {
+            offset: encoded.len(),
+            remaining: 1,
+        })
+    );
+
+    let short_items = DecodingLimits {
+        max_item_len: 2,
+        ..limits
+    };
+    assert_eq!(
+        Proof::<Blake3>::decode(&encoded, &short_items),
+        Err(ProofDecodingError::ItemTooLong {
+            offset: 0,
+            length: 3,
+            limit: 2,
+        })
+    );
+
+    let shallow = DecodingLimits {
+        max_depth: 1,
+        ..limits
+    };
+    assert_eq!(
+        Proof::<Blake3>::decode(&encoded, &shallow),
+        Err(ProofDecodingError::TooDeep {
+            offset: 19,
+            depth: 2,
+            limit: 1,
+        })
+    );
+
+    let mut bad_tag = encoded.clone();
+    bad_tag[27] = 7;
+    assert_eq!(
+        Proof::<Blake3>::decode(&bad_tag, &limits),
+        Err(ProofDecodingError::InvalidProofNodeType { offset: 27, tag: 7 })
+    );
+
+    // a hostile length prefix is rejected before anything is read
+    let mut huge = u64::MAX.to_be_bytes().to_vec();
+    huge.extend_from_slice(&[0u8; 16]);
+    assert!(matches!(
+        Proof::<Blake3>::decode(&huge, &limits),
+        Err(ProofDecodingError::ItemTooLong { offset: 0, .. })
+    ));
+    let generous = DecodingLimits {
+        max_item_len: u64::MAX,
+        max_depth: u64::MAX,
+    };
+    assert!(matches!(
+        Proof::<Blake3>::decode(&huge, &generous),
+        Err(ProofDecodingError::NotEnoughInput { offset: 8, .. })
+    ));
+}
+
+#[test]
+fn test_prove_every_index() {
+    for n in 1..70u64 {
+        let leaves: Vec<[u8; 8]> = (0..n).map(u64::to_be_bytes).collect();
+        let tree = Tree::<Blake3>::new(&mut leaves.iter().map(|leaf| leaf.as_slice())).unwrap();
+        let commitment = tree.commitment();
+        assert_eq!(commitment.num_items, n);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.prove(leaf.to_vec(), index as u64).unwrap();
+            assert!(commitment.verify(&proof));
+            for other in 0..n {
+                if other != index as u64 {
+                    let moved = Proof {
+                        index: other,
+                        ..tree.prove(leaf.to_vec(), index as u64).unwrap()
+                    };
+                    assert!(!commitment.verify(&moved));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tree_errors() {
+    assert_eq!(
+        Tree::<Blake3>::new(&mut std::iter::empty()),
+        Err(TreeError::EmptyInput)
+    );
+
+    let single = Tree::<Blake3>::new(&mut std::iter::once(b"only".as_slice())).unwrap();
+    assert_eq!(
+        single.prove(b"other".to_vec(), 0),
+        Err(TreeError::LeafMismatch { index: 0 })
+    );
+    assert_eq!(
+        single.prove(b"only".to_vec(), 1),
+        Err(TreeError::IndexOutOfRange {
+            index: 1,
+            num_items: 1
+        })
+    );
+
+    // an inner node must not pass for a leaf
+    let leaves: Vec<&[u8]> = vec![b"a", b"b", b"c", b"d"];
+    let tree = Tree::<Blake3>::new(&mut leaves.iter().copied()).unwrap();
+    let mut inner = Vec::new();
+    inner.extend_from_slice(blake3::hash(b"a").as_bytes());
+    inner.extend_from_slice(blake3::hash(b"b").as_bytes());
+    let mut proof = tree.prove(b"c".to_vec(), 2).unwrap();
+    proof.item = inner;
+    proof.index = 0;
+    proof.frontier.remove(0);
+    proof.frontier[0] = ProofNode::LeftChildWithSibling(tree.node(1, 1));
+    assert!(!tree.commitment().verify(&proof));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_serial() {
+        for n in [1u64, 2, 3, 1000, 4097] {
+            let leaves: Vec<[u8; 8]> = (0..n).map(u64::to_be_bytes).collect();
+            let tree = Tree::<Blake3>::new(&mut leaves.iter().map(|leaf| leaf.as_slice())).unwrap();
+
+            let mut levels: Vec<Vec<Hash>> =
+                vec![leaves.iter().map(|leaf| blake3::hash(leaf)).collect()];
+            while levels[levels.len() - 1].len() > 1 {
+                let level = levels[levels.len() - 1]
+                    .chunks(2)
+                    .map(hash_pair::<Blake3>)
+                    .collect();
+                levels.push(level);
+            }
+            let root = levels.pop().unwrap()[0];
+
+            assert_eq!(tree, Tree::from_levels(root, levels));
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig {
+            cases: 64, .. ProptestConfig::default()
+        })]
+
+        #[test]
+        fn prove_random_sizes(n in 0..10_000u64, choice in any::<u64>()) {
+            let leaves: Vec<[u8; 8]> = (0..n).map(u64::to_be_bytes).collect();
+            let tree = Tree::<Blake3>::new(&mut leaves.iter().map(|leaf| leaf.as_slice()));
+            if n == 0 {
+                prop_assert_eq!(tree, Err(TreeError::EmptyInput));
+                return Ok(());
+            }
+            let tree = tree.unwrap();
+            let commitment = tree.commitment();
+            prop_assert_eq!(commitment.num_items, n);
+
+            let index = choice % n;
+            let leaf = leaves[index as usize].to_vec();
+            let proof = tree.prove(leaf.clone(), index)?;
+            prop_assert!(commitment.verify(&proof));
+
+            let encoded: Vec<u8> = (&proof).into();
+            prop_assert_eq!(Proof::try_from(encoded.as_slice())?, proof);
+
+            prop_assert_eq!(
+                tree.prove(leaf.clone(), n),
+                Err(TreeError::IndexOutOfRange { index: n, num_items: n })
+            );
+            if n > 1 {
+                let other = (index + 1) % n;
+                prop_assert_eq!(
+                    tree.prove(leaf, other),
+                    Err(TreeError::LeafMismatch { index: other })
+                );
+            }
+        }
+    }
+}
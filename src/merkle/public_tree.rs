@@ -0,0 +1,244 @@
+use crate::hash::{Blake3, HashFunction};
+use crate::lamport::{self, Lamport256};
+use crate::merkle::internal::{Reader, Tree, TreeDecodingError, TreeError};
+use crate::merkle::seeded::KeyDecodingError;
+use crate::merkle::{PrivateKey, PublicKey, Signature};
+
+/// Everything needed to turn bare Lamport signatures into
+/// [`Signature`]s, and nothing secret: the Lamport public key of every leaf
+/// and every level of the tree over them.
+///
+/// It lets a proof server attach proofs to signatures made by an offline
+/// signer which holds only the Lamport secrets, as with
+/// [`PrivateKey::sign_leaf`], or only the seed of a
+/// [`seeded::PrivateKey`](super::seeded::PrivateKey),
+/// as with [`lamport::PrivateKey::derive`].
+#[derive(Clone, Debug)]
+pub struct PublicTree<H: HashFunction = Blake3> {
+    public_keys: Vec<lamport::PublicKey<Lamport256, H>>,
+    tree: Tree<H>,
+}
+
+impl<H: HashFunction> PublicTree<H> {
+    pub(crate) fn new(
+        public_keys: Vec<lamport::PublicKey<Lamport256, H>>,
+    ) -> Result<Self, TreeError> {
+        let leaves: Vec<[u8; 16384]> = public_keys.iter().map(|key| key.to_raw_bytes()).collect();
+        let tree = Tree::new(&mut leaves.iter().map(|leaf| leaf.as_slice()))?;
+        Ok(PublicTree { public_keys, tree })
+    }
+
+    pub fn public_key(&self) -> PublicKey<H> {
+        PublicKey::new(self.tree.commitment())
+    }
+
+    pub fn num_items(&self) -> u64 {
+        self.public_keys.len() as u64
+    }
+
+    /// Attaches the Lamport public key and the proof for `index` to
+    /// `lamport_signature`, which verifies only if it was made with the
+    /// Lamport key at `index`.
+    pub fn assemble_signature(
+        &self,
+        index: u64,
+        lamport_signature: lamport::Signature<Lamport256, H>,
+    ) -> Result<Signature<H>, TreeError> {
+        let lamport_public_key = self
+            .public_keys
+            .get(index as usize)
+            .ok_or(TreeError::IndexOutOfRange {
+                index,
+                num_items: self.num_items(),
+            })?
+            .clone();
+        let proof = self
+            .tree
+            .prove(lamport_public_key.to_raw_bytes().to_vec(), index)?;
+        Ok(Signature(lamport_signature, lamport_public_key, proof))
+    }
+
+    /// Encodes the tree as [`PrivateKey::tree_to_bytes`] does, every level
+    /// included, followed by every Lamport public key.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = self.tree.encode();
+        for public_key in &self.public_keys {
+            output.extend_from_slice(&public_key.to_raw_bytes());
+        }
+        output
+    }
+}
+
+impl<H: HashFunction> TryFrom<&[u8]> for PublicTree<H> {
+    type Error = KeyDecodingError;
+
+    /// Decodes a tree without rebuilding it. Like a
+    /// [`StoredTree`](super::StoredTree), only
+    /// its header, its length and the nodes just below the root are
+    /// checked here, and a corrupted node or Lamport public key makes
+    /// [`PublicTree::assemble_signature`] fail rather than return a
+    /// signature which does not verify. Compare [`PublicTree::public_key`]
+    /// with the published key to be sure the tree is the right one.
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let (tree, offset) = Tree::decode_prefix(encoded).map_err(|e| match e {
+            TreeDecodingError::WrongLength {
+                expected: Some(expected),
+                found,
+            } if expected > found => KeyDecodingError::NotEnoughInput {
+                offset: encoded.len(),
+                needed: (expected - found) as usize,
+            },
+            _ => KeyDecodingError::InvalidField { offset: 0 },
+        })?;
+        let mut reader = Reader::new(encoded, offset);
+        let mut public_keys = Vec::new();
+        for _ in 0..tree.num_items() {
+            public_keys.push(lamport::PublicKey::from_raw_bytes(&reader.array()?));
+        }
+        reader.finish()?;
+        Ok(PublicTree { public_keys, tree })
+    }
+}
+
+impl<H: HashFunction> PrivateKey<H> {
+    /// Exports the public part of the key for a proof server.
+    pub fn public_tree(&self) -> PublicTree<H> {
+        PublicTree {
+            public_keys: self.0.iter().map(|key| key.public_key()).collect(),
+            tree: self.1.clone(),
+        }
+    }
+
+    /// Signs with the next unused Lamport key like [`PrivateKey::sign`],
+    /// but without the proof, for a [`PublicTree`] to attach. Returns the
+    /// index used along with the bare signature, or [`None`] once every
+    /// key has been used.
+    pub fn sign_leaf<A: AsRef<[u8]>>(
+        &mut self,
+        message: A,
+    ) -> Option<(u64, lamport::Signature<Lamport256, H>)> {
+        let index = self.2;
+        let lamport_signature = self.0.get(index)?.sign(message);
+        self.advance_to(index + 1);
+        Some((index as u64, lamport_signature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::seeded;
+
+    #[test]
+    fn assembles_signatures() {
+        let mut private_key = PrivateKey::generate(5).unwrap();
+        let public_key = private_key.public_key();
+        let public_tree = private_key.public_tree();
+        assert_eq!(public_tree.public_key(), public_key);
+
+        for _ in 0..5 {
+            let (index, lamport_signature) = private_key.sign_leaf(b"message").unwrap();
+            let signature = public_tree
+                .assemble_signature(index, lamport_signature)
+                .unwrap();
+            assert_eq!(signature.index(), index);
+            assert!(public_key.verify(b"message", &signature));
+        }
+        assert!(private_key.sign_leaf(b"message").is_none());
+
+        let lamport_signature = private_key.inner_keys()[0].sign(b"message");
+        assert_eq!(
+            public_tree.assemble_signature(5, lamport_signature.clone()),
+            Err(TreeError::IndexOutOfRange {
+                index: 5,
+                num_items: 5
+            })
+        );
+        // a signature attached to the wrong leaf does not verify
+        let signature = public_tree
+            .assemble_signature(1, lamport_signature)
+            .unwrap();
+        assert!(!public_key.verify(b"message", &signature));
+    }
+
+    #[test]
+    fn assembles_seeded_signatures() {
+        let seed = [3u8; 32];
+        let private_key = seeded::PrivateKey::from_seed(seed, 6).unwrap();
+        let public_tree = private_key.public_tree();
+        assert_eq!(public_tree.public_key(), private_key.public_key());
+
+        // an offline signer holding only the seed
+        let lamport_signature = lamport::PrivateKey::derive(&seed, 4).sign(b"message");
+        let signature = public_tree
+            .assemble_signature(4, lamport_signature)
+            .unwrap();
+        assert!(private_key.public_key().verify(b"message", &signature));
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut private_key = PrivateKey::generate(5).unwrap();
+        let public_key = private_key.public_key();
+        let encoded = private_key.public_tree().to_bytes();
+        assert_eq!(&encoded[..4], b"B3LT");
+        let decoded = PublicTree::<Blake3>::try_from(encoded.as_slice()).unwrap();
+        assert_eq!(decoded.public_key(), public_key);
+        assert_eq!(decoded.num_items(), 5);
+        assert_eq!(decoded.to_bytes(), encoded);
+        let (index, lamport_signature) = private_key.sign_leaf(b"message").unwrap();
+        let signature = decoded
+            .assemble_signature(index, lamport_signature)
+            .unwrap();
+        assert!(public_key.verify(b"message", &signature));
+
+        assert!(matches!(
+            PublicTree::<Blake3>::try_from(&encoded[..encoded.len() - 16384]),
+            Err(KeyDecodingError::NotEnoughInput { .. })
+        ));
+        assert!(matches!(
+            PublicTree::<Blake3>::try_from(&encoded[..100]),
+            Err(KeyDecodingError::NotEnoughInput { .. })
+        ));
+        let mut tampered = encoded.clone();
+        tampered[0] ^= 1;
+        assert!(matches!(
+            PublicTree::<Blake3>::try_from(tampered.as_slice()),
+            Err(KeyDecodingError::InvalidField { offset: 0 })
+        ));
+    }
+
+    #[test]
+    fn detects_corruption_when_assembling() {
+        // levels of 5, 3 and 2 nodes follow the 45-byte header
+        let private_key = PrivateKey::generate(5).unwrap();
+        let encoded = private_key.public_tree().to_bytes();
+        let lamport_signature = || private_key.inner_keys()[0].sign(b"message");
+
+        // the nodes just below the root are checked when decoding
+        let mut tampered = encoded.clone();
+        tampered[45 + 32 * 8] ^= 1;
+        assert!(matches!(
+            PublicTree::<Blake3>::try_from(tampered.as_slice()),
+            Err(KeyDecodingError::InvalidField { offset: 0 })
+        ));
+
+        // the others only when a proof passes through them
+        let mut tampered = encoded.clone();
+        tampered[45 + 32 * 6] ^= 1;
+        let decoded = PublicTree::<Blake3>::try_from(tampered.as_slice()).unwrap();
+        assert_eq!(
+            decoded.assemble_signature(0, lamport_signature()),
+            Err(TreeError::Corrupted { index: 0 })
+        );
+
+        let mut tampered = encoded.clone();
+        let keys_offset = encoded.len() - 5 * 16384;
+        tampered[keys_offset + 7] ^= 1;
+        let decoded = PublicTree::<Blake3>::try_from(tampered.as_slice()).unwrap();
+        assert_eq!(
+            decoded.assemble_signature(0, lamport_signature()),
+            Err(TreeError::LeafMismatch { index: 0 })
+        );
+    }
+}
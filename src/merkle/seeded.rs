@@ -0,0 +1,758 @@
+use blake3::Hash;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+use crate::hash::Blake3;
+use crate::lamport;
+use crate::merkle::internal::{hash_two_hashes, Commitment, Proof, ProofNode, Reader, TreeError};
+use crate::merkle::{KeyGenerationError, PublicKey, PublicTree, Signature};
+use crate::state::{self, Reservation, SigningError, StateStore};
+
+/// The Lamport key pair at `index`, along with the Merkle leaf committing
+/// to its public key.
+fn leaf(seed: &[u8; 32], index: u64) -> (lamport::PrivateKey, lamport::PublicKey, Hash) {
+    let private_key = lamport::PrivateKey::derive(seed, index);
+    let public_key = private_key.public_key();
+    let leaf = blake3::hash(&public_key.to_raw_bytes());
+    (private_key, public_key, leaf)
+}
+
+/// How the nodes of a tree are computed, so that a [`Traversal`] can walk
+/// any tree whose leaves are regenerated on demand.
+pub(crate) trait Leaves {
+    /// The leaf at `index`.
+    fn leaf(&self, index: u64) -> Hash;
+
+    /// The node at `level` and `index` whose children are `left` and `right`.
+    fn parent(&self, level: usize, index: u64, left: &Hash, right: &Hash) -> Hash;
+}
+
+/// The leaves of a [`PrivateKey`]: Lamport public keys derived from a seed.
+struct LamportLeaves<'a>(&'a [u8; 32]);
+
+impl Leaves for LamportLeaves<'_> {
+    fn leaf(&self, index: u64) -> Hash {
+        leaf(self.0, index).2
+    }
+
+    fn parent(&self, _level: usize, _index: u64, left: &Hash, right: &Hash) -> Hash {
+        hash_two_hashes::<Blake3>(left, right)
+    }
+}
+
+/// The number of levels below the root of a tree over `num_items` leaves.
+fn height(num_items: u64) -> usize {
+    (u64::BITS - (num_items - 1).leading_zeros()) as usize
+}
+
+/// The number of nodes at `level` of a tree over `num_items` leaves.
+fn width(num_items: u64, level: usize) -> u64 {
+    (num_items - 1) / (1 << level) + 1
+}
+
+/// Computes the node at `level` and `index` of the tree over `num_items`
+/// leaves, recording the nodes at positions 1 and 3 of each level on the
+/// way, which are the first two right-hand siblings a signer will need.
+fn compute_node(
+    leaves: &impl Leaves,
+    num_items: u64,
+    level: usize,
+    index: u64,
+    first: &mut [Option<Hash>],
+    second: &mut [Option<Hash>],
+) -> Hash {
+    let node = if level == 0 {
+        leaves.leaf(index)
+    } else {
+        let left = compute_node(leaves, num_items, level - 1, index * 2, first, second);
+        if index * 2 + 1 < width(num_items, level - 1) {
+            let right = compute_node(leaves, num_items, level - 1, index * 2 + 1, first, second);
+            leaves.parent(level, index, &left, &right)
+        } else {
+            left
+        }
+    };
+    if level < first.len() {
+        match index {
+            1 => first[level] = Some(node),
+            3 => second[level] = Some(node),
+            _ => {}
+        }
+    }
+    node
+}
+
+/// An incremental computation of one node of the tree, one leaf at a time,
+/// keeping only the roots of the completed subtrees along the way.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TreeHash {
+    next_leaf: u64,
+    end_leaf: u64,
+    stack: Vec<(usize, Hash)>,
+    node: Option<Hash>,
+}
+
+impl TreeHash {
+    /// Starts computing the node at `level` and `index`, or returns [`None`]
+    /// if the tree is too narrow to have one.
+    fn start(num_items: u64, level: usize, index: u64) -> Option<TreeHash> {
+        if index >= width(num_items, level) {
+            return None;
+        }
+        Some(TreeHash {
+            next_leaf: index << level,
+            end_leaf: ((index + 1) << level).min(num_items),
+            stack: Vec::new(),
+            node: None,
+        })
+    }
+
+    fn finished(node: Hash) -> TreeHash {
+        TreeHash {
+            next_leaf: 0,
+            end_leaf: 0,
+            stack: Vec::new(),
+            node: Some(node),
+        }
+    }
+
+    /// The height of the lowest subtree root held, which is what the
+    /// scheduler minimises, or [`None`] once the node is complete.
+    fn low(&self, level: usize) -> Option<usize> {
+        match self.node {
+            Some(_) => None,
+            None => Some(self.stack.last().map_or(level, |(height, _)| *height)),
+        }
+    }
+
+    /// Folds one more leaf into the computation.
+    fn update(&mut self, leaves: &impl Leaves) {
+        if self.node.is_some() {
+            return;
+        }
+        let last = self.next_leaf;
+        let mut top = (0, leaves.leaf(last));
+        self.next_leaf += 1;
+        while let Some(&(height, left)) = self.stack.last() {
+            if height != top.0 {
+                break;
+            }
+            self.stack.pop();
+            top = (
+                height + 1,
+                leaves.parent(height + 1, last >> (height + 1), &left, &top.1),
+            );
+        }
+        self.stack.push(top);
+
+        if self.next_leaf == self.end_leaf {
+            // the node may sit at the right edge of the tree, where unpaired
+            // nodes were carried up, so fold whatever subtrees remain
+            let (_, mut node) = self.stack.pop().expect("a leaf was just pushed");
+            while let Some((height, left)) = self.stack.pop() {
+                node = leaves.parent(height + 1, last >> (height + 1), &left, &node);
+            }
+            self.node = Some(node);
+        }
+    }
+
+    fn complete(mut self, leaves: &impl Leaves) -> Hash {
+        while self.node.is_none() {
+            self.update(leaves);
+        }
+        self.node.expect("the loop ran until the node was complete")
+    }
+}
+
+/// The authentication path of the next leaf of a tree, maintained with the
+/// treehash-based traversal of Szydlo and of Buchmann, Dahmen and Schneider
+/// while holding only `O(log n)` nodes.
+///
+/// Left-hand siblings are computed from the path itself, while every level
+/// keeps a treehash instance computing its next right-hand sibling, with
+/// instances updated lowest subtree first, one leaf per level for each
+/// signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Traversal {
+    num_items: u64,
+    next_index: u64,
+    root: Hash,
+    auth: Vec<Option<Hash>>,
+    treehash: Vec<Option<TreeHash>>,
+}
+
+impl Traversal {
+    /// Computes the root of the tree over the first `n` of `leaves` and the
+    /// authentication path of its first leaf. This visits every leaf once,
+    /// but only holds `O(log n)` nodes at a time.
+    pub(crate) fn new(leaves: &impl Leaves, n: u64) -> Result<Traversal, TreeError> {
+        if n == 0 {
+            return Err(TreeError::EmptyInput);
+        }
+        let levels = height(n);
+        let mut auth = vec![None; levels];
+        let mut upcoming = vec![None; levels];
+        let root = compute_node(leaves, n, levels, 0, &mut auth, &mut upcoming);
+        let treehash = upcoming
+            .into_iter()
+            .map(|node| node.map(TreeHash::finished))
+            .collect();
+        Ok(Traversal {
+            num_items: n,
+            next_index: 0,
+            root,
+            auth,
+            treehash,
+        })
+    }
+
+    pub(crate) fn root(&self) -> Hash {
+        self.root
+    }
+
+    pub(crate) fn num_items(&self) -> u64 {
+        self.num_items
+    }
+
+    /// The index of the next unused leaf.
+    pub(crate) fn next_index(&self) -> u64 {
+        self.next_index
+    }
+
+    /// The siblings of the next leaf and its ancestors, bottom first, with
+    /// [`None`] where a node has no sibling.
+    pub(crate) fn auth_path(&self) -> &[Option<Hash>] {
+        &self.auth
+    }
+
+    /// The number of tree nodes currently held.
+    pub(crate) fn stored_nodes(&self) -> usize {
+        self.auth.iter().flatten().count()
+            + self
+                .treehash
+                .iter()
+                .flatten()
+                .map(|treehash| treehash.stack.len() + treehash.node.iter().count())
+                .sum::<usize>()
+    }
+
+    /// Marks the next leaf, whose value is `leaf`, as used, and moves the
+    /// authentication path on to the leaf after it.
+    pub(crate) fn advance(&mut self, leaves: &impl Leaves, leaf: Hash) {
+        let index = self.next_index;
+        self.next_index += 1;
+        if self.next_index >= self.num_items {
+            return;
+        }
+
+        let next = index + 1;
+        // the root of the completed subtree ending at `index`, one level at
+        // a time
+        let mut node = leaf;
+        for level in 0..self.auth.len() {
+            if !next.is_multiple_of(1 << level) {
+                break;
+            }
+            let ancestor = next >> level;
+            let previous = self.auth[level];
+            if ancestor % 2 == 1 {
+                self.auth[level] = Some(node);
+            } else {
+                self.auth[level] = self.treehash[level]
+                    .take()
+                    .map(|treehash| treehash.complete(leaves));
+                self.treehash[level] = TreeHash::start(self.num_items, level, ancestor + 3);
+            }
+            if let (0, Some(left)) = (ancestor % 2, previous) {
+                node = leaves.parent(level + 1, (ancestor - 1) >> 1, &left, &node);
+            }
+        }
+
+        for _ in 0..self.auth.len() {
+            let lowest = self
+                .treehash
+                .iter()
+                .enumerate()
+                .filter_map(|(level, treehash)| Some((treehash.as_ref()?.low(level)?, level)))
+                .min();
+            match lowest {
+                Some((_, level)) => {
+                    if let Some(treehash) = &mut self.treehash[level] {
+                        treehash.update(leaves);
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Appends the whole traversal state to `output`.
+    pub(crate) fn encode(&self, output: &mut Vec<u8>) {
+        fn push_hash(output: &mut Vec<u8>, hash: &Option<Hash>) {
+            match hash {
+                None => output.push(0),
+                Some(hash) => {
+                    output.push(1);
+                    output.extend_from_slice(hash.as_bytes());
+                }
+            }
+        }
+
+        output.extend(self.num_items.to_be_bytes());
+        output.extend(self.next_index.to_be_bytes());
+        output.extend_from_slice(self.root.as_bytes());
+        for sibling in self.auth.iter() {
+            push_hash(output, sibling);
+        }
+        for treehash in self.treehash.iter() {
+            match treehash {
+                None => output.push(0),
+                Some(treehash) => {
+                    output.push(1);
+                    output.extend(treehash.next_leaf.to_be_bytes());
+                    output.extend(treehash.end_leaf.to_be_bytes());
+                    push_hash(output, &treehash.node);
+                    output.push(treehash.stack.len() as u8);
+                    for (height, hash) in treehash.stack.iter() {
+                        output.push(*height as u8);
+                        output.extend_from_slice(hash.as_bytes());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decodes a traversal state from the current position of `reader`.
+    pub(crate) fn decode(reader: &mut Reader) -> Result<Traversal, KeyDecodingError> {
+        let offset = reader.offset();
+        let num_items = read_u64(reader)?;
+        if num_items == 0 {
+            return Err(KeyDecodingError::InvalidField { offset });
+        }
+        let offset = reader.offset();
+        let next_index = read_u64(reader)?;
+        if next_index > num_items {
+            return Err(KeyDecodingError::InvalidField { offset });
+        }
+        let root = Hash::from(reader.array::<32>()?);
+
+        // while leaves remain, the path of the next leaf holds every sibling
+        // the tree is wide enough for, and each level computes the right
+        // sibling after the current one if there is one, so that no later
+        // path lacks a node
+        let exhausted = next_index == num_items;
+        let levels = height(num_items);
+        let mut auth = Vec::with_capacity(levels);
+        for level in 0..levels {
+            let offset = reader.offset();
+            let sibling = read_optional_hash(reader)?;
+            let exists = (next_index >> level) ^ 1 < width(num_items, level);
+            if !exhausted && sibling.is_some() != exists {
+                return Err(KeyDecodingError::InvalidField { offset });
+            }
+            auth.push(sibling);
+        }
+
+        let mut treehash = Vec::with_capacity(levels);
+        for level in 0..levels {
+            let offset = reader.offset();
+            let upcoming = TreeHash::start(
+                num_items,
+                level,
+                ((next_index >> level) | 1).saturating_add(2),
+            );
+            match reader.array::<1>()?[0] {
+                0 if exhausted || upcoming.is_none() => treehash.push(None),
+                1 if exhausted || upcoming.is_some() => {
+                    let next_leaf = read_u64(reader)?;
+                    let end_leaf = read_u64(reader)?;
+                    let node = read_optional_hash(reader)?;
+                    let offset = reader.offset();
+                    let depth = reader.array::<1>()?[0] as usize;
+                    let in_range = match (&node, &upcoming) {
+                        (Some(_), _) | (None, None) => next_leaf <= end_leaf,
+                        (None, Some(upcoming)) => {
+                            next_leaf >= upcoming.next_leaf
+                                && next_leaf < end_leaf
+                                && end_leaf == upcoming.end_leaf
+                        }
+                    };
+                    if depth > level || !in_range || end_leaf > num_items {
+                        return Err(KeyDecodingError::InvalidField { offset });
+                    }
+                    let mut stack = Vec::with_capacity(depth);
+                    for _ in 0..depth {
+                        let offset = reader.offset();
+                        let height = reader.array::<1>()?[0] as usize;
+                        if height >= level {
+                            return Err(KeyDecodingError::InvalidField { offset });
+                        }
+                        stack.push((height, Hash::from(reader.array::<32>()?)));
+                    }
+                    treehash.push(Some(TreeHash {
+                        next_leaf,
+                        end_leaf,
+                        stack,
+                        node,
+                    }));
+                }
+                _ => return Err(KeyDecodingError::InvalidField { offset }),
+            }
+        }
+
+        Ok(Traversal {
+            num_items,
+            next_index,
+            root,
+            auth,
+            treehash,
+        })
+    }
+}
+
+/// A Merkle private key which stores only a secret seed, the index of the
+/// next leaf and `O(log n)` tree nodes, regenerating Lamport keys from the
+/// seed as it needs them.
+///
+/// It produces exactly the [`Signature`]s of a [`crate::merkle::PrivateKey`]
+/// built over the same Lamport keys, so its [`PublicKey`] verifies them as
+/// usual. The authentication path is kept by a [`Traversal`].
+///
+/// Unlike a [`crate::merkle::PrivateKey`], it cannot erase the secrets of
+/// used leaves, since the seed regenerates every one of them. A leaked key
+/// therefore forges signatures with any leaf, used or not: seeded keys have
+/// no forward security. The seed is wiped when the key is dropped.
+#[derive(PartialEq, Eq)]
+pub struct PrivateKey {
+    seed: [u8; 32],
+    traversal: Traversal,
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("next_index", &self.current_index())
+            .field("num_items", &self.num_items())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl PrivateKey {
+    /// Generates a key able to sign `n` messages from a fresh random seed,
+    /// failing if `n` is zero.
+    pub fn generate(n: u64) -> Result<PrivateKey, KeyGenerationError> {
+        let mut seed = [0u8; 32];
+        OsRng.try_fill_bytes(&mut seed)?;
+        Ok(PrivateKey::from_seed(seed, n)?)
+    }
+
+    /// Builds the key able to sign `n` messages with the Lamport keys
+    /// derived from `seed`. This visits every leaf once, but only holds
+    /// `O(log n)` nodes at a time.
+    pub fn from_seed(seed: [u8; 32], n: u64) -> Result<PrivateKey, TreeError> {
+        let traversal = Traversal::new(&LamportLeaves(&seed), n)?;
+        Ok(PrivateKey { seed, traversal })
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::new(Commitment {
+            root: self.traversal.root(),
+            num_items: self.traversal.num_items(),
+        })
+    }
+
+    /// The index of the leaf the next signature will use.
+    pub fn current_index(&self) -> u64 {
+        self.traversal.next_index()
+    }
+
+    pub fn num_items(&self) -> u64 {
+        self.traversal.num_items()
+    }
+
+    /// The number of tree nodes currently held, which never exceeds a
+    /// small multiple of the height of the tree.
+    pub fn stored_nodes(&self) -> usize {
+        self.traversal.stored_nodes()
+    }
+
+    /// Signs with the next unused leaf, returning [`None`] once every leaf
+    /// has been used.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<Signature> {
+        let index = self.traversal.next_index();
+        if index >= self.traversal.num_items() {
+            return None;
+        }
+
+        let (lamport_private_key, lamport_public_key, leaf) = leaf(&self.seed, index);
+        let frontier = self
+            .traversal
+            .auth_path()
+            .iter()
+            .enumerate()
+            .map(|(level, sibling)| match (index >> level) % 2 {
+                1 => ProofNode::RightChildWithSibling(sibling.expect("left siblings exist")),
+                _ => match sibling {
+                    Some(sibling) => ProofNode::LeftChildWithSibling(*sibling),
+                    None => ProofNode::NodeWithoutSibling,
+                },
+            })
+            .collect();
+        let proof = Proof::new(lamport_public_key.to_raw_bytes().to_vec(), index, frontier);
+        let lamport_signature = lamport_private_key.sign(message);
+
+        self.traversal.advance(&LamportLeaves(&self.seed), leaf);
+        Some(Signature(lamport_signature, lamport_public_key, proof))
+    }
+
+    /// Signs like [`PrivateKey::sign`], but only after durably recording in
+    /// `store` that the index is used. If `store` is ahead of this key, the
+    /// traversal is first advanced past the indices in between.
+    pub fn sign_persisted<S: StateStore, A: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        message: A,
+    ) -> Result<Signature, SigningError<S::Error>> {
+        let mut reservation = self.reserve(store, 1)?;
+        Ok(self
+            .sign_reserved(&mut reservation, message)
+            .expect("reserved indices are in range"))
+    }
+
+    /// Durably reserves the next `count` indices in `store` with a single
+    /// write, like [`super::PrivateKey::reserve`].
+    pub fn reserve<S: StateStore>(
+        &mut self,
+        store: &mut S,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        state::reserve(store, self.current_index(), self.num_items(), count)
+    }
+
+    /// Signs with the next index of `reservation`, which must have been
+    /// made for this key, first advancing the traversal past any indices
+    /// skipped to reach it. Returns [`None`] once the block is used up.
+    pub fn sign_reserved<A: AsRef<[u8]>>(
+        &mut self,
+        reservation: &mut Reservation,
+        message: A,
+    ) -> Option<Signature> {
+        let index = reservation.take_from(self.current_index())?;
+        self.skip_to(index);
+        self.sign(message)
+    }
+
+    /// Advances the traversal past every leaf before `index` without
+    /// signing with them.
+    pub(crate) fn skip_to(&mut self, index: u64) {
+        while self.traversal.next_index() < index.min(self.traversal.num_items()) {
+            let (_, _, node) = leaf(&self.seed, self.traversal.next_index());
+            self.traversal.advance(&LamportLeaves(&self.seed), node);
+        }
+    }
+
+    /// Exports the public part of the key for a proof server, deriving the
+    /// Lamport public key of every leaf.
+    pub fn public_tree(&self) -> PublicTree {
+        let public_keys = (0..self.num_items())
+            .map(|index| lamport::PrivateKey::derive(&self.seed, index).public_key())
+            .collect();
+        PublicTree::new(public_keys).expect("keys have at least one leaf")
+    }
+
+    /// Encodes the whole signing state, including the secret seed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(&self.seed);
+        self.traversal.encode(&mut output);
+        output
+    }
+}
+
+/// An error encountered while decoding a [`PrivateKey`]. Every offset is the
+/// position, in bytes, within the encoded key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyDecodingError {
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// The field at `offset` holds a value no signer could have written.
+    InvalidField { offset: usize },
+    /// The key ended at `offset`, but `remaining` bytes followed it.
+    TrailingBytes { offset: usize, remaining: usize },
+}
+
+impl std::fmt::Display for KeyDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyDecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "key truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            KeyDecodingError::InvalidField { offset } => {
+                write!(f, "invalid key field at offset {offset}")
+            }
+            KeyDecodingError::TrailingBytes { offset, remaining } => write!(
+                f,
+                "{remaining} trailing bytes after the key ending at offset {offset}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeyDecodingError {}
+
+impl From<crate::merkle::ProofDecodingError> for KeyDecodingError {
+    fn from(e: crate::merkle::ProofDecodingError) -> Self {
+        use crate::merkle::ProofDecodingError;
+        match e {
+            ProofDecodingError::NotEnoughInput { offset, needed } => {
+                KeyDecodingError::NotEnoughInput { offset, needed }
+            }
+            ProofDecodingError::TrailingBytes { offset, remaining } => {
+                KeyDecodingError::TrailingBytes { offset, remaining }
+            }
+            ProofDecodingError::InvalidProofNodeType { offset, .. }
+            | ProofDecodingError::ItemTooLong { offset, .. }
+            | ProofDecodingError::TooDeep { offset, .. } => {
+                KeyDecodingError::InvalidField { offset }
+            }
+        }
+    }
+}
+
+fn read_u64(reader: &mut Reader) -> Result<u64, KeyDecodingError> {
+    Ok(u64::from_be_bytes(reader.array()?))
+}
+
+fn read_optional_hash(reader: &mut Reader) -> Result<Option<Hash>, KeyDecodingError> {
+    let offset = reader.offset();
+    match reader.array::<1>()?[0] {
+        0 => Ok(None),
+        1 => Ok(Some(Hash::from(reader.array::<32>()?))),
+        _ => Err(KeyDecodingError::InvalidField { offset }),
+    }
+}
+
+impl TryFrom<&[u8]> for PrivateKey {
+    type Error = KeyDecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let seed = reader.array::<32>()?;
+        let traversal = Traversal::decode(&mut reader)?;
+        reader.finish()?;
+        Ok(PrivateKey { seed, traversal })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_full_private_key() {
+        let seed = [7u8; 32];
+        for n in (1..=40).chain([63, 64, 65, 100]) {
+            let keys = (0..n)
+                .map(|i| lamport::PrivateKey::derive(&seed, i))
+                .collect();
+            let mut full = crate::merkle::PrivateKey::try_from((keys, 0)).unwrap();
+            let mut seeded = PrivateKey::from_seed(seed, n).unwrap();
+            assert_eq!(
+                <[u8; 40]>::from(full.public_key()),
+                <[u8; 40]>::from(seeded.public_key())
+            );
+
+            let public_key = seeded.public_key();
+            for i in 0..n {
+                let message = i.to_be_bytes();
+                let signature = seeded.sign(message).unwrap();
+                assert!(public_key.verify(message, &signature));
+                assert_eq!(full.sign(message), Some(signature));
+            }
+            assert!(seeded.sign(b"one too many").is_none());
+        }
+    }
+
+    #[test]
+    fn storage_stays_logarithmic() {
+        let n = 1 << 10;
+        let mut private_key = PrivateKey::from_seed([1u8; 32], n).unwrap();
+        let public_key = private_key.public_key();
+        let levels = height(n);
+        for i in 0..n {
+            assert!(private_key.stored_nodes() <= 3 * levels);
+            let signature = private_key.sign(i.to_be_bytes()).unwrap();
+            if i % 97 == 0 {
+                assert!(public_key.verify(i.to_be_bytes(), &signature));
+            }
+        }
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let mut private_key = PrivateKey::from_seed([3u8; 32], 37).unwrap();
+        for _ in 0..11 {
+            private_key.sign(b"advance");
+        }
+        let encoded = private_key.to_bytes();
+        let mut decoded = PrivateKey::try_from(encoded.as_slice()).unwrap();
+        assert_eq!(decoded, private_key);
+        assert_eq!(decoded.sign(b"next"), private_key.sign(b"next"));
+
+        assert!(matches!(
+            PrivateKey::try_from(&encoded[..encoded.len() - 1]),
+            Err(KeyDecodingError::NotEnoughInput { needed: 1, .. })
+        ));
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert_eq!(
+            PrivateKey::try_from(trailing.as_slice()),
+            Err(KeyDecodingError::TrailingBytes {
+                offset: encoded.len(),
+                remaining: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unreachable_states() {
+        let n = 37;
+        let mut private_key = PrivateKey::from_seed([3u8; 32], n).unwrap();
+        for i in 0..=n {
+            let encoded = private_key.to_bytes();
+            assert_eq!(
+                PrivateKey::try_from(encoded.as_slice()).as_ref(),
+                Ok(&private_key)
+            );
+            if i == 11 {
+                // leaf 11 needs its left sibling, so a path without it
+                // could never sign
+                let offset = 32 + 8 + 8 + 32;
+                let mut missing = encoded[..offset].to_vec();
+                missing.push(0);
+                missing.extend_from_slice(&encoded[offset + 33..]);
+                assert_eq!(
+                    PrivateKey::try_from(missing.as_slice()),
+                    Err(KeyDecodingError::InvalidField { offset })
+                );
+            }
+            private_key.sign(b"advance");
+        }
+    }
+
+    #[test]
+    fn rejects_empty_trees() {
+        assert_eq!(
+            PrivateKey::from_seed([0u8; 32], 0),
+            Err(TreeError::EmptyInput)
+        );
+    }
+}
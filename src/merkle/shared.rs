@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::hash::{Blake3, HashFunction};
+use crate::merkle::{PrivateKey, PublicKey, Signature};
+
+/// A [`PrivateKey`] which many threads can sign with at once through a
+/// shared reference.
+///
+/// Each signature claims its index with a single atomic update, so no index
+/// is ever handed out twice, and then signs and builds its proof without
+/// holding any lock.
+///
+/// Signing through a shared reference cannot erase the secrets of used
+/// Lamport keys as [`PrivateKey::sign`] does. They are erased when the key
+/// is taken back with [`SharedSigner::into_private_key`], or dropped.
+pub struct SharedSigner<H: HashFunction = Blake3> {
+    private_key: PrivateKey<H>,
+    next: AtomicUsize,
+}
+
+impl<H: HashFunction> SharedSigner<H> {
+    /// Shares `private_key`, continuing from its current index.
+    pub fn new(private_key: PrivateKey<H>) -> SharedSigner<H> {
+        let next = AtomicUsize::new(private_key.2);
+        SharedSigner { private_key, next }
+    }
+
+    /// The next index to be claimed, which every signature already
+    /// returned is below.
+    pub fn current_index(&self) -> usize {
+        self.next.load(Ordering::SeqCst)
+    }
+
+    pub fn public_key(&self) -> PublicKey<H> {
+        self.private_key.public_key()
+    }
+
+    /// Signs with the next unclaimed Lamport key, returning [`None`] once
+    /// every key has been claimed.
+    pub fn sign<A: AsRef<[u8]>>(&self, message: A) -> Option<Signature<H>> {
+        let num_items = self.private_key.0.len();
+        let index = self
+            .next
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |next| {
+                (next < num_items).then_some(next + 1)
+            })
+            .ok()?;
+
+        let lamport_private_key = &self.private_key.0[index];
+        let lamport_public_key = lamport_private_key.public_key();
+        let proof = self
+            .private_key
+            .1
+            .prove(lamport_public_key.to_raw_bytes().to_vec(), index as u64)
+            .ok()?;
+        let lamport_signature = lamport_private_key.sign(message);
+        Some(Signature(lamport_signature, lamport_public_key, proof))
+    }
+
+    /// Stops sharing the key, which continues after every index claimed.
+    pub fn into_private_key(self) -> PrivateKey<H> {
+        let mut private_key = self.private_key;
+        private_key.advance_to(self.next.into_inner());
+        private_key
+    }
+}
+
+impl<H: HashFunction> From<PrivateKey<H>> for SharedSigner<H> {
+    fn from(private_key: PrivateKey<H>) -> Self {
+        SharedSigner::new(private_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn is_send_and_sync() {
+        assert_send_sync::<SharedSigner>();
+        #[cfg(feature = "sha2")]
+        assert_send_sync::<SharedSigner<crate::hash::Sha256>>();
+    }
+
+    #[test]
+    fn never_hands_out_an_index_twice() {
+        let signer = SharedSigner::new(PrivateKey::generate(200).unwrap());
+        let public_key = signer.public_key();
+        let signatures: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8u8)
+                .map(|thread| {
+                    let signer = &signer;
+                    scope.spawn(move || {
+                        (0..40)
+                            .filter_map(|_| {
+                                signer.sign([thread]).map(|signature| (thread, signature))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        assert_eq!(signatures.len(), 200);
+        let indices: HashSet<u64> = signatures
+            .iter()
+            .map(|(_, signature)| signature.index())
+            .collect();
+        assert_eq!(indices, (0..200).collect());
+        for (thread, signature) in &signatures {
+            assert!(public_key.verify([*thread], signature));
+        }
+        assert_eq!(signer.current_index(), 200);
+        assert!(signer.sign(b"message").is_none());
+    }
+
+    #[test]
+    fn continues_from_the_private_key() {
+        let mut private_key = PrivateKey::generate(3).unwrap();
+        private_key.sign(b"first").unwrap();
+        let signer = SharedSigner::from(private_key);
+        assert_eq!(signer.sign(b"second").unwrap().index(), 1);
+
+        let mut private_key = signer.into_private_key();
+        assert_eq!(private_key.current_index(), 2);
+        assert_eq!(private_key.sign(b"third").unwrap().index(), 2);
+    }
+}
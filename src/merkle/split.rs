@@ -0,0 +1,350 @@
+use std::ops::Range;
+
+use crate::hash::{Blake3, HashFunction};
+use crate::lamport::{self, Lamport256};
+use crate::merkle::internal::{DecodingLimits, Proof, Reader};
+use crate::merkle::seeded::KeyDecodingError;
+use crate::merkle::{PrivateKey, PublicKey, Signature};
+use crate::state::{self, SigningError, StateStore};
+
+/// The part of a [`PrivateKey`] able to sign with one range of its indices,
+/// for a host sharing the key's [`PublicKey`] with others.
+///
+/// It holds the Lamport secrets of its own range and the authentication
+/// path of each of their leaves, but nothing from the rest of the tree, so
+/// it cannot sign with any index outside its range. Like the Lamport keys
+/// it holds, it cannot be cloned, and its [`Debug`](std::fmt::Debug)
+/// output leaves them out.
+pub struct SubKey<H: HashFunction = Blake3> {
+    public_key: PublicKey<H>,
+    start: u64,
+    next: u64,
+    private_keys: Vec<lamport::PrivateKey<Lamport256, H>>,
+    /// The proof of each leaf in the range, without its item, which is
+    /// recomputed from the Lamport key when signing.
+    proofs: Vec<Proof<H>>,
+}
+
+impl<H: HashFunction> PrivateKey<H> {
+    /// Splits the key into one [`SubKey`] for each of `ranges`, which must
+    /// not overlap or include indices this key has already used. The key is
+    /// consumed, so that none of the indices handed out can be used through
+    /// it again.
+    pub fn split(self, ranges: &[Range<u64>]) -> Result<Vec<SubKey<H>>, SplitError> {
+        let num_items = self.0.len() as u64;
+        for (i, range) in ranges.iter().enumerate() {
+            if range.is_empty() || range.end > num_items {
+                return Err(SplitError::InvalidRange {
+                    range: range.clone(),
+                });
+            }
+            if range.start < self.2 as u64 {
+                return Err(SplitError::AlreadyUsed {
+                    range: range.clone(),
+                });
+            }
+            if let Some(other) = ranges[..i]
+                .iter()
+                .find(|other| other.start < range.end && range.start < other.end)
+            {
+                return Err(SplitError::Overlapping {
+                    first: other.clone(),
+                    second: range.clone(),
+                });
+            }
+        }
+
+        let public_key = self.public_key();
+        let tree = self.1;
+        let mut private_keys: Vec<Option<_>> = self.0.into_iter().map(Some).collect();
+        Ok(ranges
+            .iter()
+            .map(|range| {
+                let private_keys: Vec<_> = range
+                    .clone()
+                    .map(|index| {
+                        private_keys[index as usize]
+                            .take()
+                            .expect("ranges are disjoint")
+                    })
+                    .collect();
+                let proofs = range
+                    .clone()
+                    .zip(&private_keys)
+                    .map(|(index, private_key)| {
+                        tree.prove(private_key.public_key().to_raw_bytes().to_vec(), index)
+                            .expect("the tree commits to every key")
+                            .with_item(Vec::new())
+                    })
+                    .collect();
+                SubKey {
+                    public_key: public_key.clone(),
+                    start: range.start,
+                    next: range.start,
+                    private_keys,
+                    proofs,
+                }
+            })
+            .collect())
+    }
+}
+
+impl<H: HashFunction> std::fmt::Debug for SubKey<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubKey")
+            .field("range", &self.range())
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H: HashFunction> SubKey<H> {
+    /// The public key of the whole key this was split from, which verifies
+    /// its signatures.
+    pub fn public_key(&self) -> PublicKey<H> {
+        self.public_key.clone()
+    }
+
+    /// The indices this sub-key signs with.
+    pub fn range(&self) -> Range<u64> {
+        self.start..self.start + self.private_keys.len() as u64
+    }
+
+    pub fn current_index(&self) -> u64 {
+        self.next
+    }
+
+    /// Signs with the next unused index of the range, returning [`None`]
+    /// once every one has been used.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<Signature<H>> {
+        let offset = (self.next - self.start) as usize;
+        let lamport_private_key = self.private_keys.get(offset)?;
+        let lamport_public_key = lamport_private_key.public_key();
+        let proof = self.proofs[offset]
+            .clone()
+            .with_item(lamport_public_key.to_raw_bytes().to_vec());
+
+        let lamport_signature = lamport_private_key.sign(message);
+        self.advance_to(self.next + 1);
+        Some(Signature(lamport_signature, lamport_public_key, proof))
+    }
+
+    /// Moves the sub-key on to `index`, erasing the secrets of every
+    /// Lamport key it passes.
+    fn advance_to(&mut self, index: u64) {
+        let index = index.max(self.next);
+        let end = index.min(self.range().end);
+        for key in
+            &mut self.private_keys[(self.next - self.start) as usize..(end - self.start) as usize]
+        {
+            key.erase();
+        }
+        self.next = index;
+    }
+
+    /// Signs like [`SubKey::sign`], but only after durably recording in
+    /// `store` that the index is used. Each sub-key needs a store of its
+    /// own, since the stored index is only ever advanced.
+    pub fn sign_persisted<S: StateStore, A: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        message: A,
+    ) -> Result<Signature<H>, SigningError<S::Error>> {
+        let mut reservation = state::reserve(store, self.next, self.range().end, 1)?;
+        let index = reservation
+            .take_from(self.next)
+            .expect("reservations hold an index");
+        self.advance_to(index);
+        Ok(self.sign(message).expect("reserved indices are in range"))
+    }
+
+    /// Encodes the sub-key, including its Lamport secrets.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(&self.public_key.to_raw_bytes());
+        output.extend_from_slice(&self.start.to_be_bytes());
+        output.extend_from_slice(&self.next.to_be_bytes());
+        output.extend_from_slice(&(self.private_keys.len() as u64).to_be_bytes());
+        for (private_key, proof) in self.private_keys.iter().zip(&self.proofs) {
+            output.extend_from_slice(&private_key.encode());
+            output.extend_from_slice(&Vec::from(proof));
+        }
+        output
+    }
+}
+
+impl<H: HashFunction> TryFrom<&[u8]> for SubKey<H> {
+    type Error = KeyDecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let public_key = PublicKey::from(reader.array::<40>()?);
+        let start = u64::from_be_bytes(reader.array()?);
+        let offset = reader.offset();
+        let next = u64::from_be_bytes(reader.array()?);
+        let count_offset = reader.offset();
+        let count = u64::from_be_bytes(reader.array()?);
+        let end = start
+            .checked_add(count)
+            .filter(|&end| count > 0 && end <= public_key.num_items())
+            .ok_or(KeyDecodingError::InvalidField {
+                offset: count_offset,
+            })?;
+        if !(start..=end).contains(&next) {
+            return Err(KeyDecodingError::InvalidField { offset });
+        }
+
+        let key_len = lamport::PrivateKey::<Lamport256, H>::ENCODED_LEN;
+        let limits = DecodingLimits::default();
+        let mut private_keys = Vec::new();
+        let mut proofs = Vec::new();
+        for index in start..end {
+            let offset = reader.offset();
+            let private_key = lamport::PrivateKey::try_from(reader.take(key_len)?)
+                .map_err(|_| KeyDecodingError::InvalidField { offset })?;
+            let offset = reader.offset();
+            let proof = Proof::decode_from(&mut reader, &limits)?;
+            if proof.index() != index || !proof.item().is_empty() {
+                return Err(KeyDecodingError::InvalidField { offset });
+            }
+            private_keys.push(private_key);
+            proofs.push(proof);
+        }
+        reader.finish()?;
+        Ok(SubKey {
+            public_key,
+            start,
+            next,
+            private_keys,
+            proofs,
+        })
+    }
+}
+
+/// An error encountered while splitting a [`PrivateKey`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitError {
+    /// `range` is empty or reaches past the last index of the key.
+    InvalidRange { range: Range<u64> },
+    /// `range` starts below the key's current index, so some of its
+    /// indices may already have signed.
+    AlreadyUsed { range: Range<u64> },
+    /// Two of the ranges share indices.
+    Overlapping {
+        first: Range<u64>,
+        second: Range<u64>,
+    },
+}
+
+impl std::fmt::Display for SplitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitError::InvalidRange { range } => {
+                write!(f, "index range {range:?} is empty or out of range")
+            }
+            SplitError::AlreadyUsed { range } => {
+                write!(f, "index range {range:?} includes indices already used")
+            }
+            SplitError::Overlapping { first, second } => {
+                write!(f, "index ranges {first:?} and {second:?} overlap")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sub_keys_sign_their_own_ranges() {
+        let private_key = PrivateKey::generate(10).unwrap();
+        let public_key = private_key.public_key();
+        let mut sub_keys = private_key.split(&[0..3, 3..4, 4..10]).unwrap();
+        assert_eq!(
+            sub_keys
+                .iter()
+                .map(|sub_key| sub_key.range())
+                .collect::<Vec<_>>(),
+            vec![0..3, 3..4, 4..10]
+        );
+
+        let mut indices = Vec::new();
+        for sub_key in &mut sub_keys {
+            assert_eq!(sub_key.public_key(), public_key);
+            while let Some(signature) = sub_key.sign(b"message") {
+                assert!(public_key.verify(b"message", &signature));
+                indices.push(signature.index());
+            }
+            assert_eq!(sub_key.current_index(), sub_key.range().end);
+        }
+        // together they sign exactly as many messages as the whole key
+        assert_eq!(indices, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rejects_bad_ranges() {
+        let split = |ranges: &[Range<u64>]| {
+            let mut private_key = PrivateKey::generate(6).unwrap();
+            private_key.sign(b"message").unwrap();
+            private_key.split(ranges).map(|_| ())
+        };
+        assert_eq!(split(&[1..3, 3..6]), Ok(()));
+        assert_eq!(
+            split(&[1..3, 4..7]),
+            Err(SplitError::InvalidRange { range: 4..7 })
+        );
+        assert_eq!(
+            split(&[2..2, 3..4]),
+            Err(SplitError::InvalidRange { range: 2..2 })
+        );
+        assert_eq!(
+            split(&[3..4, 0..3]),
+            Err(SplitError::AlreadyUsed { range: 0..3 })
+        );
+        assert_eq!(
+            split(&[1..4, 5..6, 3..5]),
+            Err(SplitError::Overlapping {
+                first: 1..4,
+                second: 3..5
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let private_key = PrivateKey::generate(5).unwrap();
+        let public_key = private_key.public_key();
+        let mut sub_key = private_key.split(&[0..2, 2..5]).unwrap().remove(1);
+        sub_key.sign(b"first").unwrap();
+
+        let encoded = sub_key.to_bytes();
+        let mut decoded = SubKey::<Blake3>::try_from(encoded.as_slice()).unwrap();
+        assert_eq!(decoded.range(), 2..5);
+        assert_eq!(decoded.current_index(), 3);
+        let signature = decoded.sign(b"second").unwrap();
+        assert_eq!(signature.index(), 3);
+        assert!(public_key.verify(b"second", &signature));
+        let mut store = crate::state::MemoryStore::default();
+        let signature = decoded.sign_persisted(&mut store, b"third").unwrap();
+        assert_eq!(signature.index(), 4);
+        assert!(matches!(
+            decoded.sign_persisted(&mut store, b"fourth"),
+            Err(SigningError::Exhausted)
+        ));
+
+        assert!(matches!(
+            SubKey::<Blake3>::try_from(&encoded[..encoded.len() - 1]),
+            Err(KeyDecodingError::NotEnoughInput { .. })
+        ));
+        // a sub-key claiming indices beyond its secrets is rejected
+        let mut forged = encoded.clone();
+        forged[56..64].copy_from_slice(&4u64.to_be_bytes());
+        assert_eq!(
+            SubKey::<Blake3>::try_from(forged.as_slice()).unwrap_err(),
+            KeyDecodingError::InvalidField { offset: 56 }
+        );
+    }
+}
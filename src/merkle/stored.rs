@@ -0,0 +1,262 @@
+use crate::hash::{Blake3, HashFunction};
+use crate::lamport::{self, Lamport256};
+use crate::merkle::internal::{Nodes, Tree, TreeDecodingError, TreeError};
+use crate::merkle::{PrivateKey, PublicKey, Signature};
+use crate::state::{self, Reservation, SigningError, StateStore};
+
+/// The tree of a [`PrivateKey`] as written beside it by
+/// [`PrivateKey::tree_to_bytes`], loaded without recomputing any Lamport
+/// public key or tree node.
+///
+/// Loading only checks the header, the length and the nodes just below the
+/// root, so it takes the same time for any number of keys. Every proof is
+/// checked against the root before it is attached to a signature, so a
+/// corrupted node fails signing instead of producing a signature which
+/// does not verify. Compare [`StoredTree::public_key`] with the published
+/// key to be sure the tree is the right one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredTree<H: HashFunction = Blake3>(Tree<H>);
+
+impl<H: HashFunction> StoredTree<H> {
+    /// Loads a tree from its encoding.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, TreeDecodingError> {
+        Tree::decode(Nodes::Owned(bytes)).map(StoredTree)
+    }
+
+    /// Memory-maps the tree stored in the file at `path`, reading its nodes
+    /// only as proofs need them. Decoding errors are reported as
+    /// [`std::io::ErrorKind::InvalidData`].
+    ///
+    /// The file must only ever be replaced by renaming a new file over it,
+    /// never rewritten in place: a signer reading a mapped file which
+    /// another process truncates is killed by `SIGBUS`.
+    #[cfg(feature = "memmap2")]
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the map is only read. Writers replace tree files by
+        // renaming new ones over them, which leaves the mapped file as it
+        // was, so its length cannot shrink under the map. A node changed in
+        // place by anything else makes every proof through it fail its
+        // check rather than sign
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Tree::decode(Nodes::Mapped(std::sync::Arc::new(map), 0))
+            .map(StoredTree)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn public_key(&self) -> PublicKey<H> {
+        PublicKey::new(self.0.commitment())
+    }
+
+    pub fn num_items(&self) -> u64 {
+        self.0.num_items()
+    }
+
+    /// Durably reserves the next `count` indices in `store`, for signers
+    /// holding the tree and only the Lamport keys they sign with. The store
+    /// alone tracks which indices are used.
+    pub fn reserve<S: StateStore>(
+        &self,
+        store: &mut S,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        self.reserve_from(store, 0, count)
+    }
+
+    /// Reserves like [`StoredTree::reserve`], but never below `current`,
+    /// for keys known to have used the indices before it, such as one
+    /// recorded in a key file from before its store existed. An empty store
+    /// starts from `current`, and a store already past it is unaffected.
+    pub fn reserve_from<S: StateStore>(
+        &self,
+        store: &mut S,
+        current: u64,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        state::reserve(store, current, self.num_items(), count)
+    }
+
+    /// Takes the next index of `reservation` and signs `message` with
+    /// `lamport_private_key`, which must be the key at that index, attaching
+    /// its proof. Load the key at [`Reservation::next_index`] beforehand. The
+    /// index is used up even if signing fails.
+    pub fn sign<A: AsRef<[u8]>>(
+        &self,
+        reservation: &mut Reservation,
+        lamport_private_key: &lamport::PrivateKey<Lamport256, H>,
+        message: A,
+    ) -> Result<Signature<H>, TreeError> {
+        let index = reservation.take().ok_or(TreeError::Unreserved)?;
+        let lamport_public_key = lamport_private_key.public_key();
+        let proof = self
+            .0
+            .prove(lamport_public_key.to_raw_bytes().to_vec(), index)?;
+        let lamport_signature = lamport_private_key.sign(message);
+        Ok(Signature(lamport_signature, lamport_public_key, proof))
+    }
+}
+
+impl<H: HashFunction> PrivateKey<H> {
+    /// Encodes the tree for [`StoredTree`], to be saved beside the Lamport
+    /// keys.
+    pub fn tree_to_bytes(&self) -> Vec<u8> {
+        self.1.encode()
+    }
+
+    /// Assembles a key from its Lamport keys and its stored tree, without
+    /// recomputing either, or returns [`None`] if the tree has a different
+    /// number of leaves. The keys are only checked against the tree as each
+    /// one signs, so those below `current_index` may already be erased.
+    pub fn from_stored_tree(
+        private_keys: Vec<lamport::PrivateKey<Lamport256, H>>,
+        tree: StoredTree<H>,
+        current_index: usize,
+    ) -> Option<Self> {
+        if private_keys.len() as u64 != tree.num_items() {
+            return None;
+        }
+        let mut private_key = PrivateKey(private_keys, tree.0, 0);
+        private_key.advance_to(current_index);
+        Some(private_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MemoryStore;
+
+    #[test]
+    fn loads_without_rebuilding() {
+        let private_key = PrivateKey::generate(7).unwrap();
+        let public_key = private_key.public_key();
+        let encoded = private_key.tree_to_bytes();
+        let tree = StoredTree::<Blake3>::from_bytes(encoded.clone()).unwrap();
+        assert_eq!(tree.public_key(), public_key);
+
+        let mut store = MemoryStore::default();
+        let mut reservation = tree.reserve(&mut store, 2).unwrap();
+        let index = reservation.next_index() as usize;
+        let signature = tree
+            .sign(
+                &mut reservation,
+                &private_key.inner_keys()[index],
+                b"message",
+            )
+            .unwrap();
+        assert!(public_key.verify(b"message", &signature));
+        assert_eq!(
+            tree.sign(&mut reservation, &private_key.inner_keys()[4], b"message"),
+            Err(TreeError::LeafMismatch { index: 1 })
+        );
+        // the mismatch used up the last index
+        assert_eq!(
+            tree.sign(&mut reservation, &private_key.inner_keys()[2], b"message"),
+            Err(TreeError::Unreserved)
+        );
+
+        assert!(PrivateKey::from_stored_tree(Vec::new(), tree.clone(), 0).is_none());
+        let mut restored = PrivateKey::from_stored_tree(
+            private_key
+                .inner_keys()
+                .iter()
+                .map(|key| key.duplicate())
+                .collect(),
+            tree,
+            private_key.current_index(),
+        )
+        .unwrap();
+        assert_eq!(restored.public_key(), public_key);
+        assert!(public_key.verify(b"message", &restored.sign(b"message").unwrap()));
+    }
+
+    #[test]
+    fn reservations_start_from_known_indices() {
+        let private_key = PrivateKey::generate(6).unwrap();
+        let tree = StoredTree::<Blake3>::from_bytes(private_key.tree_to_bytes()).unwrap();
+
+        // an empty store takes the index recorded before it existed
+        let mut store = MemoryStore::default();
+        assert_eq!(tree.reserve_from(&mut store, 3, 1).unwrap().next_index(), 3);
+        assert_eq!(store.load(), Ok(4));
+
+        // a store ahead of that index keeps its own
+        let mut store = MemoryStore::new(5);
+        assert_eq!(tree.reserve_from(&mut store, 3, 1).unwrap().next_index(), 5);
+        assert!(matches!(
+            tree.reserve_from(&mut MemoryStore::default(), 6, 1),
+            Err(SigningError::Exhausted)
+        ));
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let private_key = PrivateKey::generate(8).unwrap();
+        let encoded = private_key.tree_to_bytes();
+        assert_eq!(
+            StoredTree::<Blake3>::from_bytes(encoded[..encoded.len() - 1].to_vec()),
+            Err(TreeDecodingError::WrongLength {
+                expected: Some(encoded.len() as u64),
+                found: encoded.len() as u64 - 1
+            })
+        );
+        let mut wrong_hash = encoded.clone();
+        wrong_hash[4] ^= 1;
+        assert_eq!(
+            StoredTree::<Blake3>::from_bytes(wrong_hash),
+            Err(TreeDecodingError::BadHeader)
+        );
+        // the nodes just below the root are checked on load
+        let mut top = encoded.clone();
+        let last = top.len() - 1;
+        top[last] ^= 1;
+        assert_eq!(
+            StoredTree::<Blake3>::from_bytes(top),
+            Err(TreeDecodingError::Corrupted)
+        );
+
+        // deeper nodes are checked by every proof which uses them
+        let mut leaf = encoded.clone();
+        leaf[45 + 32] ^= 1;
+        let tree = StoredTree::<Blake3>::from_bytes(leaf).unwrap();
+        let keys = private_key.inner_keys();
+        let mut reservation = tree.reserve(&mut MemoryStore::default(), 1).unwrap();
+        assert_eq!(
+            tree.sign(&mut reservation, &keys[0], b"message"),
+            Err(TreeError::Corrupted { index: 0 })
+        );
+        let mut reservation = tree
+            .reserve_from(&mut MemoryStore::default(), 5, 1)
+            .unwrap();
+        assert!(tree.sign(&mut reservation, &keys[5], b"message").is_ok());
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn maps_stored_trees() {
+        let private_key = PrivateKey::generate(5).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("b3ls-{}-maps_stored_trees", std::process::id()));
+        std::fs::write(&path, private_key.tree_to_bytes()).unwrap();
+        let tree = StoredTree::<Blake3>::open(&path).unwrap();
+        assert_eq!(
+            tree,
+            StoredTree::from_bytes(private_key.tree_to_bytes()).unwrap()
+        );
+        let mut reservation = tree
+            .reserve_from(&mut MemoryStore::default(), 4, 1)
+            .unwrap();
+        let signature = tree
+            .sign(&mut reservation, &private_key.inner_keys()[4], b"message")
+            .unwrap();
+        assert!(private_key.public_key().verify(b"message", &signature));
+        drop(tree);
+
+        std::fs::write(&path, b"not a tree").unwrap();
+        assert_eq!(
+            StoredTree::<Blake3>::open(&path).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
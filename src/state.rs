@@ -0,0 +1,798 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Durable storage for the signing state of a stateful key: the index of
+/// its next unused leaf. Every index below it may already have signed a
+/// message, so the stored index must never move backwards, crashes
+/// included.
+///
+/// Keys sign through a store with `sign_persisted`, which reserves an index
+/// here before producing the signature which uses it. A crash after the
+/// reservation burns the index, but can never lead to it signing twice.
+/// Keys which sign too often to write here every time can reserve a whole
+/// [`Reservation`] of indices in one write instead, and burn whatever is
+/// left of it when they stop.
+/// Reservations only ever advance the index with
+/// [`StateStore::compare_and_swap`], so signers sharing one store, in one
+/// process or many, never reserve the same index. A store restored from an
+/// older copy would hand out its indices again, which an [`AnchoredStore`]
+/// detects.
+pub trait StateStore {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// The next unused index as last durably recorded, or zero if nothing
+    /// has been recorded yet.
+    fn load(&mut self) -> Result<u64, Self::Error>;
+
+    /// Atomically replaces the stored index with `new` if it is still
+    /// `current`, returning only once the change would survive a crash.
+    /// Otherwise nothing is written, and the index found is returned as
+    /// `Ok(Err(found))`.
+    fn compare_and_swap(&mut self, current: u64, new: u64) -> Result<Result<(), u64>, Self::Error>;
+}
+
+/// A [`StateStore`] keeping the index in a file of its own as 8 big-endian
+/// bytes.
+///
+/// The file is never modified in place. Each new index is written to a
+/// temporary file beside it and fsynced, the temporary file is renamed over
+/// the old one, and the directory is fsynced so that the rename itself is
+/// durable. A crash leaves either the old index or the new one, never a mix.
+/// Swaps hold an exclusive lock on a third file, named after the first with
+/// `.lock` appended, so processes sharing the file take turns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileStore {
+        FileStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(suffix);
+        path.into()
+    }
+}
+
+impl StateStore for FileStore {
+    type Error = std::io::Error;
+
+    fn load(&mut self) -> Result<u64, Self::Error> {
+        let bytes = match std::fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let bytes = <[u8; 8]>::try_from(bytes.as_slice()).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "state file {} holds {} bytes, not 8",
+                    self.path.display(),
+                    bytes.len()
+                ),
+            )
+        })?;
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn compare_and_swap(&mut self, current: u64, new: u64) -> Result<Result<(), u64>, Self::Error> {
+        // released when the file is closed
+        let lock = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.sibling(".lock"))?;
+        lock.lock()?;
+
+        let found = self.load()?;
+        if found != current {
+            return Ok(Err(found));
+        }
+        let temporary_path = self.sibling(".tmp");
+        let mut file = File::create(&temporary_path)?;
+        file.write_all(&new.to_be_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&temporary_path, &self.path)?;
+        sync_parent(&self.path)?;
+        Ok(Ok(()))
+    }
+}
+
+/// Makes a rename within the directory holding `path` durable. Only Unix
+/// lets directories be opened and synced.
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// A [`StateStore`] held in memory, for tests. It is not durable at all.
+/// Clones share one index, standing in for signers sharing a real store.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    next: Arc<Mutex<u64>>,
+}
+
+impl MemoryStore {
+    pub fn new(next: u64) -> MemoryStore {
+        MemoryStore {
+            next: Arc::new(Mutex::new(next)),
+        }
+    }
+}
+
+impl StateStore for MemoryStore {
+    type Error = std::convert::Infallible;
+
+    fn load(&mut self) -> Result<u64, Self::Error> {
+        Ok(*self.next.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn compare_and_swap(&mut self, current: u64, new: u64) -> Result<Result<(), u64>, Self::Error> {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        if *next != current {
+            return Ok(Err(*next));
+        }
+        *next = new;
+        Ok(Ok(()))
+    }
+}
+
+/// A [`StateStore`] keeping indices in an SQLite database, in a table named
+/// `signing_state` with one row per key. Many keys can share a database,
+/// and many processes a key: swaps run in immediate transactions, which
+/// SQLite serializes, with full synchronous writes.
+#[cfg(feature = "rusqlite")]
+pub struct SqliteStore {
+    connection: rusqlite::Connection,
+    key: String,
+}
+
+#[cfg(feature = "rusqlite")]
+impl SqliteStore {
+    /// Opens the index named `key` in the database at `path`, creating the
+    /// database and its table as needed.
+    pub fn open<P: AsRef<Path>>(path: P, key: &str) -> rusqlite::Result<SqliteStore> {
+        SqliteStore::from_connection(rusqlite::Connection::open(path)?, key)
+    }
+
+    /// Uses the index named `key` in an already open database, creating its
+    /// table as needed.
+    pub fn from_connection(
+        connection: rusqlite::Connection,
+        key: &str,
+    ) -> rusqlite::Result<SqliteStore> {
+        connection.busy_timeout(std::time::Duration::from_secs(10))?;
+        connection.pragma_update(None, "synchronous", "FULL")?;
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS signing_state (key TEXT PRIMARY KEY, next INTEGER NOT NULL)",
+            [],
+        )?;
+        Ok(SqliteStore {
+            connection,
+            key: key.to_owned(),
+        })
+    }
+
+    fn select(connection: &rusqlite::Connection, key: &str) -> rusqlite::Result<u64> {
+        use rusqlite::OptionalExtension;
+        let next = connection
+            .query_row(
+                "SELECT next FROM signing_state WHERE key = ?1",
+                [key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(next.unwrap_or(0))
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl StateStore for SqliteStore {
+    type Error = rusqlite::Error;
+
+    fn load(&mut self) -> Result<u64, Self::Error> {
+        SqliteStore::select(&self.connection, &self.key)
+    }
+
+    fn compare_and_swap(&mut self, current: u64, new: u64) -> Result<Result<(), u64>, Self::Error> {
+        let transaction = self
+            .connection
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let found = SqliteStore::select(&transaction, &self.key)?;
+        if found != current {
+            return Ok(Err(found));
+        }
+        transaction.execute(
+            "INSERT INTO signing_state (key, next) VALUES (?1, ?2) \
+             ON CONFLICT (key) DO UPDATE SET next = excluded.next",
+            rusqlite::params![self.key, new],
+        )?;
+        transaction.commit()?;
+        Ok(Ok(()))
+    }
+}
+
+/// A [`StateStore`] which detects being rolled back, as when the store is
+/// restored from a backup along with the key.
+///
+/// Every index written to `store` is then recorded in `anchor` as well, a
+/// second store kept somewhere a backup of the first will not restore, such
+/// as a [`FileStore`] in another directory. Indices only ever advance, so
+/// a `store` found behind its `anchor` has been rolled back, and loads and
+/// swaps fail with [`AnchorError::RolledBack`] rather than hand out indices
+/// which may already have signed. The anchor is written after the store, so
+/// a crash in between only leaves it behind, which is harmless.
+#[derive(Clone, Debug)]
+pub struct AnchoredStore<S, A> {
+    store: S,
+    anchor: A,
+}
+
+impl<S: StateStore, A: StateStore> AnchoredStore<S, A> {
+    pub fn new(store: S, anchor: A) -> AnchoredStore<S, A> {
+        AnchoredStore { store, anchor }
+    }
+
+    /// Loads the store, failing if it is behind the anchor. The anchor never
+    /// passes the store, so reading it first means that other signers
+    /// advancing both in between cannot make the store look behind.
+    fn load_checked(&mut self) -> Result<u64, AnchorError<S::Error, A::Error>> {
+        let anchored = self.anchor.load().map_err(AnchorError::Anchor)?;
+        let stored = self.store.load().map_err(AnchorError::Store)?;
+        match stored < anchored {
+            true => Err(AnchorError::RolledBack { stored, anchored }),
+            false => Ok(stored),
+        }
+    }
+}
+
+impl<S: StateStore, A: StateStore> StateStore for AnchoredStore<S, A> {
+    type Error = AnchorError<S::Error, A::Error>;
+
+    fn load(&mut self) -> Result<u64, Self::Error> {
+        self.load_checked()
+    }
+
+    fn compare_and_swap(&mut self, current: u64, new: u64) -> Result<Result<(), u64>, Self::Error> {
+        let stored = self.load_checked()?;
+        if stored != current {
+            return Ok(Err(stored));
+        }
+        if let Err(found) = self
+            .store
+            .compare_and_swap(current, new)
+            .map_err(AnchorError::Store)?
+        {
+            return Ok(Err(found));
+        }
+        let mut anchored = self.anchor.load().map_err(AnchorError::Anchor)?;
+        while anchored < new {
+            match self
+                .anchor
+                .compare_and_swap(anchored, new)
+                .map_err(AnchorError::Anchor)?
+            {
+                Ok(()) => break,
+                Err(found) => anchored = found,
+            }
+        }
+        Ok(Ok(()))
+    }
+}
+
+/// An error encountered by an [`AnchoredStore`].
+#[derive(Debug)]
+pub enum AnchorError<E, A> {
+    /// The store could not be read or written.
+    Store(E),
+    /// The anchor could not be read or written.
+    Anchor(A),
+    /// The store holds `stored`, but has already recorded `anchored`, so it
+    /// has been rolled back.
+    RolledBack { stored: u64, anchored: u64 },
+}
+
+impl<E: std::fmt::Display, A: std::fmt::Display> std::fmt::Display for AnchorError<E, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnchorError::Store(e) => write!(f, "{e}"),
+            AnchorError::Anchor(e) => write!(f, "could not check the state anchor: {e}"),
+            AnchorError::RolledBack { stored, anchored } => write!(
+                f,
+                "signing state was rolled back to index {stored} after reaching {anchored}"
+            ),
+        }
+    }
+}
+
+impl<E, A> std::error::Error for AnchorError<E, A>
+where
+    E: std::error::Error + 'static,
+    A: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AnchorError::Store(e) => Some(e),
+            AnchorError::Anchor(e) => Some(e),
+            AnchorError::RolledBack { .. } => None,
+        }
+    }
+}
+
+/// A block of indices durably reserved in a [`StateStore`] for one key,
+/// from which it signs in memory with `sign_reserved` without touching the
+/// store again. The store has already moved past the whole block, so any
+/// index left in it when it is dropped, or when the process crashes, is
+/// burned rather than ever used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reservation {
+    next: u64,
+    end: u64,
+    skipped: u64,
+}
+
+impl Reservation {
+    /// The next index to sign with, or where the block ended if every index
+    /// in it has been used.
+    pub fn next_index(&self) -> u64 {
+        self.next
+    }
+
+    /// The index just past the block.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// How many indices are left in the block, all of which are burned if
+    /// it is dropped now.
+    pub fn remaining(&self) -> u64 {
+        self.end - self.next
+    }
+
+    /// How many indices the key skipped to reach the start of the block,
+    /// because the store was ahead of it. Those were burned by a crash
+    /// after an earlier reservation, or used through another copy of the
+    /// key sharing the store.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Takes the next index of the block, for signers which track indices
+    /// themselves rather than through a key. An index taken is used up,
+    /// whether or not it goes on to sign.
+    pub fn take(&mut self) -> Option<u64> {
+        self.take_from(0)
+    }
+
+    /// Takes the next index for a key whose own state says `current` is
+    /// next, passing over any indices of the block it has already moved
+    /// beyond.
+    pub(crate) fn take_from(&mut self, current: u64) -> Option<u64> {
+        self.next = self.next.max(current).min(self.end);
+        (self.next < self.end).then(|| {
+            self.next += 1;
+            self.next - 1
+        })
+    }
+}
+
+/// Reserves up to `count` indices for a key whose own state says `current`
+/// is next, starting from the later of `current` and the stored index, so
+/// that a key restored from an old copy skips what its newer copies used.
+/// The block ends early if the key runs out. The index after it is durably
+/// stored before the reservation is returned, and if another signer
+/// advances the store first, the reservation starts over from where it
+/// left the index.
+pub(crate) fn reserve<S: StateStore>(
+    store: &mut S,
+    current: u64,
+    num_items: u64,
+    count: u64,
+) -> Result<Reservation, SigningError<S::Error>> {
+    let mut stored = store.load().map_err(SigningError::Store)?;
+    loop {
+        let start = stored.max(current);
+        if start >= num_items {
+            return Err(SigningError::Exhausted);
+        }
+        let end = start.saturating_add(count.max(1)).min(num_items);
+        match store.compare_and_swap(stored, end) {
+            Ok(Ok(())) => {
+                return Ok(Reservation {
+                    next: start,
+                    end,
+                    skipped: start - current,
+                })
+            }
+            Ok(Err(found)) => stored = found,
+            Err(e) => return Err(SigningError::Store(e)),
+        }
+    }
+}
+
+/// An error encountered while signing through a [`StateStore`]. No
+/// signature is produced in either case.
+#[derive(Debug)]
+pub enum SigningError<E> {
+    /// Every index has been used, according to the key or the store.
+    Exhausted,
+    /// The store could not be read or written.
+    Store(E),
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for SigningError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigningError::Exhausted => write!(f, "every one-time key has been used"),
+            SigningError::Store(e) => write!(f, "could not persist the signing state: {e}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for SigningError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SigningError::Exhausted => None,
+            SigningError::Store(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lms, merkle, xmss};
+
+    /// A store which fails every write.
+    struct FailingStore;
+
+    impl StateStore for FailingStore {
+        type Error = std::io::Error;
+
+        fn load(&mut self) -> Result<u64, Self::Error> {
+            Ok(1)
+        }
+
+        fn compare_and_swap(&mut self, _: u64, _: u64) -> Result<Result<(), u64>, Self::Error> {
+            Err(std::io::Error::other("disk full"))
+        }
+    }
+
+    fn temporary_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("b3ls-{}-{name}", std::process::id()))
+    }
+
+    /// Reserves `count` indices through each of `stores` at once, all
+    /// sharing one index, and checks that no index is handed out twice.
+    fn reserve_concurrently<S: StateStore + Send + 'static>(mut stores: Vec<S>, count: u64) {
+        let signers = stores.len() as u64;
+        let start = stores[0].load().unwrap();
+        let handles: Vec<_> = stores
+            .into_iter()
+            .map(|mut store| {
+                std::thread::spawn(move || {
+                    (0..count)
+                        .map(|_| reserve(&mut store, 0, u64::MAX, 1).unwrap().next_index())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut reserved: Vec<u64> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        reserved.sort();
+        assert_eq!(
+            reserved,
+            (start..start + signers * count).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn file_store_swaps() {
+        let path = temporary_file("file_store_swaps");
+        let _ = std::fs::remove_file(&path);
+        let mut store = FileStore::new(&path);
+        assert_eq!(store.load().unwrap(), 0);
+        assert_eq!(store.compare_and_swap(0, 7).unwrap(), Ok(()));
+        assert_eq!(store.compare_and_swap(0, 8).unwrap(), Err(7));
+        assert_eq!(FileStore::new(&path).load().unwrap(), 7);
+        assert!(!store.sibling(".tmp").exists());
+
+        reserve_concurrently(vec![FileStore::new(&path); 4], 10);
+        assert_eq!(store.load().unwrap(), 47);
+
+        std::fs::write(&path, [1, 2, 3]).unwrap();
+        assert_eq!(
+            store.load().unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(store.sibling(".lock")).unwrap();
+    }
+
+    #[test]
+    fn reserves_before_signing() {
+        let mut private_key = merkle::PrivateKey::generate(3).unwrap();
+        let public_key = private_key.public_key();
+        let mut store = MemoryStore::default();
+        let signature = private_key.sign_persisted(&mut store, b"first").unwrap();
+        assert_eq!(signature.index(), 0);
+        assert_eq!(store.load(), Ok(1));
+
+        // nothing is signed unless the reservation is durable
+        assert!(matches!(
+            private_key.sign_persisted(&mut FailingStore, b"second"),
+            Err(SigningError::Store(_))
+        ));
+        assert_eq!(private_key.current_index(), 1);
+
+        // another signer sharing the store takes the next index, so this
+        // one skips it
+        let mut other = store.clone();
+        assert_eq!(reserve(&mut other, 0, 3, 1).unwrap().next_index(), 1);
+        let signature = private_key.sign_persisted(&mut store, b"third").unwrap();
+        assert_eq!(signature.index(), 2);
+        assert!(public_key.verify(b"third", &signature));
+        assert!(matches!(
+            private_key.sign_persisted(&mut store, b"fourth"),
+            Err(SigningError::Exhausted)
+        ));
+    }
+
+    #[test]
+    fn memory_stores_share_an_index() {
+        let store = MemoryStore::new(5);
+        reserve_concurrently(vec![store.clone(); 8], 100);
+        assert_eq!(store.clone().load(), Ok(805));
+    }
+
+    #[test]
+    fn seeded_keys_skip_to_the_stored_index() {
+        let mut private_key = merkle::seeded::PrivateKey::from_seed([4u8; 32], 8).unwrap();
+        let public_key = private_key.public_key();
+        let mut store = MemoryStore::new(5);
+        let signature = private_key.sign_persisted(&mut store, b"message").unwrap();
+        assert_eq!(signature.index(), 5);
+        assert!(public_key.verify(b"message", &signature));
+        assert_eq!(private_key.current_index(), 6);
+        assert_eq!(store.load(), Ok(6));
+    }
+
+    #[test]
+    fn burns_unused_reservations() {
+        let mut private_key = merkle::seeded::PrivateKey::from_seed([6u8; 32], 16).unwrap();
+        let public_key = private_key.public_key();
+        let backup = merkle::seeded::PrivateKey::try_from(&private_key.to_bytes()[..]).unwrap();
+        let mut store = MemoryStore::default();
+        let mut reservation = private_key.reserve(&mut store, 10).unwrap();
+        assert_eq!((reservation.next_index(), reservation.end()), (0, 10));
+        assert_eq!(store.load(), Ok(10));
+        for i in 0..3 {
+            let signature = private_key
+                .sign_reserved(&mut reservation, b"message")
+                .unwrap();
+            assert_eq!(signature.index(), i);
+            assert!(public_key.verify(b"message", &signature));
+        }
+        assert_eq!(reservation.remaining(), 7);
+
+        // after a crash, the key restored from its backup learns that ten
+        // indices are gone and never signs with them
+        let mut private_key = backup;
+        let mut reservation = private_key.reserve(&mut store, 10).unwrap();
+        assert_eq!(reservation.skipped(), 10);
+        assert_eq!((reservation.next_index(), reservation.end()), (10, 16));
+        let signature = private_key
+            .sign_reserved(&mut reservation, b"message")
+            .unwrap();
+        assert_eq!(signature.index(), 10);
+        assert!(public_key.verify(b"message", &signature));
+        assert_eq!(reservation.remaining(), 5);
+        assert!(matches!(
+            private_key.reserve(&mut store, 1),
+            Err(SigningError::Exhausted)
+        ));
+    }
+
+    #[test]
+    fn reservations_pass_over_used_indices() {
+        let mut private_key = merkle::PrivateKey::generate(4).unwrap();
+        let mut store = MemoryStore::default();
+        let mut reservation = private_key.reserve(&mut store, 3).unwrap();
+        private_key.sign(b"outside the reservation").unwrap();
+        let signature = private_key
+            .sign_reserved(&mut reservation, b"message")
+            .unwrap();
+        assert_eq!(signature.index(), 1);
+        private_key
+            .sign_reserved(&mut reservation, b"message")
+            .unwrap();
+        assert!(private_key
+            .sign_reserved(&mut reservation, b"message")
+            .is_none());
+        assert_eq!(reservation.remaining(), 0);
+    }
+
+    #[test]
+    fn restored_xmss_keys_skip_used_indices() {
+        let mut private_key =
+            xmss::PrivateKey::from_seeds(xmss::XmssType::Blake3H10, [1; 32], [2; 32], [3; 32]);
+        let public_key = private_key.public_key();
+        let snapshot = private_key.to_bytes();
+        let mut store = MemoryStore::default();
+        for i in 0..2 {
+            let signature = private_key.sign_persisted(&mut store, b"message").unwrap();
+            assert_eq!(signature.index(), i);
+        }
+
+        // the copy taken before those signatures must not sign with their
+        // leaves again
+        let mut restored = xmss::PrivateKey::try_from(&snapshot[..]).unwrap();
+        let signature = restored.sign_persisted(&mut store, b"restored").unwrap();
+        assert_eq!(signature.index(), 2);
+        assert!(public_key.verify(b"restored", &signature));
+        assert_eq!(restored.current_index(), 3);
+        assert_eq!(store.load(), Ok(3));
+    }
+
+    #[test]
+    fn restored_lms_keys_skip_used_indices() {
+        let mut private_key = lms::PrivateKey::from_seed(
+            lms::LmsType::Blake3M32H5,
+            lms::OtsType::Blake3N32W4,
+            [1; 16],
+            [2; 32],
+        );
+        let public_key = private_key.public_key();
+        let snapshot = private_key.to_bytes();
+        let mut store = MemoryStore::default();
+        let mut reservation = private_key.reserve(&mut store, 4).unwrap();
+        private_key
+            .sign_reserved(&mut reservation, b"message")
+            .unwrap();
+
+        let mut restored = lms::PrivateKey::try_from(&snapshot[..]).unwrap();
+        let signature = restored.sign_persisted(&mut store, b"restored").unwrap();
+        assert_eq!(signature.index(), 4);
+        assert!(public_key.verify(b"restored", &signature));
+        assert!(matches!(
+            restored.reserve(&mut MemoryStore::new(32), 1),
+            Err(SigningError::Exhausted)
+        ));
+    }
+
+    #[test]
+    fn restored_hss_keys_skip_used_indices() {
+        let levels = [(lms::LmsType::Blake3M32H5, lms::OtsType::Blake3N32W4); 2];
+        let mut private_key = lms::HssPrivateKey::from_seed(&levels, [3; 16], [4; 32]).unwrap();
+        let public_key = private_key.public_key();
+        assert_eq!(private_key.num_items(), 1024);
+        let snapshot = private_key.to_bytes();
+        let mut store = MemoryStore::default();
+        for _ in 0..3 {
+            private_key.sign_persisted(&mut store, b"message").unwrap();
+        }
+        assert_eq!(private_key.current_index(), 3);
+        // a crash burns a reservation running into the second bottom key
+        private_key.reserve(&mut store, 40).unwrap();
+
+        let mut restored = lms::HssPrivateKey::try_from(&snapshot[..]).unwrap();
+        let signature = restored.sign_persisted(&mut store, b"restored").unwrap();
+        assert!(public_key.verify(b"restored", &signature));
+        assert_eq!(restored.current_index(), 44);
+        assert_eq!(store.load(), Ok(44));
+
+        // the restored key is where signing every index would have left it
+        let mut sequential = lms::HssPrivateKey::from_seed(&levels, [3; 16], [4; 32]).unwrap();
+        for _ in 0..44 {
+            sequential.sign(b"message").unwrap();
+        }
+        assert_eq!(restored.to_bytes(), sequential.to_bytes());
+    }
+
+    #[test]
+    fn anchors_detect_rollbacks() {
+        let mut private_key = merkle::PrivateKey::generate(8).unwrap();
+        let anchor = MemoryStore::default();
+        let mut store = AnchoredStore::new(MemoryStore::default(), anchor.clone());
+        private_key.sign_persisted(&mut store, b"first").unwrap();
+        private_key.sign_persisted(&mut store, b"second").unwrap();
+        assert_eq!(anchor.clone().load(), Ok(2));
+
+        // the store is restored to an older copy, but the anchor is not
+        let mut restored = AnchoredStore::new(MemoryStore::new(1), anchor.clone());
+        assert!(matches!(
+            restored.load(),
+            Err(AnchorError::RolledBack {
+                stored: 1,
+                anchored: 2
+            })
+        ));
+        let mut stale_key = merkle::PrivateKey::generate(8).unwrap();
+        assert!(matches!(
+            stale_key.sign_persisted(&mut restored, b"third"),
+            Err(SigningError::Store(AnchorError::RolledBack { .. }))
+        ));
+        assert_eq!(stale_key.current_index(), 0);
+
+        // a store found ahead of its anchor only means the anchor was not
+        // written before a crash
+        let mut ahead = AnchoredStore::new(MemoryStore::new(5), anchor.clone());
+        assert_eq!(reserve(&mut ahead, 0, 8, 1).unwrap().next_index(), 5);
+        assert_eq!(anchor.clone().load(), Ok(6));
+    }
+
+    #[test]
+    fn anchored_stores_share_an_index() {
+        let store = AnchoredStore::new(MemoryStore::default(), MemoryStore::default());
+        reserve_concurrently(vec![store.clone(); 8], 100);
+        assert_eq!(store.anchor.clone().load(), Ok(800));
+    }
+
+    #[test]
+    fn anchors_detect_restored_state_files() {
+        let path = temporary_file("anchors_detect_restored_state_files");
+        let anchor_path = temporary_file("anchors_detect_restored_state_files.anchor");
+        let mut store = AnchoredStore::new(FileStore::new(&path), FileStore::new(&anchor_path));
+        assert_eq!(store.compare_and_swap(0, 3).unwrap(), Ok(()));
+        let backup = std::fs::read(&path).unwrap();
+        assert_eq!(store.compare_and_swap(3, 4).unwrap(), Ok(()));
+        std::fs::write(&path, backup).unwrap();
+        assert!(matches!(
+            store.load(),
+            Err(AnchorError::RolledBack {
+                stored: 3,
+                anchored: 4
+            })
+        ));
+        assert!(matches!(
+            store.compare_and_swap(3, 5),
+            Err(AnchorError::RolledBack { .. })
+        ));
+        for path in [&path, &anchor_path] {
+            std::fs::remove_file(path).unwrap();
+            std::fs::remove_file(FileStore::new(path).sibling(".lock")).unwrap();
+        }
+    }
+
+    #[cfg(feature = "rusqlite")]
+    #[test]
+    fn sqlite_stores_swap() {
+        let path = temporary_file("sqlite_stores_swap.db");
+        let _ = std::fs::remove_file(&path);
+        let mut store = SqliteStore::open(&path, "alice").unwrap();
+        assert_eq!(store.load().unwrap(), 0);
+        assert_eq!(store.compare_and_swap(0, 3).unwrap(), Ok(()));
+        assert_eq!(store.compare_and_swap(0, 4).unwrap(), Err(3));
+        assert_eq!(SqliteStore::open(&path, "bob").unwrap().load().unwrap(), 0);
+
+        let stores = (0..4)
+            .map(|_| SqliteStore::open(&path, "alice").unwrap())
+            .collect();
+        reserve_concurrently(stores, 10);
+        assert_eq!(store.load().unwrap(), 43);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,716 @@
+use blake3::Hash;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+use crate::lamport;
+use crate::merkle::internal::{Commitment, Proof, ProofDecodingError, Reader, Tree};
+use crate::merkle::{self, DecodingLimits};
+use crate::wire::{self, Algorithm, Framed, Kind};
+
+/// The shape of a stateless key: a hypertree of `layers` layers of Merkle
+/// trees with `2^tree_height` Lamport leaves each, whose bottom leaves sign
+/// the public keys of FORS instances made of `fors_trees` trees with
+/// `2^fors_height` secret values each.
+///
+/// Every signature rebuilds one tree per layer, so taller trees make for
+/// fewer layers and smaller signatures at the price of slower signing.
+///
+/// Nothing counts signatures, so each one picks a hypertree leaf at random,
+/// and after `q` signatures each FORS instance has been used about
+/// `q / 2^(layers * tree_height)` times. Every reuse reveals more of it, so
+/// the total height bounds how many messages a key can safely sign: with
+/// 14 FORS trees of 2^12 values, a height of 20 leaves forgeries a chance
+/// of about 2^-30 per attempt after 2^30 signatures, while a height of 60
+/// keeps it below 2^-170 after 2^50 signatures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Parameters {
+    layers: u8,
+    tree_height: u8,
+    fors_trees: u8,
+    fors_height: u8,
+}
+
+impl Parameters {
+    /// Sixteen layers of 16-leaf trees, 64 levels in all: signing derives
+    /// only 256 Lamport keys, but signatures carry sixteen Lamport
+    /// signatures and public keys. Forgery attempts succeed with
+    /// probability below 2^-180 after 2^50 signatures, and 2^-140 after
+    /// 2^64.
+    pub const FAST: Parameters = Parameters {
+        layers: 16,
+        tree_height: 4,
+        fors_trees: 14,
+        fors_height: 12,
+    };
+
+    /// Six layers of 1024-leaf trees, 60 levels in all: signatures carry
+    /// only six Lamport signatures and public keys, but signing derives
+    /// 6144 Lamport keys. Forgery attempts succeed with probability below
+    /// 2^-170 after 2^50 signatures, and 2^-140 after 2^60.
+    pub const SMALL: Parameters = Parameters {
+        layers: 6,
+        tree_height: 10,
+        fors_trees: 14,
+        fors_height: 12,
+    };
+
+    /// Returns [`None`] unless every count is non-zero, the hypertree has at
+    /// most 2^64 leaves, its trees at most 2^16 leaves, and the FORS trees
+    /// at most 2^20 values each. A hypertree much shorter than the presets'
+    /// limits the number of signatures a key can safely make, as described
+    /// above.
+    pub fn new(layers: u8, tree_height: u8, fors_trees: u8, fors_height: u8) -> Option<Parameters> {
+        if layers == 0 || tree_height == 0 || fors_trees == 0 || fors_height == 0 {
+            return None;
+        }
+        if tree_height > 16 || fors_height > 20 || layers as u32 * tree_height as u32 > 64 {
+            return None;
+        }
+        Some(Parameters {
+            layers,
+            tree_height,
+            fors_trees,
+            fors_height,
+        })
+    }
+
+    /// The parameter set field of framed encodings: the four counts, one
+    /// byte each, in the order [`Parameters::new`] takes them.
+    fn framed_parameter_set(&self) -> u32 {
+        u32::from_be_bytes([
+            self.layers,
+            self.tree_height,
+            self.fors_trees,
+            self.fors_height,
+        ])
+    }
+
+    fn from_framed_parameter_set(parameter_set: u32) -> Result<Parameters, wire::DecodingError> {
+        let [layers, tree_height, fors_trees, fors_height] = parameter_set.to_be_bytes();
+        Parameters::new(layers, tree_height, fors_trees, fors_height)
+            .ok_or(wire::DecodingError::InvalidParameterSet { parameter_set })
+    }
+
+    pub fn layers(&self) -> u8 {
+        self.layers
+    }
+
+    pub fn tree_height(&self) -> u8 {
+        self.tree_height
+    }
+
+    pub fn fors_trees(&self) -> u8 {
+        self.fors_trees
+    }
+
+    pub fn fors_height(&self) -> u8 {
+        self.fors_height
+    }
+
+    /// The length in bytes of every encoded signature.
+    pub fn signature_len(&self) -> usize {
+        let proof_len = |item_len: usize, height: u8| 24 + item_len + height as usize * 33;
+        let fors = self.fors_trees as usize * proof_len(32, self.fors_height);
+        let layer = 8 + 8192 + 16384 + proof_len(16384, self.tree_height);
+        32 + fors + self.layers as usize * layer
+    }
+
+    fn hypertree_height(&self) -> u32 {
+        self.layers as u32 * self.tree_height as u32
+    }
+}
+
+/// Derives a 32-byte secret for the purpose named by `context`.
+fn derive(seed: &[u8; 32], context: &[u8], fields: &[u64]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_keyed(seed);
+    hasher.update(context);
+    for field in fields {
+        hasher.update(&field.to_be_bytes());
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// Reads `bits` bits, most significant first, starting `offset` bits in.
+pub(crate) fn read_bits(bytes: &[u8], offset: usize, bits: u32) -> u64 {
+    (0..bits as usize).fold(0, |value, i| {
+        let bit = offset + i;
+        (value << 1) | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as u64
+    })
+}
+
+/// Splits the digest of a message into the hypertree leaf which signs it
+/// and the FORS value revealed from each FORS tree.
+fn message_digest(
+    parameters: &Parameters,
+    randomizer: &[u8; 32],
+    root: &Hash,
+    message: &[u8],
+) -> (u64, Vec<u64>) {
+    let height = parameters.hypertree_height();
+    let fors_bits = parameters.fors_trees as usize * parameters.fors_height as usize;
+    let mut digest = vec![0u8; (height as usize + fors_bits).div_ceil(8)];
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"stateless message digest");
+    hasher.update(randomizer);
+    hasher.update(root.as_bytes());
+    hasher.update(message);
+    hasher.finalize_xof().fill(&mut digest);
+
+    let leaf = read_bits(&digest, 0, height);
+    let fors_indices = (0..parameters.fors_trees as usize)
+        .map(|i| {
+            let offset = height as usize + i * parameters.fors_height as usize;
+            read_bits(&digest, offset, parameters.fors_height as u32)
+        })
+        .collect();
+    (leaf, fors_indices)
+}
+
+/// Combines the FORS tree roots into the message signed by the leaf.
+fn fors_public_key(roots: &[Hash]) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"stateless fors public key");
+    for root in roots {
+        hasher.update(root.as_bytes());
+    }
+    hasher.finalize()
+}
+
+/// A stateless private key: a secret seed from which every Lamport key,
+/// FORS value and tree is rebuilt on demand. The same message always
+/// receives the same signature, and no index ever needs to be stored.
+#[derive(PartialEq, Eq)]
+pub struct PrivateKey {
+    seed: [u8; 32],
+    parameters: Parameters,
+    root: Hash,
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("parameters", &self.parameters)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl PrivateKey {
+    pub fn generate(parameters: Parameters) -> Result<PrivateKey, rand::Error> {
+        let mut seed = [0u8; 32];
+        OsRng.try_fill_bytes(&mut seed)?;
+        Ok(PrivateKey::from_seed(seed, parameters))
+    }
+
+    /// Deterministically builds the key with the given `parameters` from a
+    /// secret `seed`.
+    pub fn from_seed(seed: [u8; 32], parameters: Parameters) -> PrivateKey {
+        let mut private_key = PrivateKey {
+            seed,
+            parameters,
+            root: Hash::from([0u8; 32]),
+        };
+        private_key.root = private_key
+            .hypertree_tree(parameters.layers - 1, 0)
+            .commitment()
+            .root;
+        private_key
+    }
+
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            parameters: self.parameters,
+            root: self.root,
+        }
+    }
+
+    /// The Lamport key at `leaf` of the `tree`th tree of `layer`.
+    fn lamport_key(&self, layer: u8, tree: u64, leaf: u64) -> lamport::PrivateKey {
+        let tree_seed = derive(&self.seed, b"stateless tree seed", &[layer as u64, tree]);
+        lamport::PrivateKey::derive(&tree_seed, leaf)
+    }
+
+    fn hypertree_tree(&self, layer: u8, tree: u64) -> Tree {
+        let public_keys: Vec<[u8; 16384]> = (0..1u64 << self.parameters.tree_height)
+            .map(|leaf| {
+                self.lamport_key(layer, tree, leaf)
+                    .public_key()
+                    .to_raw_bytes()
+            })
+            .collect();
+        Tree::new(&mut public_keys.iter().map(|key| key.as_slice()))
+            .expect("hypertree trees are never empty")
+    }
+
+    /// The FORS tree `fors_tree` of the FORS instance belonging to the
+    /// hypertree leaf `leaf`.
+    fn fors_tree(&self, leaf: u64, fors_tree: u64) -> (Vec<[u8; 32]>, Tree) {
+        let secrets: Vec<[u8; 32]> = (0..1u64 << self.parameters.fors_height)
+            .map(|value| {
+                derive(
+                    &self.seed,
+                    b"stateless fors secret",
+                    &[leaf, fors_tree, value],
+                )
+            })
+            .collect();
+        let tree = Tree::new(&mut secrets.iter().map(|secret| secret.as_slice()))
+            .expect("fors trees are never empty");
+        (secrets, tree)
+    }
+
+    pub fn sign<A: AsRef<[u8]>>(&self, message: A) -> Signature {
+        let message = message.as_ref();
+        let mut hasher = blake3::Hasher::new_keyed(&self.seed);
+        hasher.update(b"stateless randomizer");
+        hasher.update(message);
+        let randomizer = *hasher.finalize().as_bytes();
+
+        let (leaf, fors_indices) =
+            message_digest(&self.parameters, &randomizer, &self.root, message);
+
+        let mut roots = Vec::with_capacity(fors_indices.len());
+        let mut fors = Vec::with_capacity(fors_indices.len());
+        for (i, &index) in fors_indices.iter().enumerate() {
+            let (secrets, tree) = self.fors_tree(leaf, i as u64);
+            roots.push(tree.commitment().root);
+            fors.push(
+                tree.prove(secrets[index as usize].to_vec(), index)
+                    .expect("the secret was committed to at this index"),
+            );
+        }
+
+        // each leaf only ever signs one FORS public key or tree root, so
+        // its Lamport key is never used on two different messages
+        let height = self.parameters.tree_height as u32;
+        let mut signed = fors_public_key(&roots).as_bytes().to_vec();
+        let mut chain = Vec::with_capacity(self.parameters.layers as usize);
+        for layer in 0..self.parameters.layers {
+            let tree_index = leaf.checked_shr(height * (layer as u32 + 1)).unwrap_or(0);
+            let leaf_index = (leaf >> (height * layer as u32)) & ((1 << height) - 1);
+
+            let tree = self.hypertree_tree(layer, tree_index);
+            let lamport_private_key = self.lamport_key(layer, tree_index, leaf_index);
+            let lamport_public_key = lamport_private_key.public_key();
+            let proof = tree
+                .prove(lamport_public_key.to_raw_bytes().to_vec(), leaf_index)
+                .expect("the public key was committed to at this index");
+            chain.push(merkle::Signature(
+                lamport_private_key.sign(&signed),
+                lamport_public_key,
+                proof,
+            ));
+            signed = tree.commitment().to_bytes().to_vec();
+        }
+
+        Signature {
+            parameters: self.parameters,
+            randomizer,
+            fors,
+            chain,
+        }
+    }
+}
+
+/// The public key of a stateless key: the root of its top-level tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    parameters: Parameters,
+    root: Hash,
+}
+
+impl PublicKey {
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature) -> bool {
+        let parameters = &self.parameters;
+        if signature.parameters != *parameters
+            || signature.fors.len() != parameters.fors_trees as usize
+            || signature.chain.len() != parameters.layers as usize
+        {
+            return false;
+        }
+        let (leaf, fors_indices) = message_digest(
+            parameters,
+            &signature.randomizer,
+            &self.root,
+            message.as_ref(),
+        );
+
+        let mut roots = Vec::with_capacity(fors_indices.len());
+        for (proof, &index) in signature.fors.iter().zip(fors_indices.iter()) {
+            if proof.index() != index {
+                return false;
+            }
+            match proof.root(1 << parameters.fors_height) {
+                Some(root) => roots.push(root),
+                None => return false,
+            }
+        }
+
+        let height = parameters.tree_height as u32;
+        let num_items = 1 << height;
+        let mut signed = fors_public_key(&roots).as_bytes().to_vec();
+        let mut commitment = None;
+        for (layer, signature) in signature.chain.iter().enumerate() {
+            let leaf_index = (leaf >> (height * layer as u32)) & (num_items - 1);
+            if signature.index() != leaf_index {
+                return false;
+            }
+            let recovered = match signature.recover(&signed, num_items) {
+                Some(recovered) => recovered,
+                None => return false,
+            };
+            signed = recovered.to_bytes().to_vec();
+            commitment = Some(recovered);
+        }
+
+        commitment
+            == Some(Commitment {
+                root: self.root,
+                num_items,
+            })
+    }
+
+    /// The framed encoding, naming the parameters.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else a raw one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// Encodes the parameters followed by the root of the top-level tree.
+    pub fn to_raw_bytes(&self) -> [u8; 36] {
+        let mut out = [0u8; 36];
+        out[0] = self.parameters.layers;
+        out[1] = self.parameters.tree_height;
+        out[2] = self.parameters.fors_trees;
+        out[3] = self.parameters.fors_height;
+        out[4..].copy_from_slice(self.root.as_bytes());
+        out
+    }
+}
+
+impl TryFrom<&[u8; 36]> for PublicKey {
+    type Error = SignatureDecodingError;
+
+    fn try_from(value: &[u8; 36]) -> Result<Self, Self::Error> {
+        let parameters = Parameters::new(value[0], value[1], value[2], value[3])
+            .ok_or(SignatureDecodingError::InvalidParameters)?;
+        let mut root = [0u8; 32];
+        root.copy_from_slice(&value[4..]);
+        Ok(PublicKey {
+            parameters,
+            root: Hash::from(root),
+        })
+    }
+}
+
+impl Framed for PublicKey {
+    const ALGORITHM: Algorithm = Algorithm::Stateless;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        self.parameters.framed_parameter_set()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.to_raw_bytes().to_vec()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let raw = <&[u8; 36]>::try_from(body).map_err(|_| wire::DecodingError::LengthMismatch {
+            declared: 36,
+            actual: body.len(),
+        })?;
+        let public_key = PublicKey::try_from(raw).map_err(wire::invalid_body)?;
+        if public_key.parameter_set() != parameter_set {
+            return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+        }
+        Ok(public_key)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        PublicKey::try_from(<&[u8; 36]>::try_from(bytes).ok()?).ok()
+    }
+}
+
+/// A stateless signature: the randomizer mixed into the message digest,
+/// one revealed value and its proof from each FORS tree, and the chain of
+/// Merkle signatures from the FORS public key up to the top-level tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    parameters: Parameters,
+    randomizer: [u8; 32],
+    fors: Vec<Proof>,
+    chain: Vec<merkle::Signature>,
+}
+
+impl Signature {
+    /// The framed encoding, naming the parameters.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, bounding each proof by the default
+    /// [`DecodingLimits`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// Decodes a signature made with `parameters` from untrusted bytes,
+    /// bounding each proof by `limits` and rejecting any trailing input.
+    pub fn decode(
+        signature_bytes: &[u8],
+        parameters: &Parameters,
+        limits: &DecodingLimits,
+    ) -> Result<Self, SignatureDecodingError> {
+        let mut reader = Reader::new(signature_bytes, 0);
+        let randomizer = reader.array::<32>()?;
+
+        let mut fors = Vec::with_capacity(parameters.fors_trees as usize);
+        for _ in 0..parameters.fors_trees {
+            fors.push(Proof::decode_from(&mut reader, limits)?);
+        }
+
+        let mut chain = Vec::with_capacity(parameters.layers as usize);
+        for layer in 0..parameters.layers as usize {
+            let length_offset = reader.offset();
+            let length = u64::from_be_bytes(reader.array()?);
+            let length = usize::try_from(length).unwrap_or(usize::MAX);
+            if length > reader.remaining() {
+                return Err(SignatureDecodingError::NotEnoughInput {
+                    offset: length_offset + 8,
+                    needed: length - reader.remaining(),
+                });
+            }
+            let offset = reader.offset();
+            let encoded = reader.take(length)?;
+            chain.push(merkle::Signature::decode(encoded, limits).map_err(|error| {
+                SignatureDecodingError::InvalidLayer {
+                    layer,
+                    offset,
+                    error,
+                }
+            })?);
+        }
+        reader.finish()?;
+
+        Ok(Signature {
+            parameters: *parameters,
+            randomizer,
+            fors,
+            chain,
+        })
+    }
+}
+
+impl From<&Signature> for Vec<u8> {
+    fn from(signature: &Signature) -> Self {
+        let mut output = signature.randomizer.to_vec();
+        for proof in signature.fors.iter() {
+            output.extend(Vec::from(proof));
+        }
+        for signature in signature.chain.iter() {
+            let encoded = Vec::from(signature);
+            output.extend((encoded.len() as u64).to_be_bytes());
+            output.extend(encoded);
+        }
+        output
+    }
+}
+
+impl Framed for Signature {
+    const ALGORITHM: Algorithm = Algorithm::Stateless;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        self.parameters.framed_parameter_set()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let parameters = Parameters::from_framed_parameter_set(parameter_set)?;
+        Signature::decode(body, &parameters, &DecodingLimits::default()).map_err(wire::invalid_body)
+    }
+}
+
+/// An error encountered while decoding a [`Signature`] or [`PublicKey`].
+/// Every offset is the position, in bytes, within the encoded signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureDecodingError {
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// A FORS proof could not be decoded.
+    InvalidForsProof(ProofDecodingError),
+    /// The Merkle signature of `layer`, starting at `offset`, is malformed.
+    /// Offsets within `error` are relative to the start of that signature.
+    InvalidLayer {
+        layer: usize,
+        offset: usize,
+        error: merkle::SignatureDecodingError,
+    },
+    /// The signature ended at `offset`, but `remaining` bytes followed it.
+    TrailingBytes { offset: usize, remaining: usize },
+    /// A public key named parameters which [`Parameters::new`] rejects.
+    InvalidParameters,
+}
+
+impl From<ProofDecodingError> for SignatureDecodingError {
+    fn from(e: ProofDecodingError) -> Self {
+        match e {
+            ProofDecodingError::NotEnoughInput { offset, needed } => {
+                SignatureDecodingError::NotEnoughInput { offset, needed }
+            }
+            ProofDecodingError::TrailingBytes { offset, remaining } => {
+                SignatureDecodingError::TrailingBytes { offset, remaining }
+            }
+            e => SignatureDecodingError::InvalidForsProof(e),
+        }
+    }
+}
+
+impl std::fmt::Display for SignatureDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureDecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "signature truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            SignatureDecodingError::InvalidForsProof(e) => write!(f, "invalid fors proof: {e}"),
+            SignatureDecodingError::InvalidLayer {
+                layer,
+                offset,
+                error,
+            } => write!(
+                f,
+                "invalid signature for layer {layer} at offset {offset}: {error}"
+            ),
+            SignatureDecodingError::TrailingBytes { offset, remaining } => write!(
+                f,
+                "{remaining} trailing bytes after the signature ending at offset {offset}"
+            ),
+            SignatureDecodingError::InvalidParameters => write!(f, "invalid parameters"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureDecodingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SignatureDecodingError::InvalidForsProof(e) => Some(e),
+            SignatureDecodingError::InvalidLayer { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters() -> Parameters {
+        Parameters::new(2, 2, 4, 3).unwrap()
+    }
+
+    #[test]
+    fn end_to_end() {
+        let private_key = PrivateKey::from_seed([2u8; 32], parameters());
+        let public_key = private_key.public_key();
+        for message in [&b"Hello, world!"[..], b"", b"Yeah, I said it"] {
+            let signature = private_key.sign(message);
+            assert!(public_key.verify(message, &signature));
+            assert!(!public_key.verify(b"something else", &signature));
+            assert_eq!(private_key.sign(message), signature);
+        }
+
+        let other = PrivateKey::from_seed([3u8; 32], parameters()).public_key();
+        assert!(!other.verify(b"Hello, world!", &private_key.sign(b"Hello, world!")));
+    }
+
+    #[test]
+    fn rejects_moved_fors_values() {
+        let private_key = PrivateKey::from_seed([4u8; 32], parameters());
+        let public_key = private_key.public_key();
+        let mut signature = private_key.sign(b"message");
+        signature.fors.swap(0, 1);
+        assert!(!public_key.verify(b"message", &signature));
+
+        let mut signature = private_key.sign(b"message");
+        signature.randomizer[0] ^= 1;
+        assert!(!public_key.verify(b"message", &signature));
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let parameters = parameters();
+        let private_key = PrivateKey::from_seed([5u8; 32], parameters);
+        let public_key = PublicKey::try_from(&private_key.public_key().to_raw_bytes()).unwrap();
+        assert_eq!(public_key, private_key.public_key());
+
+        let signature = private_key.sign(b"message");
+        let encoded = Vec::from(&signature);
+        assert_eq!(encoded.len(), parameters.signature_len());
+        let limits = DecodingLimits::default();
+        let decoded = Signature::decode(&encoded, &parameters, &limits).unwrap();
+        assert_eq!(decoded, signature);
+        assert!(public_key.verify(b"message", &decoded));
+        assert_eq!(
+            Signature::from_bytes(&signature.to_bytes()).unwrap(),
+            signature
+        );
+        assert_eq!(
+            PublicKey::from_bytes(&public_key.to_bytes()).unwrap(),
+            public_key
+        );
+        assert_eq!(
+            PublicKey::from_bytes(&public_key.to_raw_bytes()).unwrap(),
+            public_key
+        );
+
+        assert!(matches!(
+            Signature::decode(&encoded[..encoded.len() - 1], &parameters, &limits),
+            Err(SignatureDecodingError::NotEnoughInput { .. })
+        ));
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert_eq!(
+            Signature::decode(&trailing, &parameters, &limits),
+            Err(SignatureDecodingError::TrailingBytes {
+                offset: encoded.len(),
+                remaining: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn validates_parameters() {
+        assert_eq!(Parameters::new(0, 4, 14, 12), None);
+        assert_eq!(Parameters::new(5, 17, 14, 12), None);
+        assert_eq!(Parameters::new(5, 4, 14, 21), None);
+        assert!(Parameters::SMALL.signature_len() < Parameters::FAST.signature_len());
+        // FORS instances stay all but unused however many messages are signed
+        for parameters in [Parameters::FAST, Parameters::SMALL] {
+            assert!(parameters.hypertree_height() >= 60);
+        }
+    }
+}
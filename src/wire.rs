@@ -0,0 +1,466 @@
+use crate::fingerprint::Fingerprint;
+use crate::merkle::internal::Reader;
+
+/// The bytes every framed encoding starts with.
+pub const MAGIC: [u8; 4] = *b"B3LS";
+
+/// The version of the frame written by this crate.
+pub const VERSION: u8 = 1;
+
+/// The length in bytes of the frame preceding each body.
+pub const HEADER_LEN: usize = 15;
+
+/// The scheme an encoding belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Algorithm {
+    Lamport = 1,
+    Merkle = 2,
+    Hypertree = 3,
+    Hors = 4,
+    Horst = 5,
+    Stateless = 6,
+    Lms = 7,
+    Hss = 8,
+    Xmss = 9,
+}
+
+impl Algorithm {
+    fn from_id(id: u8) -> Option<Algorithm> {
+        [
+            Algorithm::Lamport,
+            Algorithm::Merkle,
+            Algorithm::Hypertree,
+            Algorithm::Hors,
+            Algorithm::Horst,
+            Algorithm::Stateless,
+            Algorithm::Lms,
+            Algorithm::Hss,
+            Algorithm::Xmss,
+        ]
+        .into_iter()
+        .find(|algorithm| *algorithm as u8 == id)
+    }
+}
+
+/// Whether an encoding holds a public key or a signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kind {
+    PublicKey = 1,
+    Signature = 2,
+    /// A signature whose body starts with the [`Fingerprint`] of the key
+    /// which made it, so that verifiers holding many keys can pick the
+    /// right one with [`key_id`]. It decodes wherever a signature does.
+    KeyIdentifiedSignature = 3,
+}
+
+/// The frame preceding the body of every encoded public key and signature:
+///
+/// ```text
+/// magic "B3LS" | version (1) | algorithm (1) | kind (1) | parameter set (4) | length (4) | body
+/// ```
+///
+/// with integers in big-endian order. The parameter set names everything a
+/// decoder needs to read the body, whose format is the unframed encoding of
+/// each type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub version: u8,
+    pub algorithm: Algorithm,
+    pub kind: Kind,
+    pub parameter_set: u32,
+    pub length: u32,
+}
+
+impl Header {
+    /// Reads the frame at the start of `bytes`, checking the magic and the
+    /// version but not what follows.
+    pub fn decode(bytes: &[u8]) -> Result<Header, DecodingError> {
+        let mut reader = Reader::new(bytes, 0);
+        if reader.array::<4>().map_err(truncated)? != MAGIC {
+            return Err(DecodingError::BadMagic);
+        }
+        let [version, algorithm, kind] = reader.array::<3>().map_err(truncated)?;
+        if version != VERSION {
+            return Err(DecodingError::UnsupportedVersion { version });
+        }
+        let algorithm =
+            Algorithm::from_id(algorithm).ok_or(DecodingError::UnknownAlgorithm { algorithm })?;
+        let kind = match kind {
+            1 => Kind::PublicKey,
+            2 => Kind::Signature,
+            3 => Kind::KeyIdentifiedSignature,
+            _ => return Err(DecodingError::UnknownKind { kind }),
+        };
+        let parameter_set = u32::from_be_bytes(reader.array().map_err(truncated)?);
+        let length = u32::from_be_bytes(reader.array().map_err(truncated)?);
+        Ok(Header {
+            version,
+            algorithm,
+            kind,
+            parameter_set,
+            length,
+        })
+    }
+}
+
+fn truncated(e: crate::merkle::ProofDecodingError) -> DecodingError {
+    match e {
+        crate::merkle::ProofDecodingError::NotEnoughInput { offset, needed } => {
+            DecodingError::NotEnoughInput { offset, needed }
+        }
+        _ => unreachable!("only running out of input can fail a read"),
+    }
+}
+
+/// A type with a framed encoding.
+pub(crate) trait Framed: Sized {
+    const ALGORITHM: Algorithm;
+    const KIND: Kind;
+
+    fn parameter_set(&self) -> u32;
+
+    /// The unframed encoding.
+    fn body(&self) -> Vec<u8>;
+
+    /// Decodes the unframed encoding made with `parameter_set`.
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, DecodingError>;
+
+    /// Decodes an encoding written before framing, if there is one which
+    /// names everything needed to read it.
+    fn from_legacy(_bytes: &[u8]) -> Option<Self> {
+        None
+    }
+}
+
+pub(crate) fn to_bytes<T: Framed>(value: &T) -> Vec<u8> {
+    frame(value, T::KIND, &[])
+}
+
+/// Frames a signature as [`Kind::KeyIdentifiedSignature`], its body
+/// prefixed by `key_id`.
+pub(crate) fn to_bytes_with_key_id<T: Framed>(value: &T, key_id: &Fingerprint) -> Vec<u8> {
+    debug_assert_eq!(T::KIND, Kind::Signature);
+    frame(value, Kind::KeyIdentifiedSignature, key_id.as_bytes())
+}
+
+fn frame<T: Framed>(value: &T, kind: Kind, prefix: &[u8]) -> Vec<u8> {
+    let body = value.body();
+    let length = prefix.len() + body.len();
+    let mut output = Vec::with_capacity(HEADER_LEN + length);
+    output.extend_from_slice(&MAGIC);
+    output.extend([VERSION, T::ALGORITHM as u8, kind as u8]);
+    output.extend(value.parameter_set().to_be_bytes());
+    output.extend((length as u32).to_be_bytes());
+    output.extend_from_slice(prefix);
+    output.extend(body);
+    output
+}
+
+/// The key ID embedded in a framed signature, or [`None`] if it carries
+/// none. Only the header and the ID are read, so this is cheap to call
+/// before choosing the key to decode and verify the signature with.
+pub fn key_id(bytes: &[u8]) -> Result<Option<Fingerprint>, DecodingError> {
+    if Header::decode(bytes)?.kind != Kind::KeyIdentifiedSignature {
+        return Ok(None);
+    }
+    let key_id = Reader::new(bytes, HEADER_LEN)
+        .array::<32>()
+        .map_err(truncated)?;
+    Ok(Some(Fingerprint::from(key_id)))
+}
+
+/// Decodes a framed encoding, or failing that a legacy one. Legacy blobs
+/// almost never start with the magic, but one that does still decodes if
+/// it fails to parse as a frame.
+pub(crate) fn from_bytes<T: Framed>(bytes: &[u8]) -> Result<T, DecodingError> {
+    let framed = Header::decode(bytes).and_then(|header| {
+        let kind = match header.kind {
+            Kind::KeyIdentifiedSignature => Kind::Signature,
+            kind => kind,
+        };
+        if header.algorithm != T::ALGORITHM || kind != T::KIND {
+            return Err(DecodingError::WrongType {
+                algorithm: header.algorithm,
+                kind: header.kind,
+            });
+        }
+        let body = &bytes[HEADER_LEN..];
+        if body.len() != header.length as usize {
+            return Err(DecodingError::LengthMismatch {
+                declared: header.length,
+                actual: body.len(),
+            });
+        }
+        let body = match header.kind {
+            Kind::KeyIdentifiedSignature => {
+                body.get(32..)
+                    .ok_or_else(|| DecodingError::NotEnoughInput {
+                        offset: bytes.len(),
+                        needed: 32 - body.len(),
+                    })?
+            }
+            _ => body,
+        };
+        T::from_body(header.parameter_set, body)
+    });
+    framed.or_else(|e| T::from_legacy(bytes).ok_or(e))
+}
+
+/// Writes `bytes` as lowercase hex, for the `Display` impls of keys and
+/// signatures.
+pub(crate) fn write_hex(f: &mut std::fmt::Formatter<'_>, bytes: &[u8]) -> std::fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+}
+
+/// Parses hex digits of either case, as written by [`write_hex`].
+pub(crate) fn from_hex(s: &str) -> Result<Vec<u8>, DecodingError> {
+    let digits = s
+        .chars()
+        .enumerate()
+        .map(|(offset, c)| c.to_digit(16).ok_or(DecodingError::InvalidHex { offset }))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return Err(DecodingError::InvalidHex {
+            offset: digits.len(),
+        });
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4 | pair[1]) as u8)
+        .collect())
+}
+
+/// Wraps an error from decoding a body.
+pub(crate) fn invalid_body<E: std::error::Error + Send + Sync + 'static>(e: E) -> DecodingError {
+    DecodingError::InvalidBody(Box::new(e))
+}
+
+/// An error encountered while decoding a framed key or signature.
+#[derive(Debug)]
+pub enum DecodingError {
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput {
+        offset: usize,
+        needed: usize,
+    },
+    /// The input does not start with [`MAGIC`], and is not a legacy
+    /// encoding either.
+    BadMagic,
+    UnsupportedVersion {
+        version: u8,
+    },
+    UnknownAlgorithm {
+        algorithm: u8,
+    },
+    UnknownKind {
+        kind: u8,
+    },
+    /// The frame holds a well-formed encoding of some other type.
+    WrongType {
+        algorithm: Algorithm,
+        kind: Kind,
+    },
+    /// The frame names a parameter set which is unknown, disabled, or
+    /// differs from the one the type or its body fixes.
+    InvalidParameterSet {
+        parameter_set: u32,
+    },
+    /// The frame declares a body of `declared` bytes, but `actual` follow.
+    LengthMismatch {
+        declared: u32,
+        actual: usize,
+    },
+    /// The character at `offset` of a hex string is not a hex digit, or the
+    /// string has an odd number of digits and `offset` is its length.
+    InvalidHex {
+        offset: usize,
+    },
+    /// The body could not be decoded. Offsets within the error are relative
+    /// to the start of the body, [`HEADER_LEN`] bytes in, or for
+    /// [`Kind::KeyIdentifiedSignature`] to the end of the key ID.
+    InvalidBody(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl std::fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "frame truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            DecodingError::BadMagic => write!(f, "not a framed or legacy encoding"),
+            DecodingError::UnsupportedVersion { version } => {
+                write!(f, "unsupported frame version {version}")
+            }
+            DecodingError::UnknownAlgorithm { algorithm } => {
+                write!(f, "unknown algorithm {algorithm}")
+            }
+            DecodingError::UnknownKind { kind } => write!(f, "unknown kind {kind}"),
+            DecodingError::WrongType { algorithm, kind } => {
+                write!(f, "unexpected {algorithm:?} {kind:?}")
+            }
+            DecodingError::InvalidParameterSet { parameter_set } => {
+                write!(f, "invalid parameter set {parameter_set:#010x}")
+            }
+            DecodingError::LengthMismatch { declared, actual } => write!(
+                f,
+                "frame declares {declared} bytes, but {actual} bytes follow it"
+            ),
+            DecodingError::InvalidHex { offset } => {
+                write!(f, "invalid hex digit at offset {offset}")
+            }
+            DecodingError::InvalidBody(e) => write!(f, "invalid body: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodingError::InvalidBody(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lamport, merkle};
+
+    #[test]
+    fn frames_round_trip() {
+        let mut private_key = merkle::PrivateKey::generate(2).unwrap();
+        let public_key = private_key.public_key();
+        let signature = private_key.sign(b"hello").unwrap();
+
+        let encoded = public_key.to_bytes();
+        let header = Header::decode(&encoded).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                version: VERSION,
+                algorithm: Algorithm::Merkle,
+                kind: Kind::PublicKey,
+                parameter_set: 1,
+                length: 40,
+            }
+        );
+        assert_eq!(
+            <merkle::PublicKey>::from_bytes(&encoded).unwrap(),
+            public_key
+        );
+
+        let encoded = signature.to_bytes();
+        assert_eq!(Header::decode(&encoded).unwrap().kind, Kind::Signature);
+        let decoded = <merkle::Signature>::from_bytes(&encoded).unwrap();
+        assert!(public_key.verify(b"hello", &decoded));
+
+        let lamport_key = lamport::PrivateKey::generate().unwrap();
+        let lamport_public_key = lamport_key.public_key();
+        let encoded = lamport_public_key.to_bytes();
+        assert_eq!(encoded.len(), HEADER_LEN + 16384);
+        assert_eq!(
+            <lamport::PublicKey>::from_bytes(&encoded).unwrap(),
+            lamport_public_key
+        );
+    }
+
+    #[test]
+    fn reads_legacy_encodings() {
+        let mut private_key = merkle::PrivateKey::generate(2).unwrap();
+        let public_key = private_key.public_key();
+        let signature = private_key.sign(b"hello").unwrap();
+
+        let raw = public_key.to_raw_bytes();
+        assert_eq!(<merkle::PublicKey>::from_bytes(&raw).unwrap(), public_key);
+        let raw = Vec::from(&signature);
+        let decoded = <merkle::Signature>::from_bytes(&raw).unwrap();
+        assert!(public_key.verify(b"hello", &decoded));
+
+        let lamport_key = lamport::PrivateKey::generate().unwrap();
+        let lamport_public_key = lamport_key.public_key();
+        assert_eq!(
+            <lamport::PublicKey>::from_bytes(&lamport_public_key.to_raw_bytes()).unwrap(),
+            lamport_public_key
+        );
+    }
+
+    #[test]
+    fn signatures_carry_key_ids() {
+        let mut private_key = merkle::PrivateKey::generate(2).unwrap();
+        let public_key = private_key.public_key();
+        let signature = private_key.sign(b"hello").unwrap();
+
+        let encoded = signature.to_bytes_with_key_id(&public_key.fingerprint());
+        assert_eq!(
+            Header::decode(&encoded).unwrap().kind,
+            Kind::KeyIdentifiedSignature
+        );
+        assert_eq!(key_id(&encoded).unwrap(), Some(public_key.fingerprint()));
+        let decoded = <merkle::Signature>::from_bytes(&encoded).unwrap();
+        assert_eq!(decoded, signature);
+        assert_eq!(key_id(&signature.to_bytes()).unwrap(), None);
+        assert_eq!(key_id(&public_key.to_bytes()).unwrap(), None);
+        assert!(matches!(
+            <merkle::PublicKey>::from_bytes(&encoded),
+            Err(DecodingError::WrongType { .. })
+        ));
+        assert!(matches!(
+            key_id(&encoded[..HEADER_LEN + 31]),
+            Err(DecodingError::NotEnoughInput { needed: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_frames() {
+        let public_key = merkle::PrivateKey::generate(2).unwrap().public_key();
+        let encoded = public_key.to_bytes();
+
+        let mut bad_magic = encoded.clone();
+        bad_magic[0] ^= 1;
+        assert!(matches!(
+            <merkle::PublicKey>::from_bytes(&bad_magic),
+            Err(DecodingError::BadMagic)
+        ));
+
+        let mut future = encoded.clone();
+        future[4] = VERSION + 1;
+        assert!(matches!(
+            <merkle::PublicKey>::from_bytes(&future),
+            Err(DecodingError::UnsupportedVersion { version: 2 })
+        ));
+
+        assert!(matches!(
+            <merkle::Signature>::from_bytes(&encoded),
+            Err(DecodingError::WrongType {
+                algorithm: Algorithm::Merkle,
+                kind: Kind::PublicKey,
+            })
+        ));
+
+        let mut truncated = encoded.clone();
+        truncated.pop();
+        assert!(matches!(
+            <merkle::PublicKey>::from_bytes(&truncated),
+            Err(DecodingError::LengthMismatch {
+                declared: 40,
+                actual: 39,
+            })
+        ));
+
+        let mut other_hash = encoded.clone();
+        other_hash[10] = 0xff;
+        assert!(matches!(
+            <merkle::PublicKey>::from_bytes(&other_hash),
+            Err(DecodingError::InvalidParameterSet {
+                parameter_set: 0xff
+            })
+        ));
+
+        assert!(matches!(
+            Header::decode(&encoded[..HEADER_LEN - 1]),
+            Err(DecodingError::NotEnoughInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,1150 @@
+use blake3::Hash;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+use crate::hash::Family;
+use crate::merkle::internal::{ProofDecodingError, Reader};
+use crate::merkle::seeded::{Leaves, Traversal};
+use crate::state::{self, Reservation, SigningError, StateStore};
+use crate::stateless::read_bits;
+use crate::wire::{self, Algorithm, Framed, Kind};
+
+pub use crate::merkle::seeded::KeyDecodingError;
+
+/// The Winternitz parameter of WOTS+, the same for every parameter set.
+const W: u32 = 16;
+/// The number of hash chains covering the message digest.
+const LEN_1: usize = 64;
+/// The number of hash chains covering the checksum.
+const LEN_2: usize = 3;
+/// The number of hash chains of a WOTS+ key.
+const LEN: usize = LEN_1 + LEN_2;
+
+/// An XMSS parameter set: the hash function and the height of the tree, a
+/// key signing `2^height` messages. Every set uses 32-byte hashes and WOTS+
+/// with `w = 16`.
+///
+/// The SHA-256 sets are XMSS-SHA2_10_256, XMSS-SHA2_16_256 and
+/// XMSS-SHA2_20_256, carrying their RFC 8391 OIDs, and require the `sha2`
+/// feature. The BLAKE3 sets use OIDs from the range the RFC reserves for
+/// private use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum XmssType {
+    #[cfg(feature = "sha2")]
+    Sha256H10,
+    #[cfg(feature = "sha2")]
+    Sha256H16,
+    #[cfg(feature = "sha2")]
+    Sha256H20,
+    Blake3H10,
+    Blake3H16,
+    Blake3H20,
+}
+
+impl XmssType {
+    const ALL: &'static [XmssType] = &[
+        #[cfg(feature = "sha2")]
+        XmssType::Sha256H10,
+        #[cfg(feature = "sha2")]
+        XmssType::Sha256H16,
+        #[cfg(feature = "sha2")]
+        XmssType::Sha256H20,
+        XmssType::Blake3H10,
+        XmssType::Blake3H16,
+        XmssType::Blake3H20,
+    ];
+
+    fn fields(self) -> (u32, Family, u8) {
+        match self {
+            #[cfg(feature = "sha2")]
+            XmssType::Sha256H10 => (1, Family::Sha256, 10),
+            #[cfg(feature = "sha2")]
+            XmssType::Sha256H16 => (2, Family::Sha256, 16),
+            #[cfg(feature = "sha2")]
+            XmssType::Sha256H20 => (3, Family::Sha256, 20),
+            XmssType::Blake3H10 => (0xE000_0001, Family::Blake3, 10),
+            XmssType::Blake3H16 => (0xE000_0002, Family::Blake3, 16),
+            XmssType::Blake3H20 => (0xE000_0003, Family::Blake3, 20),
+        }
+    }
+
+    pub fn oid(self) -> u32 {
+        self.fields().0
+    }
+
+    /// The parameter set with `oid`, if it is known and enabled.
+    pub fn from_oid(oid: u32) -> Option<XmssType> {
+        XmssType::ALL
+            .iter()
+            .copied()
+            .find(|xmss_type| xmss_type.oid() == oid)
+    }
+
+    fn family(self) -> Family {
+        self.fields().1
+    }
+
+    pub fn height(self) -> u8 {
+        self.fields().2
+    }
+
+    /// The number of messages a key can sign.
+    pub fn num_items(self) -> u64 {
+        1 << self.height()
+    }
+
+    /// The length in bytes of every encoded signature.
+    pub fn signature_len(self) -> usize {
+        4 + 32 + LEN * 32 + self.height() as usize * 32
+    }
+}
+
+/// A hash function address, as in section 2.5 of RFC 8391, for a key with a
+/// single tree: the layer and tree words are always zero.
+#[derive(Clone, Copy, Debug, Default)]
+struct Address([u8; 32]);
+
+impl Address {
+    const OTS: u32 = 0;
+    const L_TREE: u32 = 1;
+    const HASH_TREE: u32 = 2;
+
+    /// The OTS key pair or L-tree index, unused by hash tree addresses.
+    const KEY_PAIR: usize = 4;
+    /// The chain index, or the height of a tree node.
+    const CHAIN: usize = 5;
+    /// The step within a chain, or the index of a tree node within its
+    /// level.
+    const HASH: usize = 6;
+    const KEY_AND_MASK: usize = 7;
+
+    fn new(kind: u32) -> Address {
+        let mut address = Address::default();
+        address.set(3, kind);
+        address
+    }
+
+    fn set(&mut self, word: usize, value: u32) {
+        self.0[4 * word..4 * word + 4].copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// The hash functions of section 5.1 of RFC 8391, told apart by the 32-byte
+/// big-endian value of `tag` which prefixes their input: 0 for `F`, 1 for
+/// `H`, 2 for `H_msg` and 3 for `PRF`.
+fn tagged_hash(family: Family, tag: u8, key: &[u8], message: &[&[u8]]) -> [u8; 32] {
+    let mut padding = [0u8; 32];
+    padding[31] = tag;
+    let mut inputs: Vec<&[u8]> = vec![&padding, key];
+    inputs.extend_from_slice(message);
+    family.hash(&inputs)
+}
+
+fn prf(family: Family, key: &[u8; 32], address: &Address) -> [u8; 32] {
+    tagged_hash(family, 3, key, &[&address.0])
+}
+
+fn xor(value: &[u8; 32], mask: &[u8; 32]) -> [u8; 32] {
+    std::array::from_fn(|i| value[i] ^ mask[i])
+}
+
+/// Applies the given `steps` of the WOTS+ chain at `address` to `value`, as
+/// in algorithm 2 of RFC 8391.
+fn chain(
+    family: Family,
+    public_seed: &[u8; 32],
+    address: &mut Address,
+    steps: std::ops::Range<u32>,
+    mut value: [u8; 32],
+) -> [u8; 32] {
+    for step in steps {
+        address.set(Address::HASH, step);
+        address.set(Address::KEY_AND_MASK, 0);
+        let key = prf(family, public_seed, address);
+        address.set(Address::KEY_AND_MASK, 1);
+        let mask = prf(family, public_seed, address);
+        value = tagged_hash(family, 0, &key, &[&xor(&value, &mask)]);
+    }
+    value
+}
+
+/// `RAND_HASH` of section 4.1.4 of RFC 8391, combining two nodes of an
+/// L-tree or hash tree.
+fn rand_hash(
+    family: Family,
+    public_seed: &[u8; 32],
+    address: &mut Address,
+    left: &[u8; 32],
+    right: &[u8; 32],
+) -> [u8; 32] {
+    address.set(Address::KEY_AND_MASK, 0);
+    let key = prf(family, public_seed, address);
+    address.set(Address::KEY_AND_MASK, 1);
+    let left_mask = prf(family, public_seed, address);
+    address.set(Address::KEY_AND_MASK, 2);
+    let right_mask = prf(family, public_seed, address);
+    tagged_hash(
+        family,
+        1,
+        &key,
+        &[&xor(left, &left_mask), &xor(right, &right_mask)],
+    )
+}
+
+/// The number of chain steps signing each base-16 digit of `digest`,
+/// followed by those of its checksum.
+fn wots_coefficients(digest: &[u8; 32]) -> Vec<u32> {
+    let digit = |bytes: &[u8], i: usize| read_bits(bytes, i * 4, 4) as u32;
+    let mut coefficients: Vec<u32> = (0..LEN_1).map(|i| digit(digest, i)).collect();
+    let checksum: u32 = coefficients.iter().map(|c| W - 1 - c).sum();
+    let checksum = ((checksum << 4) as u16).to_be_bytes();
+    coefficients.extend((0..LEN_2).map(|i| digit(&checksum, i)));
+    coefficients
+}
+
+/// The secret starting chain `chain` of the WOTS+ key at leaf `index`. RFC
+/// 8391 leaves their derivation to implementations, and these are `PRF`
+/// outputs under the secret seed.
+fn wots_secret(family: Family, secret_seed: &[u8; 32], index: u32, chain: u32) -> [u8; 32] {
+    let mut address = Address::new(Address::OTS);
+    address.set(Address::KEY_PAIR, index);
+    address.set(Address::CHAIN, chain);
+    prf(family, secret_seed, &address)
+}
+
+/// Runs chain `i` of the WOTS+ key at leaf `index` through the `i`th of
+/// `steps`, starting from the `i`th of `values`.
+fn wots_chains(
+    family: Family,
+    public_seed: &[u8; 32],
+    index: u32,
+    values: impl Iterator<Item = [u8; 32]>,
+    steps: impl Iterator<Item = std::ops::Range<u32>>,
+) -> Vec<[u8; 32]> {
+    let mut address = Address::new(Address::OTS);
+    address.set(Address::KEY_PAIR, index);
+    values
+        .zip(steps)
+        .zip(0u32..)
+        .map(|((value, steps), i)| {
+            address.set(Address::CHAIN, i);
+            chain(family, public_seed, &mut address, steps, value)
+        })
+        .collect()
+}
+
+/// Compresses the WOTS+ public key of leaf `index` into one node, as in
+/// algorithm 8 of RFC 8391.
+fn l_tree(family: Family, public_seed: &[u8; 32], index: u32, mut nodes: Vec<[u8; 32]>) -> Hash {
+    let mut address = Address::new(Address::L_TREE);
+    address.set(Address::KEY_PAIR, index);
+    let mut height = 0;
+    while nodes.len() > 1 {
+        address.set(Address::CHAIN, height);
+        nodes = nodes
+            .chunks(2)
+            .zip(0u32..)
+            .map(|(pair, i)| match pair {
+                [left, right] => {
+                    address.set(Address::HASH, i);
+                    rand_hash(family, public_seed, &mut address, left, right)
+                }
+                _ => pair[0],
+            })
+            .collect();
+        height += 1;
+    }
+    Hash::from(nodes[0])
+}
+
+/// The node combining `left` and `right` into the node at `index` of the
+/// hash tree level `level`, counting from the leaves.
+fn tree_node(
+    family: Family,
+    public_seed: &[u8; 32],
+    level: usize,
+    index: u64,
+    left: &Hash,
+    right: &Hash,
+) -> Hash {
+    let mut address = Address::new(Address::HASH_TREE);
+    address.set(Address::CHAIN, level as u32 - 1);
+    address.set(Address::HASH, index as u32);
+    Hash::from(rand_hash(
+        family,
+        public_seed,
+        &mut address,
+        left.as_bytes(),
+        right.as_bytes(),
+    ))
+}
+
+/// The leaves of an XMSS tree, regenerated from its seeds.
+struct XmssLeaves<'a> {
+    xmss_type: XmssType,
+    secret_seed: &'a [u8; 32],
+    public_seed: &'a [u8; 32],
+}
+
+impl Leaves for XmssLeaves<'_> {
+    fn leaf(&self, index: u64) -> Hash {
+        let family = self.xmss_type.family();
+        let index = index as u32;
+        let secrets = (0..LEN as u32).map(|i| wots_secret(family, self.secret_seed, index, i));
+        let public_key = wots_chains(
+            family,
+            self.public_seed,
+            index,
+            secrets,
+            std::iter::repeat(0..W - 1),
+        );
+        l_tree(family, self.public_seed, index, public_key)
+    }
+
+    fn parent(&self, level: usize, index: u64, left: &Hash, right: &Hash) -> Hash {
+        let family = self.xmss_type.family();
+        tree_node(family, self.public_seed, level, index, left, right)
+    }
+}
+
+/// The digest `H_msg` signed by the WOTS+ key at leaf `index`.
+fn message_digest(
+    family: Family,
+    randomness: &[u8; 32],
+    root: &Hash,
+    index: u32,
+    message: &[u8],
+) -> [u8; 32] {
+    let mut key = [0u8; 96];
+    key[..32].copy_from_slice(randomness);
+    key[32..64].copy_from_slice(root.as_bytes());
+    key[92..].copy_from_slice(&index.to_be_bytes());
+    tagged_hash(family, 2, &key, &[message])
+}
+
+/// An XMSS private key: the secret seeds of its WOTS+ keys and its message
+/// randomness, the public seed, and the state of the traversal shared with
+/// [`crate::merkle::seeded::PrivateKey`], which keeps only `O(log n)` nodes.
+///
+/// [`PrivateKey::to_bytes`] writes the OID, the index of the next leaf, the
+/// secret seed, the PRF key, the root and the public seed, as the RFC's
+/// reference implementation does, followed by the traversal state.
+///
+/// Like a seeded Merkle key, it keeps the seeds of used leaves, so it has no
+/// forward security.
+#[derive(PartialEq, Eq)]
+pub struct PrivateKey {
+    xmss_type: XmssType,
+    secret_seed: [u8; 32],
+    prf_key: [u8; 32],
+    public_seed: [u8; 32],
+    traversal: Traversal,
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("xmss_type", &self.xmss_type)
+            .field("next_index", &self.current_index())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.secret_seed.zeroize();
+        self.prf_key.zeroize();
+    }
+}
+
+impl PrivateKey {
+    pub fn generate(xmss_type: XmssType) -> Result<PrivateKey, rand::Error> {
+        let mut seeds = [[0u8; 32]; 3];
+        for seed in seeds.iter_mut() {
+            OsRng.try_fill_bytes(seed)?;
+        }
+        let [secret_seed, prf_key, public_seed] = seeds;
+        Ok(PrivateKey::from_seeds(
+            xmss_type,
+            secret_seed,
+            prf_key,
+            public_seed,
+        ))
+    }
+
+    /// Builds the key whose WOTS+ keys are derived from `secret_seed` and
+    /// whose message randomness is derived from `prf_key`. This computes
+    /// every leaf once, so it takes time proportional to the number of
+    /// messages the key can sign.
+    pub fn from_seeds(
+        xmss_type: XmssType,
+        secret_seed: [u8; 32],
+        prf_key: [u8; 32],
+        public_seed: [u8; 32],
+    ) -> PrivateKey {
+        let leaves = XmssLeaves {
+            xmss_type,
+            secret_seed: &secret_seed,
+            public_seed: &public_seed,
+        };
+        let traversal =
+            Traversal::new(&leaves, xmss_type.num_items()).expect("XMSS trees are never empty");
+        PrivateKey {
+            xmss_type,
+            secret_seed,
+            prf_key,
+            public_seed,
+            traversal,
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            xmss_type: self.xmss_type,
+            root: self.traversal.root(),
+            public_seed: self.public_seed,
+        }
+    }
+
+    /// The index of the leaf the next signature will use.
+    pub fn current_index(&self) -> u64 {
+        self.traversal.next_index()
+    }
+
+    pub fn num_items(&self) -> u64 {
+        self.traversal.num_items()
+    }
+
+    /// Signs with the next unused leaf, as in algorithm 12 of RFC 8391,
+    /// returning [`None`] once every leaf has been used.
+    pub fn sign<A: AsRef<[u8]>>(&mut self, message: A) -> Option<Signature> {
+        let next = self.traversal.next_index();
+        if next >= self.traversal.num_items() {
+            return None;
+        }
+        let index = next as u32;
+        let family = self.xmss_type.family();
+
+        let mut index_bytes = [0u8; 32];
+        index_bytes[28..].copy_from_slice(&index.to_be_bytes());
+        let randomness = tagged_hash(family, 3, &self.prf_key, &[&index_bytes]);
+        let digest = message_digest(
+            family,
+            &randomness,
+            &self.traversal.root(),
+            index,
+            message.as_ref(),
+        );
+        let secrets = (0..LEN as u32).map(|i| wots_secret(family, &self.secret_seed, index, i));
+        let wots = wots_chains(
+            family,
+            &self.public_seed,
+            index,
+            secrets,
+            wots_coefficients(&digest).into_iter().map(|steps| 0..steps),
+        );
+        let auth = self
+            .traversal
+            .auth_path()
+            .iter()
+            .map(|sibling| sibling.expect("XMSS trees are perfect"))
+            .collect();
+
+        let leaves = XmssLeaves {
+            xmss_type: self.xmss_type,
+            secret_seed: &self.secret_seed,
+            public_seed: &self.public_seed,
+        };
+        let leaf = leaves.leaf(next);
+        self.traversal.advance(&leaves, leaf);
+
+        Some(Signature {
+            xmss_type: self.xmss_type,
+            index,
+            randomness,
+            wots,
+            auth,
+        })
+    }
+
+    /// Signs like [`PrivateKey::sign`], but only after durably recording in
+    /// `store` that the index is used. If `store` is ahead of this key, as
+    /// it is for a key restored from an old copy, the traversal is first
+    /// advanced past the indices in between.
+    pub fn sign_persisted<S: StateStore, A: AsRef<[u8]>>(
+        &mut self,
+        store: &mut S,
+        message: A,
+    ) -> Result<Signature, SigningError<S::Error>> {
+        let mut reservation = self.reserve(store, 1)?;
+        Ok(self
+            .sign_reserved(&mut reservation, message)
+            .expect("reserved indices are in range"))
+    }
+
+    /// Durably reserves the next `count` indices in `store` with a single
+    /// write, like [`crate::merkle::PrivateKey::reserve`].
+    pub fn reserve<S: StateStore>(
+        &mut self,
+        store: &mut S,
+        count: u64,
+    ) -> Result<Reservation, SigningError<S::Error>> {
+        state::reserve(store, self.current_index(), self.num_items(), count)
+    }
+
+    /// Signs with the next index of `reservation`, which must have been
+    /// made for this key, first advancing the traversal past any indices
+    /// skipped to reach it. Returns [`None`] once the block is used up.
+    pub fn sign_reserved<A: AsRef<[u8]>>(
+        &mut self,
+        reservation: &mut Reservation,
+        message: A,
+    ) -> Option<Signature> {
+        let index = reservation.take_from(self.current_index())?;
+        let leaves = XmssLeaves {
+            xmss_type: self.xmss_type,
+            secret_seed: &self.secret_seed,
+            public_seed: &self.public_seed,
+        };
+        while self.traversal.next_index() < index {
+            let leaf = leaves.leaf(self.traversal.next_index());
+            self.traversal.advance(&leaves, leaf);
+        }
+        self.sign(message)
+    }
+
+    /// Encodes the whole signing state, including the secret seeds.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend(self.xmss_type.oid().to_be_bytes());
+        output.extend((self.traversal.next_index() as u32).to_be_bytes());
+        output.extend_from_slice(&self.secret_seed);
+        output.extend_from_slice(&self.prf_key);
+        output.extend_from_slice(self.traversal.root().as_bytes());
+        output.extend_from_slice(&self.public_seed);
+        self.traversal.encode(&mut output);
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for PrivateKey {
+    type Error = KeyDecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let offset = reader.offset();
+        let xmss_type = XmssType::from_oid(u32::from_be_bytes(reader.array()?))
+            .ok_or(KeyDecodingError::InvalidField { offset })?;
+        let index_offset = reader.offset();
+        let index = u32::from_be_bytes(reader.array()?);
+        let secret_seed = reader.array()?;
+        let prf_key = reader.array()?;
+        let root_offset = reader.offset();
+        let root = Hash::from(reader.array::<32>()?);
+        let public_seed = reader.array()?;
+        let offset = reader.offset();
+        let traversal = Traversal::decode(&mut reader)?;
+        reader.finish()?;
+
+        if traversal.num_items() != xmss_type.num_items() {
+            return Err(KeyDecodingError::InvalidField { offset });
+        }
+        if traversal.next_index() != index as u64 {
+            return Err(KeyDecodingError::InvalidField {
+                offset: index_offset,
+            });
+        }
+        if traversal.root() != root {
+            return Err(KeyDecodingError::InvalidField {
+                offset: root_offset,
+            });
+        }
+        Ok(PrivateKey {
+            xmss_type,
+            secret_seed,
+            prf_key,
+            public_seed,
+            traversal,
+        })
+    }
+}
+
+/// An XMSS public key, encoded as in section 4.1.7 of RFC 8391: the OID,
+/// the root and the public seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PublicKey {
+    xmss_type: XmssType,
+    root: Hash,
+    public_seed: [u8; 32],
+}
+
+impl PublicKey {
+    pub fn xmss_type(&self) -> XmssType {
+        self.xmss_type
+    }
+
+    /// Verifies `signature` as in algorithm 14 of RFC 8391.
+    pub fn verify<A: AsRef<[u8]>>(&self, message: A, signature: &Signature) -> bool {
+        let height = self.xmss_type.height() as usize;
+        if signature.xmss_type != self.xmss_type
+            || signature.index as u64 >= self.xmss_type.num_items()
+            || signature.auth.len() != height
+        {
+            return false;
+        }
+        let family = self.xmss_type.family();
+        let digest = message_digest(
+            family,
+            &signature.randomness,
+            &self.root,
+            signature.index,
+            message.as_ref(),
+        );
+        let public_key = wots_chains(
+            family,
+            &self.public_seed,
+            signature.index,
+            signature.wots.iter().copied(),
+            wots_coefficients(&digest)
+                .into_iter()
+                .map(|steps| steps..W - 1),
+        );
+
+        let mut node = l_tree(family, &self.public_seed, signature.index, public_key);
+        for (level, sibling) in signature.auth.iter().enumerate() {
+            let index = (signature.index as u64) >> level;
+            node = if index.is_multiple_of(2) {
+                tree_node(
+                    family,
+                    &self.public_seed,
+                    level + 1,
+                    index / 2,
+                    &node,
+                    sibling,
+                )
+            } else {
+                tree_node(
+                    family,
+                    &self.public_seed,
+                    level + 1,
+                    index / 2,
+                    sibling,
+                    &node,
+                )
+            };
+        }
+        node == self.root
+    }
+
+    /// The framed encoding, whose body is the RFC 8391 encoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding, or else an RFC 8391 one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    /// The RFC 8391 encoding.
+    pub fn to_raw_bytes(&self) -> [u8; 68] {
+        let mut output = [0u8; 68];
+        output[..4].copy_from_slice(&self.xmss_type.oid().to_be_bytes());
+        output[4..36].copy_from_slice(self.root.as_bytes());
+        output[36..].copy_from_slice(&self.public_seed);
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = DecodingError;
+
+    fn try_from(encoded: &[u8]) -> Result<Self, Self::Error> {
+        let mut reader = Reader::new(encoded, 0);
+        let offset = reader.offset();
+        let oid = u32::from_be_bytes(reader.array()?);
+        let xmss_type = XmssType::from_oid(oid).ok_or(DecodingError::UnknownOid { offset, oid })?;
+        let root = Hash::from(reader.array::<32>()?);
+        let public_seed = reader.array()?;
+        reader.finish()?;
+        Ok(PublicKey {
+            xmss_type,
+            root,
+            public_seed,
+        })
+    }
+}
+
+/// An XMSS signature, encoded as in section 4.1.8 of RFC 8391: the leaf
+/// index, the message randomness, the WOTS+ signature and the
+/// authentication path. The encoding does not name its parameter set, so
+/// decoding takes it from the public key, or from the frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    xmss_type: XmssType,
+    index: u32,
+    randomness: [u8; 32],
+    wots: Vec<[u8; 32]>,
+    auth: Vec<Hash>,
+}
+
+impl Signature {
+    /// The index of the leaf which produced this signature.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The framed encoding, which names the parameter set.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::to_bytes(self)
+    }
+
+    /// Decodes a framed encoding. Unframed encodings need
+    /// [`Signature::decode`], since they do not name their parameter set.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wire::DecodingError> {
+        wire::from_bytes(bytes)
+    }
+
+    pub fn decode(encoded: &[u8], xmss_type: XmssType) -> Result<Signature, DecodingError> {
+        let mut reader = Reader::new(encoded, 0);
+        let offset = reader.offset();
+        let index = u32::from_be_bytes(reader.array()?);
+        if index as u64 >= xmss_type.num_items() {
+            return Err(DecodingError::InvalidField { offset });
+        }
+        let randomness = reader.array()?;
+        let wots = (0..LEN)
+            .map(|_| reader.array())
+            .collect::<Result<Vec<_>, _>>()?;
+        let auth = (0..xmss_type.height())
+            .map(|_| reader.array::<32>().map(Hash::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        reader.finish()?;
+        Ok(Signature {
+            xmss_type,
+            index,
+            randomness,
+            wots,
+            auth,
+        })
+    }
+}
+
+impl From<&Signature> for Vec<u8> {
+    fn from(signature: &Signature) -> Self {
+        let mut output = Vec::new();
+        output.extend(signature.index.to_be_bytes());
+        output.extend_from_slice(&signature.randomness);
+        for value in signature.wots.iter() {
+            output.extend_from_slice(value);
+        }
+        for sibling in signature.auth.iter() {
+            output.extend_from_slice(sibling.as_bytes());
+        }
+        output
+    }
+}
+
+impl Framed for PublicKey {
+    const ALGORITHM: Algorithm = Algorithm::Xmss;
+    const KIND: Kind = Kind::PublicKey;
+
+    fn parameter_set(&self) -> u32 {
+        self.xmss_type.oid()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        self.to_raw_bytes().to_vec()
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let public_key = PublicKey::try_from(body).map_err(wire::invalid_body)?;
+        if public_key.xmss_type.oid() != parameter_set {
+            return Err(wire::DecodingError::InvalidParameterSet { parameter_set });
+        }
+        Ok(public_key)
+    }
+
+    fn from_legacy(bytes: &[u8]) -> Option<Self> {
+        PublicKey::try_from(bytes).ok()
+    }
+}
+
+impl Framed for Signature {
+    const ALGORITHM: Algorithm = Algorithm::Xmss;
+    const KIND: Kind = Kind::Signature;
+
+    fn parameter_set(&self) -> u32 {
+        self.xmss_type.oid()
+    }
+
+    fn body(&self) -> Vec<u8> {
+        Vec::from(self)
+    }
+
+    fn from_body(parameter_set: u32, body: &[u8]) -> Result<Self, wire::DecodingError> {
+        let xmss_type = XmssType::from_oid(parameter_set)
+            .ok_or(wire::DecodingError::InvalidParameterSet { parameter_set })?;
+        Signature::decode(body, xmss_type).map_err(wire::invalid_body)
+    }
+}
+
+/// An error encountered while decoding an XMSS public key or signature.
+/// Every offset is the position, in bytes, within the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodingError {
+    /// `needed` more bytes were required at `offset`, but the input ended.
+    NotEnoughInput { offset: usize, needed: usize },
+    /// The OID at `offset` is unknown, or belongs to a parameter set whose
+    /// feature is disabled.
+    UnknownOid { offset: usize, oid: u32 },
+    /// The field at `offset` holds a value no signer could have written.
+    InvalidField { offset: usize },
+    /// The input ended at `offset`, but `remaining` bytes followed it.
+    TrailingBytes { offset: usize, remaining: usize },
+}
+
+impl std::fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodingError::NotEnoughInput { offset, needed } => write!(
+                f,
+                "input truncated: needed {needed} more bytes at offset {offset}"
+            ),
+            DecodingError::UnknownOid { offset, oid } => {
+                write!(f, "unknown OID {oid:#010x} at offset {offset}")
+            }
+            DecodingError::InvalidField { offset } => {
+                write!(f, "invalid field at offset {offset}")
+            }
+            DecodingError::TrailingBytes { offset, remaining } => write!(
+                f,
+                "{remaining} trailing bytes after the input ending at offset {offset}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
+impl From<ProofDecodingError> for DecodingError {
+    fn from(e: ProofDecodingError) -> Self {
+        match e {
+            ProofDecodingError::NotEnoughInput { offset, needed } => {
+                DecodingError::NotEnoughInput { offset, needed }
+            }
+            ProofDecodingError::TrailingBytes { offset, remaining } => {
+                DecodingError::TrailingBytes { offset, remaining }
+            }
+            ProofDecodingError::InvalidProofNodeType { offset, .. }
+            | ProofDecodingError::ItemTooLong { offset, .. }
+            | ProofDecodingError::TooDeep { offset, .. } => DecodingError::InvalidField { offset },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A key over a 16-leaf tree, too small for any real parameter set but
+    /// quick to build. Its signatures only verify against its own root.
+    fn small_key(seed: u8) -> PrivateKey {
+        let secret_seed = [seed; 32];
+        let public_seed = [seed + 1; 32];
+        let leaves = XmssLeaves {
+            xmss_type: XmssType::Blake3H10,
+            secret_seed: &secret_seed,
+            public_seed: &public_seed,
+        };
+        PrivateKey {
+            xmss_type: XmssType::Blake3H10,
+            secret_seed,
+            prf_key: [seed + 2; 32],
+            public_seed,
+            traversal: Traversal::new(&leaves, 16).unwrap(),
+        }
+    }
+
+    #[test]
+    fn wots_parameters() {
+        // section 5.2 of RFC 8391: len_1 = 64 and len_2 = 3 for n = 32, w = 16
+        assert_eq!(wots_coefficients(&[0u8; 32]).len(), 67);
+        // every digit zero gives the largest checksum, 64 * 15 = 0x3c0
+        assert_eq!(&wots_coefficients(&[0u8; 32])[64..], &[3, 12, 0]);
+        assert_eq!(&wots_coefficients(&[0xffu8; 32])[64..], &[0, 0, 0]);
+        for &xmss_type in XmssType::ALL {
+            assert_eq!(XmssType::from_oid(xmss_type.oid()), Some(xmss_type));
+        }
+        assert_eq!(XmssType::from_oid(0), None);
+    }
+
+    #[test]
+    fn end_to_end() {
+        let mut private_key =
+            PrivateKey::from_seeds(XmssType::Blake3H10, [1u8; 32], [2u8; 32], [3u8; 32]);
+        let public_key = private_key.public_key();
+        assert_eq!(
+            PublicKey::try_from(&public_key.to_raw_bytes()[..]),
+            Ok(public_key)
+        );
+        for i in 0..3u32 {
+            let encoded = private_key.to_bytes();
+            assert_eq!(
+                PrivateKey::try_from(&encoded[..]).as_ref(),
+                Ok(&private_key)
+            );
+            let message = i.to_be_bytes();
+            let signature = private_key.sign(message).unwrap();
+            assert_eq!(signature.index(), i);
+            assert!(public_key.verify(message, &signature));
+            assert!(!public_key.verify(b"something else", &signature));
+
+            let encoded = Vec::from(&signature);
+            assert_eq!(encoded.len(), XmssType::Blake3H10.signature_len());
+            assert_eq!(
+                Signature::decode(&encoded, XmssType::Blake3H10),
+                Ok(signature.clone())
+            );
+            assert_eq!(
+                Signature::from_bytes(&signature.to_bytes()).unwrap(),
+                signature
+            );
+        }
+        assert_eq!(
+            PublicKey::from_bytes(&public_key.to_bytes()).unwrap(),
+            public_key
+        );
+        assert_eq!(
+            PublicKey::from_bytes(&public_key.to_raw_bytes()).unwrap(),
+            public_key
+        );
+
+        let mut encoded = private_key.to_bytes();
+        encoded[7] ^= 1;
+        assert_eq!(
+            PrivateKey::try_from(&encoded[..]),
+            Err(KeyDecodingError::InvalidField { offset: 4 })
+        );
+
+        let signature = private_key.sign(b"message").unwrap();
+        let mut moved = signature.clone();
+        moved.index = 1;
+        assert!(!public_key.verify(b"message", &moved));
+        let mut bent = signature.clone();
+        bent.randomness[0] ^= 1;
+        assert!(!public_key.verify(b"message", &bent));
+
+        let encoded = Vec::from(&signature);
+        assert!(matches!(
+            Signature::decode(&encoded[..encoded.len() - 1], XmssType::Blake3H10),
+            Err(DecodingError::NotEnoughInput { .. })
+        ));
+        let mut out_of_range = encoded.clone();
+        out_of_range[..4].copy_from_slice(&1024u32.to_be_bytes());
+        assert_eq!(
+            Signature::decode(&out_of_range, XmssType::Blake3H10),
+            Err(DecodingError::InvalidField { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn auth_paths_reach_the_root() {
+        let mut private_key = small_key(4);
+        let root = private_key.traversal.root();
+        for i in 0..16u32 {
+            let signature = private_key.sign(i.to_be_bytes()).unwrap();
+            let index = signature.index as u64;
+            let leaves = XmssLeaves {
+                xmss_type: XmssType::Blake3H10,
+                secret_seed: &private_key.secret_seed,
+                public_seed: &private_key.public_seed,
+            };
+            let mut node = leaves.leaf(index);
+            for (level, sibling) in signature.auth.iter().enumerate() {
+                let (left, right) = match (index >> level) % 2 {
+                    0 => (&node, sibling),
+                    _ => (sibling, &node),
+                };
+                node = leaves.parent(level + 1, index >> (level + 1), left, right);
+            }
+            assert_eq!(node, root);
+        }
+        assert_eq!(private_key.sign(b"one too many"), None);
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_parameter_sets() {
+        assert_eq!(XmssType::Sha256H10.oid(), 1);
+        assert_eq!(XmssType::Sha256H20.oid(), 3);
+        let mut private_key =
+            PrivateKey::from_seeds(XmssType::Sha256H10, [8u8; 32], [9u8; 32], [10u8; 32]);
+        let public_key = private_key.public_key();
+        assert_eq!(&public_key.to_raw_bytes()[..4], &[0, 0, 0, 1]);
+        let signature = private_key.sign(b"message").unwrap();
+        assert!(public_key.verify(b"message", &signature));
+        assert_eq!(Vec::from(&signature).len(), 2500);
+    }
+
+    #[cfg(feature = "sha2")]
+    fn from_hex(s: &str) -> Vec<u8> {
+        wire::from_hex(&s.split_whitespace().collect::<String>()).unwrap()
+    }
+
+    /// XMSS-SHA2_10_256 from fixed seeds. The WOTS+ secrets are derived as
+    /// this crate derives them, which RFC 8391 leaves open, so the
+    /// reference implementation's keys differ; every other value was
+    /// checked against a separate implementation of the RFC's algorithms.
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_h10_known_answer() {
+        let mut private_key = PrivateKey::from_seeds(
+            XmssType::Sha256H10,
+            std::array::from_fn(|i| i as u8),
+            std::array::from_fn(|i| 0x20 + i as u8),
+            std::array::from_fn(|i| 0x40 + i as u8),
+        );
+        let public_key = private_key.public_key();
+        assert_eq!(
+            public_key.to_raw_bytes().to_vec(),
+            from_hex(
+                "00000001 15dbc89d6374a42d683eadafa865ec642347edf22ab96b96d2bb200399cf5785
+                 404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f"
+            )
+        );
+
+        // leaf 5 takes both branches of the authentication path
+        for i in 0..5 {
+            private_key.sign(format!("message {i}")).unwrap();
+        }
+        let signature = private_key.sign(b"message 5").unwrap();
+        let encoded = Vec::from(&signature);
+        assert_eq!(
+            encoded[..36],
+            from_hex("00000005 2dc27b1d1eaaecbf1d6538cce0b72c4f0c2eeaed991448d7a550639acbd45452")
+        );
+        assert_eq!(
+            Family::Sha256.hash(&[&encoded]).to_vec(),
+            from_hex("65562dc5776b5fde0a399c81b5b0f5ccc0eb686ba5e2fa8b5f6b5f34f55a6ec3")
+        );
+
+        let decoded = Signature::decode(&encoded, XmssType::Sha256H10).unwrap();
+        assert!(public_key.verify(b"message 5", &decoded));
+        assert!(!public_key.verify(b"message 4", &decoded));
+        for offset in [3, 40, 36 + 67 * 32 + 5, encoded.len() - 1] {
+            let mut tampered = encoded.clone();
+            tampered[offset] ^= 1;
+            let verifies = Signature::decode(&tampered, XmssType::Sha256H10)
+                .is_ok_and(|tampered| public_key.verify(b"message 5", &tampered));
+            assert!(!verifies, "flipping byte {offset} went unnoticed");
+        }
+    }
+
+    /// An XMSS-SHA2_10_256 signature made outside this crate, by a separate
+    /// implementation of the RFC's algorithms which derives the WOTS+
+    /// secrets of leaf `i` as `PRF(PRF(SK_SEED, toByte(i, 32)), toByte(j,
+    /// 32))`, so only decoding and verification are exercised. The vector
+    /// is not taken from the reference implementation.
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_h10_verify_only() {
+        let public_key = PublicKey::try_from(
+            &from_hex(
+                "00000001 1ba12b23b62525a992a17f6bc5fc7e71a699e9a1aa45069d941a5d499f1d3ead
+                 f4db92f51b247ab911500b1d4de3dfa6b38a0949f8f04583a107c985063de600",
+            )[..],
+        )
+        .unwrap();
+        let encoded = from_hex(
+            "000002ca
+                 30b7e2d857847263479ce44195bbbaa361e53107a17e7e87d6f4ee1324b61b65
+                 793ac700f6ed68498f8d32b92e535dba8a5f86db15d70fc68494615035308b00
+                 debd09b115aadc09802152cfeed6e3a14b396c9db0b1c45933d5feb5ca4f136e
+                 325eb71aed4441a4c4ec8bbb35073716801c186369e043c50d0008807310549d
+                 30e3dc568a5c2fe67c1d3238e9a371fa68d8e87f171fca41867c601d03b411c5
+                 a39162e345a6193fe318564a8492dae9ca29bdfcc17e18c992fb5515657f6b58
+                 ae47804747206bb1c6b5b49a869098a2ef4c5e4d2e4467d18c313c2797b12a12
+                 6c9bc25ccf73f3d179015349294fdda88781a1044db3b7e303feed9587b0965c
+                 ec256aaf7dc92bd3976089ecdb298791f17ec36ff0b6eaaac4495200d433276a
+                 c44762b722c081f7e592aeafc9d1f8cded40d11110b336e9d416989c0140a409
+                 8c2949b696c45dc1d7bf2b6b601cf8803dd62b44a8b47ef75b947ff9a6ed1b44
+                 15dbed52656acf1244cfee8dd22088393598b300e33b58e6ec5159d20defa508
+                 5f6594289aa4e4f67794fa485f27babbc2222046584c29a59b8af9f5fbe64f41
+                 7e7b625bd451f1f0afa43558d42df30d07787b3276851fa04627f6d983bb263e
+                 175cd1bb736d91f6b1a912f3b9fcdcedc944613699a72f1c88ab0cd2ba857c8e
+                 c51e605a062f089e0e24bea74c8c9d333718a7f302701688aec32eb386ae2b84
+                 16bf1ccc262b8066a37bbacf6b8a635d99d21d9700faf837b984026cc62024df
+                 b347dd248d50a26dee5e8625feaf2d9ce9b7bf15f28f7d29bf6d1cdcad1cdc21
+                 b36ddc5c8d445f6ced2ac312e739d460a3ece0e30abe49672d2844524b16bfb8
+                 de33c0826fd88a29e4e2f49d7b68f9eb45ae853895284460c401325b63671535
+                 fd59788294b4c9b85895f347f1dcd0e4569b1658a0ed61c088a02fc11c2aaa1c
+                 7d9261ae8e98cc6494d2d250f1195d0ac772eba2d216ac716b9b1633c8177a54
+                 993d39ace0b2f088b5c1ac272d350e66368c835a81e5fd846ae20be7d39a68f3
+                 b01ba469d95dd3131de831d2b180decdf8d2a2998710f8d452038956035a1ebd
+                 1d80a73c76ad2dfb9c77947029482bde5630685ecf0671b53f1fe3cae8690447
+                 1d0b67d83b8ab1a20d36146a8dc40fb4cebea039ca7db958be0348636a0fcbc4
+                 d270c3c56351f07877f2debe6ed51a53614449cfb09be5f1d0e83355548528b2
+                 5f3f4b5dcb3605b5fb6e161e83d9884358fd31eefef6fd08c93ac275f4a45ba9
+                 2f0421148c8bc07232084ce5593ca6fba4245fa737cbb9731d610dd4822ed9c7
+                 0c3f38d08ecbe903c622795a4c9f6ec8a246364145aac3cc8b2c64849362c2dd
+                 276ca6b950b7426af808c08d052b45d560ecb38d4270ad842d5542e442420502
+                 68c8b13d2a8744c710b1a16c9eca084beea44fdbff37eb60c92d50e5590c67f2
+                 defeefb725399df4cdf30858435dc54201cdb7f24b7d5eb11543fc6682990bfe
+                 b2fab397f72ca0451ab74eafd5ffe8f21910e74e171b2b78ef5582e8f99ba1b1
+                 6a604e66dfd826e46914fdc101264c1bd64dfbfae136bb3549fe101548d98010
+                 dc50e2a5dd2bd55a8e6b786395e2e132304189a22ee09bd0fd2995db3eabb37c
+                 f059f80a6d383aae7295eb819ca2a2b6f17f8b68db6fdd86ef396a231ae3fdee
+                 2898cc14437d02388e80951a8b77740f01b6f5146fe7820596570fb3d38cc3ef
+                 e8618d52870e8a1962274f39250d953e6b730bdcfbd28e6fe5a2a7a2c317ce16
+                 ca9fa52199c4d3e964f4c2dae4d754210e36636bb48324282e405db8b05b0b3b
+                 01820dcd41031bc6c049efdfe700318f8a4c8fddcc77b884f3ebbaeafc908160
+                 9677ea39a63dae45fb13db5f60a6df4b3b207154f5d8e9ebdd3fa036578ddd95
+                 31490c7a4b2c0c5346c47f605d2e557056d9becf8cb89003bced508beb671a92
+                 24ce77fc5827a88f2d4f551437997fd168441f3b7a9a587f9e13e95061048865
+                 61041db9a3f56938a1a5aa491355a90dba91259b3ddf023b90793dfe3d8d9b58
+                 ad1176ba1d63b7de30f35a3b16d7d6a270db507b083378cecfdd221b7d8a37b8
+                 80d6635cafa8a1489533029b0436a85680b6286c37d3063cdd81e7c5875ae82d
+                 bb576ced245079723303250e89edc97e79387627c190c81a2c9846ece293f2fc
+                 11f59757450edf077d7397b64c67d2f2df48475629adcd58623f3d0de9819871
+                 bf1e6e4384ed54e0da7cc21b2662e25ef47c14cb5474320c8be4e1e18f962b3d
+                 8396ba838f14429d6b525db97163c796c234cceed16e66249e018db108d50540
+                 c0364010c352e0a0d4a8a183f3ea1e7e7b6d672a0f43a6286918675dc48c3e9a
+                 753845201f1e4cae8191765f817feaaeffb04058191edc171d6d1a7c849e60e5
+                 d90084171156a8a063bc9103931530dc0b3b2cdc0686a779ad2255ef45be36a5
+                 e0ba276c5a81e6c62c60d73ac4211755fb7cb8def834739aa8987f74a7ff5e18
+                 4463a5b0e87d94e80c12a50a570f433587f42511f6bc944536af76f38aa5d06a
+                 b2254b22e1899cef686be6e624547ef718e10192a9c885035bd728516b938616
+                 6ed6d9a9dafe995b97c8175cc5ca78213888aedee9d50c077c964912cc0314be
+                 9591fe7d4d9ffb6fdf113ee4ccb1730f6abea164b13c49fe677b6d836b711081
+                 1bd52ff5fdcc11bbad9985a88715a35a6e91b593eadcbad02342c4acb23ae63f
+                 ffd2ef3506153ad7724b0b6d2dc6fcddccb658d27eddb6dfbba0ba1935e347cc
+                 fa28407e5573c3c937261c058ba2381193fc1b0200b48197a76fd288e4410135
+                 c1a21eb0a43c13dbb1523018b7b85089e383f83799af894661061791a1ed6c7c
+                 3f65ca10b79f1e2c6c095a6115ec48a85c995379e96fa2103a7764dc22666881
+                 c831d5bc80e6587bb7fc95e24c2dd20e8f04163ed3c2455843d51ea1fdb6e2e0
+                 539fba2a7daf7c071be29b5d8e59385bb0c899474913b133f4deaa4060f6ac21
+                 e7577670f86af5ff362248422a5df05bb4ffeaa20cdf5bce715d8929dc245b06
+                 bd8216f96a2f0b7ca3f742d8ff2839c4d5aab630b1509b40dbd3c0f3930e1445
+                 39de04ac5442def15d48b088f50905630588004528bd72fe8928a8335fb8c289
+                 0f1b5136169a71106a4a8d8ff09f4bcd00ff0a37c6ef06e3814af530145304b2
+                 bf76b0c885d2019cd55ed3ce19b829ad528aa575fd66b2a7569ef8ed7e2acfea
+                 3ac973d75600e8f18a78f48927a657e895b831d772187a2d8f3ddd619c070fcf
+                 4edc7fa9de66740506d19375206f748ad5b698a135f6b1ad68ed7fd8fa4cd77b
+                 e3a1393880617367dfe3016dbb459518af3b3e236cfa205ed46b637f514cb07e
+                 1cc9ed203288e4e733420695856c3c40a557801138e3e4d320f823a18114fe99
+                 d55e8a841548003b50696cd20e8d7c759b205d290be5396006a45f86475da8d8
+                 ce4eb9d69f516edfb22b883f9b4e2ca7771f6cecba468b221a8ecb2e95d13cb4
+                 c6ec07349dae5d31acdfa4d6c5abac13980ea4b4e725e9112178e884f4dca082",
+        );
+        let signature = Signature::decode(&encoded, XmssType::Sha256H10).unwrap();
+        assert_eq!(signature.index(), 714);
+        assert!(public_key.verify(b"verify-only vector", &signature));
+        assert!(!public_key.verify(b"verify-only vectors", &signature));
+        let mut tampered = encoded.clone();
+        tampered[36 + 67 * 32] ^= 1;
+        let tampered = Signature::decode(&tampered, XmssType::Sha256H10).unwrap();
+        assert!(!public_key.verify(b"verify-only vector", &tampered));
+    }
+}